@@ -0,0 +1,188 @@
+use crate::login_config::LoginConfig;
+use crate::migration::{migrate, MigrationError};
+use crate::uci::{login_config_from_uci, UciError};
+use crate::validation::{validate_config_source, ConfigValidationErrors};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// Format of a config file, detected by the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+    /// Files under `/etc/config` are managed by UCI on OpenWrt, which is read-only here.
+    Uci,
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigFileError {
+    #[error("io error")]
+    Io(#[from] io::Error),
+    #[error("invalid YAML")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("invalid TOML")]
+    TomlDeserialize(#[from] toml::de::Error),
+    #[error("failed to serialize as TOML")]
+    TomlSerialize(#[from] toml::ser::Error),
+    #[error("invalid JSON")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid UCI config")]
+    Uci(#[from] UciError),
+    #[error("writing UCI config is not supported, use `uci set` instead")]
+    UciNotWritable,
+    #[error("invalid config")]
+    Invalid(#[from] ConfigValidationErrors),
+    #[error("invalid value")]
+    InvalidValue(#[source] serde_json::Error),
+    #[error("failed to upgrade config")]
+    Migration(#[from] MigrationError),
+}
+
+/// Files under `/etc/config` are managed by UCI on OpenWrt.
+pub fn is_uci_config_file(path: &Path) -> bool {
+    path.parent() == Some(Path::new("/etc/config"))
+}
+
+impl ConfigFormat {
+    /// Unknown extensions fall back to YAML, which has always been the default.
+    pub fn from_path(path: &Path) -> Self {
+        if is_uci_config_file(path) {
+            return ConfigFormat::Uci;
+        }
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("toml") => ConfigFormat::Toml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    /// Parse into the tree shared by the formats, or `None` for UCI.
+    pub fn parse_value(self, s: &str) -> Result<Option<serde_json::Value>, ConfigFileError> {
+        Ok(match self {
+            ConfigFormat::Yaml => Some(serde_yaml::from_str(s)?),
+            ConfigFormat::Toml => Some(toml::from_str(s)?),
+            ConfigFormat::Json => Some(serde_json::from_str(s)?),
+            ConfigFormat::Uci => None,
+        })
+    }
+
+    pub fn parse(self, s: &str) -> Result<LoginConfig, ConfigFileError> {
+        self.parse_and_migrate(s).map(|(config, _)| config)
+    }
+
+    /// Parse and upgrade an older document,
+    /// also returns the version it was upgraded from if any.
+    pub fn parse_and_migrate(self, s: &str) -> Result<(LoginConfig, Option<u32>), ConfigFileError> {
+        let Some(mut value) = self.parse_value(s)? else {
+            return Ok((login_config_from_uci(s)?, None));
+        };
+        let migrated_from = migrate(&mut value)?;
+        let config = serde_json::from_value(value).map_err(ConfigFileError::InvalidValue)?;
+        Ok((config, migrated_from))
+    }
+
+    pub fn serialize(self, config: &LoginConfig) -> Result<String, ConfigFileError> {
+        Ok(match self {
+            ConfigFormat::Yaml => serde_yaml::to_string(config)?,
+            ConfigFormat::Toml => toml::to_string(config)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(config)? + "\n",
+            ConfigFormat::Uci => return Err(ConfigFileError::UciNotWritable),
+        })
+    }
+}
+
+pub fn read_config_file(path: impl AsRef<Path>) -> Result<LoginConfig, ConfigFileError> {
+    read_and_migrate_config_file(path).map(|(config, _)| config)
+}
+
+/// Read the config, also returns the version it was upgraded from if it is an older one.
+pub fn read_and_migrate_config_file(
+    path: impl AsRef<Path>,
+) -> Result<(LoginConfig, Option<u32>), ConfigFileError> {
+    let path = path.as_ref();
+    let s = fs::read_to_string(path)?;
+    let format = ConfigFormat::from_path(path);
+    format.parse_and_migrate(&s).map_err(|e| {
+        // Explain what is wrong in detail, rather than a generic serde error.
+        match validate_config_source(format, &s) {
+            Err(errors) => errors.into(),
+            Ok(_) => e,
+        }
+    })
+}
+
+/// Write the config in the format matching the file extension.
+/// The file is replaced atomically, so a running instance never reads it half written,
+/// and it is only readable by the owner, as it contains the credential.
+pub fn write_config_file(
+    path: impl AsRef<Path>,
+    config: &LoginConfig,
+) -> Result<(), ConfigFileError> {
+    let path = path.as_ref();
+    let s = ConfigFormat::from_path(path).serialize(config)?;
+    let directory = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::create_dir_all(directory)?;
+    // In the same directory, as a rename does not work across file systems.
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = directory.join(temp_name);
+    // A leftover of an interrupted write would keep its own mode.
+    let _ = fs::remove_file(&temp_path);
+    let result =
+        write_and_sync(&temp_path, s.as_bytes()).and_then(|()| fs::rename(&temp_path, path));
+    if let Err(err) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err.into());
+    }
+    // Persist the rename itself.
+    #[cfg(unix)]
+    fs::File::open(directory)?.sync_all()?;
+    Ok(())
+}
+
+fn write_and_sync(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    io::Write::write_all(&mut file, data)?;
+    file.sync_all()
+}
+
+/// Upgrade an older config file in place, returns the version it was upgraded from.
+/// If `backup` is set, the original file is kept as `<file>.v<version>.bak`.
+pub fn upgrade_config_file(
+    path: impl AsRef<Path>,
+    backup: bool,
+) -> Result<Option<(u32, Option<PathBuf>)>, ConfigFileError> {
+    let path = path.as_ref();
+    let (config, migrated_from) = read_and_migrate_config_file(path)?;
+    let Some(migrated_from) = migrated_from else {
+        return Ok(None);
+    };
+    let backup_path = if backup {
+        let mut backup_path = path.as_os_str().to_owned();
+        backup_path.push(format!(".v{}.bak", migrated_from));
+        let backup_path = PathBuf::from(backup_path);
+        fs::copy(path, &backup_path)?;
+        Some(backup_path)
+    } else {
+        None
+    };
+    write_config_file(path, &config)?;
+    Ok(Some((migrated_from, backup_path)))
+}
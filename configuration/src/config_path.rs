@@ -0,0 +1,163 @@
+use std::{
+    env, fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// Name of the config file when it is not specified explicitly.
+pub const DEFAULT_CONFIG_FILE_NAME: &str = "njupt_wifi.yml";
+/// Name of the config file without the extension.
+pub const CONFIG_FILE_STEM: &str = "njupt_wifi";
+/// Extensions searched in each directory, the first existing one is used.
+pub const CONFIG_FILE_EXTENSIONS: [&str; 4] = ["yml", "yaml", "toml", "json"];
+/// Environment variable holding the full path of the config file.
+pub const CONFIG_PATH_ENV: &str = "NJUPT_WIFI_CONFIG";
+/// Name of the directory under `$XDG_CONFIG_HOME` and `/etc`.
+pub const CONFIG_DIR_NAME: &str = "njupt_wifi";
+
+/// Where a config file was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigLocation {
+    /// Specified on the command line.
+    CommandLine,
+    /// `$NJUPT_WIFI_CONFIG`
+    Environment,
+    /// `$XDG_CONFIG_HOME/njupt_wifi/`
+    UserConfigDir,
+    /// `/etc/njupt_wifi/`
+    SystemConfigDir,
+    /// The directory containing the executable.
+    ExecutableDir,
+}
+
+impl fmt::Display for ConfigLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConfigLocation::CommandLine => "command line",
+            ConfigLocation::Environment => CONFIG_PATH_ENV,
+            ConfigLocation::UserConfigDir => "user config directory",
+            ConfigLocation::SystemConfigDir => "system config directory",
+            ConfigLocation::ExecutableDir => "executable directory",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigPath {
+    pub path: PathBuf,
+    pub location: ConfigLocation,
+}
+
+impl fmt::Display for ConfigPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (from {})", self.path.display(), self.location)
+    }
+}
+
+/// `$XDG_CONFIG_HOME`, falling back to `$HOME/.config` as the XDG spec says.
+#[cfg(unix)]
+fn user_config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| Path::new(dir).is_absolute())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+}
+
+/// Where runtime state such as the login history is kept, when it is not given by systemd:
+/// `$XDG_STATE_HOME/njupt_wifi/`, `$HOME/.local/state/njupt_wifi/` or `/var/lib/njupt_wifi/`
+/// on Unix, and the directory containing the executable elsewhere.
+pub fn default_state_dir() -> io::Result<PathBuf> {
+    #[cfg(unix)]
+    {
+        let dir = env::var_os("XDG_STATE_HOME")
+            .filter(|dir| Path::new(dir).is_absolute())
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME")
+                    .filter(|home| !home.is_empty())
+                    .map(|home| Path::new(&home).join(".local").join("state"))
+            })
+            .unwrap_or_else(|| PathBuf::from("/var/lib"));
+        Ok(dir.join(CONFIG_DIR_NAME))
+    }
+    #[cfg(not(unix))]
+    {
+        executable_dir()
+    }
+}
+
+/// Candidates of the config file in the search order,
+/// with every supported extension in each directory.
+///
+/// `$NJUPT_WIFI_CONFIG` always wins if it is set,
+/// otherwise the first existing file among the others is used.
+pub fn config_candidates() -> io::Result<Vec<ConfigPath>> {
+    let mut candidates = Vec::new();
+    if let Some(path) = env::var_os(CONFIG_PATH_ENV).filter(|path| !path.is_empty()) {
+        candidates.push(ConfigPath {
+            path: PathBuf::from(path),
+            location: ConfigLocation::Environment,
+        });
+    }
+    let mut directories = Vec::new();
+    #[cfg(unix)]
+    {
+        if let Some(dir) = user_config_dir() {
+            directories.push((dir.join(CONFIG_DIR_NAME), ConfigLocation::UserConfigDir));
+        }
+        directories.push((
+            Path::new("/etc").join(CONFIG_DIR_NAME),
+            ConfigLocation::SystemConfigDir,
+        ));
+    }
+    directories.push((executable_dir()?, ConfigLocation::ExecutableDir));
+    for (dir, location) in directories {
+        candidates.extend(config_file_names().map(|file_name| ConfigPath {
+            path: dir.join(file_name),
+            location,
+        }));
+    }
+    Ok(candidates)
+}
+
+fn config_file_names() -> impl Iterator<Item = String> {
+    CONFIG_FILE_EXTENSIONS
+        .iter()
+        .map(|extension| format!("{}.{}", CONFIG_FILE_STEM, extension))
+}
+
+fn executable_dir() -> io::Result<PathBuf> {
+    let mut exe_dir = env::current_exe()?;
+    exe_dir.pop();
+    Ok(exe_dir)
+}
+
+/// The first existing config file in `dir` with any supported extension,
+/// or [`DEFAULT_CONFIG_FILE_NAME`] in it if there is none.
+pub fn find_config_in_dir(dir: impl AsRef<Path>) -> PathBuf {
+    let dir = dir.as_ref();
+    config_file_names()
+        .map(|file_name| dir.join(file_name))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| dir.join(DEFAULT_CONFIG_FILE_NAME))
+}
+
+/// Find the config file in the search order.
+///
+/// If no file exists, [`DEFAULT_CONFIG_FILE_NAME`] in the executable directory is returned,
+/// so that it can be created there as before.
+pub fn discover_config_path() -> io::Result<ConfigPath> {
+    let mut candidates = config_candidates()?;
+    if candidates[0].location == ConfigLocation::Environment {
+        return Ok(candidates.swap_remove(0));
+    }
+    match candidates
+        .iter()
+        .position(|candidate| candidate.path.is_file())
+    {
+        Some(index) => Ok(candidates.swap_remove(index)),
+        None => Ok(ConfigPath {
+            path: executable_dir()?.join(DEFAULT_CONFIG_FILE_NAME),
+            location: ConfigLocation::ExecutableDir,
+        }),
+    }
+}
@@ -1,23 +1,43 @@
 use crate::password::Password;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// ISP type: CMCC for China Mobile, CT for China Telecom, EDU for CERNET.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
 pub enum IspType {
     EDU,
     CMCC,
     CT,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(from = "UnboundCredential")]
 pub struct Credential {
     userid: String,
     password: Password,
     isp: IspType,
 }
 
+/// As read from the config, before the password is bound to the account.
+#[derive(Deserialize, JsonSchema)]
+struct UnboundCredential {
+    /// Account ID, e.g. B22999999.
+    #[schemars(length(min = 1))]
+    userid: String,
+    password: Password,
+    isp: IspType,
+}
+
+impl From<UnboundCredential> for Credential {
+    fn from(credential: UnboundCredential) -> Self {
+        Credential::new(credential.userid, credential.password, credential.isp)
+    }
+}
+
 #[allow(dead_code)]
 impl Credential {
-    pub fn new(userid: String, password: Password, isp: IspType) -> Self {
+    pub fn new(userid: String, mut password: Password, isp: IspType) -> Self {
+        password.bind_userid(&userid);
         Credential {
             userid,
             password,
@@ -1,5 +1,13 @@
+pub mod config_file;
+pub mod config_path;
 pub mod credential;
+pub mod linux_keyring;
 mod local_machine_data_protection;
 pub mod login_config;
+pub mod migration;
 pub mod password;
+pub mod schema;
+pub mod secret;
+pub mod uci;
+pub mod validation;
 mod win32_data_protection;
@@ -0,0 +1,270 @@
+#![cfg(target_os = "linux")]
+//! Keep the password in a keyring of the current user, the config only holds a reference.
+//!
+//! The freedesktop Secret Service (GNOME Keyring, KWallet, KeePassXC, ...) is used when it is
+//! available on the session bus. Otherwise, e.g. on a headless machine, the password is kept
+//! in the user keyring of the kernel, which does not survive a reboot.
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use hex::ToHex;
+use linux_keyutils::{KeyError, KeyPermissionsBuilder, KeyRing, KeyRingIdentifier, Permission};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use std::{collections::HashMap, fmt, str::FromStr};
+use thiserror::Error;
+use zbus::blocking::{fdo::DBusProxy, Connection, Proxy};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+const APPLICATION: &str = "njupt_wifi_login";
+
+const SECRET_SERVICE: &str = "org.freedesktop.secrets";
+const SECRET_SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+const COLLECTION_INTERFACE: &str = "org.freedesktop.Secret.Collection";
+const ITEM_INTERFACE: &str = "org.freedesktop.Secret.Item";
+const PROMPT_INTERFACE: &str = "org.freedesktop.Secret.Prompt";
+
+/// `(session, parameters, value, content_type)` of the Secret Service API.
+type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyringBackend {
+    SecretService,
+    KeyUtils,
+}
+
+/// Written to the config as `secret-service:<id>` or `keyutils:<id>`.
+#[derive(Debug, Clone, PartialEq, Eq, SerializeDisplay, DeserializeFromStr)]
+pub struct KeyringReference {
+    backend: KeyringBackend,
+    id: String,
+}
+
+#[derive(Error, Debug)]
+pub enum KeyringError {
+    #[error("secret service error")]
+    SecretService(#[from] zbus::Error),
+    #[error("kernel keyring error")]
+    KeyUtils(#[from] KeyError),
+    #[error("the secret service has no default collection")]
+    NoDefaultCollection,
+    #[error("unlocking the keyring was dismissed")]
+    PromptDismissed,
+    #[error("password `{0}` is not found in the keyring")]
+    NotFound(KeyringReference),
+    #[error("invalid keyring reference `{0}`, expected `secret-service:<id>` or `keyutils:<id>`")]
+    InvalidReference(String),
+}
+
+impl KeyringBackend {
+    fn as_str(self) -> &'static str {
+        match self {
+            KeyringBackend::SecretService => "secret-service",
+            KeyringBackend::KeyUtils => "keyutils",
+        }
+    }
+}
+
+impl fmt::Display for KeyringReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.backend.as_str(), self.id)
+    }
+}
+
+impl FromStr for KeyringReference {
+    type Err = KeyringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || KeyringError::InvalidReference(s.to_string());
+        let (backend, id) = s.split_once(':').ok_or_else(invalid)?;
+        let backend = match backend {
+            "secret-service" => KeyringBackend::SecretService,
+            "keyutils" => KeyringBackend::KeyUtils,
+            _ => return Err(invalid()),
+        };
+        if id.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            backend,
+            id: id.to_string(),
+        })
+    }
+}
+
+impl KeyringReference {
+    /// Store the secret under a new random id.
+    pub fn store(secret: &[u8]) -> Result<Self, KeyringError> {
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let id: String = id.encode_hex();
+        let backend = match secret_service_connection()? {
+            Some(connection) => {
+                secret_service_store(&connection, &id, secret)?;
+                KeyringBackend::SecretService
+            }
+            None => {
+                keyutils_store(&id, secret)?;
+                KeyringBackend::KeyUtils
+            }
+        };
+        Ok(Self { backend, id })
+    }
+
+    pub fn load(&self) -> Result<Vec<u8>, KeyringError> {
+        let secret = match self.backend {
+            KeyringBackend::SecretService => {
+                secret_service_load(&Connection::session()?, &self.id)?
+            }
+            KeyringBackend::KeyUtils => keyutils_load(&self.id)?,
+        };
+        secret.ok_or_else(|| KeyringError::NotFound(self.clone()))
+    }
+
+    pub fn backend(&self) -> KeyringBackend {
+        self.backend
+    }
+}
+
+/// The session bus, if the Secret Service is running or can be activated on it.
+fn secret_service_connection() -> Result<Option<Connection>, KeyringError> {
+    let Ok(connection) = Connection::session() else {
+        return Ok(None);
+    };
+    let dbus = DBusProxy::new(&connection)?;
+    let name = SECRET_SERVICE.try_into().map_err(zbus::Error::from)?;
+    let available = dbus.name_has_owner(name).map_err(zbus::Error::from)?
+        || dbus
+            .list_activatable_names()
+            .map_err(zbus::Error::from)?
+            .iter()
+            .any(|activatable| activatable.as_str() == SECRET_SERVICE);
+    Ok(available.then_some(connection))
+}
+
+fn proxy<'a>(
+    connection: &Connection,
+    path: ObjectPath<'a>,
+    interface: &'a str,
+) -> Result<Proxy<'a>, zbus::Error> {
+    Proxy::new(connection, SECRET_SERVICE, path, interface)
+}
+
+fn service(connection: &Connection) -> Result<Proxy<'static>, zbus::Error> {
+    let path = ObjectPath::from_static_str_unchecked(SECRET_SERVICE_PATH);
+    proxy(connection, path, SERVICE_INTERFACE)
+}
+
+fn attributes(id: &str) -> HashMap<&str, &str> {
+    HashMap::from([("application", APPLICATION), ("id", id)])
+}
+
+/// Secrets are transferred in plain, as the session bus is private to the user.
+fn open_session(service: &Proxy) -> Result<OwnedObjectPath, zbus::Error> {
+    let (_, session): (OwnedValue, OwnedObjectPath) =
+        service.call("OpenSession", &("plain", Value::from("")))?;
+    Ok(session)
+}
+
+/// Let the Secret Service ask the user, e.g. to unlock the keyring.
+fn complete_prompt(connection: &Connection, prompt: &ObjectPath) -> Result<(), KeyringError> {
+    if prompt.as_str() == "/" {
+        return Ok(());
+    }
+    let prompt = proxy(connection, prompt.to_owned(), PROMPT_INTERFACE)?;
+    let mut completed = prompt.receive_signal("Completed")?;
+    prompt.call_method("Prompt", &("",))?;
+    let Some(message) = completed.next() else {
+        return Err(KeyringError::PromptDismissed);
+    };
+    let (dismissed, _): (bool, OwnedValue) = message.body().deserialize()?;
+    if dismissed {
+        return Err(KeyringError::PromptDismissed);
+    }
+    Ok(())
+}
+
+fn unlock(
+    connection: &Connection,
+    service: &Proxy,
+    objects: Vec<OwnedObjectPath>,
+) -> Result<(), KeyringError> {
+    let (_, prompt): (Vec<OwnedObjectPath>, OwnedObjectPath) =
+        service.call("Unlock", &(objects,))?;
+    complete_prompt(connection, &prompt)
+}
+
+fn secret_service_store(
+    connection: &Connection,
+    id: &str,
+    secret: &[u8],
+) -> Result<(), KeyringError> {
+    let service = service(connection)?;
+    let collection: OwnedObjectPath = service.call("ReadAlias", &("default",))?;
+    if collection.as_str() == "/" {
+        return Err(KeyringError::NoDefaultCollection);
+    }
+    unlock(connection, &service, vec![collection.clone()])?;
+    let session = open_session(&service)?;
+    let properties = HashMap::from([
+        (
+            "org.freedesktop.Secret.Item.Label",
+            Value::from(format!("NJUPT WiFi password ({})", id)),
+        ),
+        (
+            "org.freedesktop.Secret.Item.Attributes",
+            Value::from(attributes(id)),
+        ),
+    ]);
+    let secret: Secret = (
+        session,
+        Vec::new(),
+        secret.to_vec(),
+        "text/plain".to_string(),
+    );
+    let collection = proxy(connection, collection.into(), COLLECTION_INTERFACE)?;
+    let (_, prompt): (OwnedObjectPath, OwnedObjectPath) =
+        collection.call("CreateItem", &(properties, secret, true))?;
+    complete_prompt(connection, &prompt)
+}
+
+fn secret_service_load(connection: &Connection, id: &str) -> Result<Option<Vec<u8>>, KeyringError> {
+    let service = service(connection)?;
+    let (unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) =
+        service.call("SearchItems", &(attributes(id),))?;
+    let item = match (unlocked.into_iter().next(), locked.into_iter().next()) {
+        (Some(item), _) => item,
+        (None, Some(item)) => {
+            unlock(connection, &service, vec![item.clone()])?;
+            item
+        }
+        (None, None) => return Ok(None),
+    };
+    let session = open_session(&service)?;
+    let item = proxy(connection, item.into(), ITEM_INTERFACE)?;
+    let (_, _, value, _): Secret = item.call("GetSecret", &(session,))?;
+    Ok(Some(value))
+}
+
+fn keyutils_description(id: &str) -> String {
+    format!("{}:{}", APPLICATION, id)
+}
+
+fn keyutils_store(id: &str, secret: &[u8]) -> Result<(), KeyError> {
+    let keyring = KeyRing::from_special_id(KeyRingIdentifier::User, false)?;
+    let key = keyring.add_key(&keyutils_description(id), secret)?;
+    // Processes of the user that do not possess the key, e.g. a service, also need to read it.
+    key.set_perms(
+        KeyPermissionsBuilder::builder()
+            .posessor(Permission::ALL)
+            .user(Permission::VIEW | Permission::READ | Permission::SEARCH)
+            .build(),
+    )
+}
+
+fn keyutils_load(id: &str) -> Result<Option<Vec<u8>>, KeyError> {
+    let keyring = KeyRing::from_special_id(KeyRingIdentifier::User, false)?;
+    match keyring.search(&keyutils_description(id)) {
+        Ok(key) => key.read_to_vec().map(Some),
+        Err(KeyError::KeyDoesNotExist) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
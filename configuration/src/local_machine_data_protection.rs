@@ -1,17 +1,47 @@
-#![cfg(not(target_os = "windows"))]
+//! Passwords encrypted with ChaCha20-Poly1305, serialized as `v<version>$<kind>$...`:
+//! - `v2$m$<salt>$<check>$<nonce>$<ciphertext>`: keyed by the machine ID through HKDF with a
+//!   per-file salt, and bound to the account ID as associated data. `check` tells a changed
+//!   machine ID apart from a corrupted password. Not available on Windows, which has its own
+//!   data protection API.
+//! - `v1$m$<nonce>$<ciphertext>`: keyed by the bare SHA-256 of the machine ID, still readable.
+//! - `v1$p$m=<m>,t=<t>,p=<p>$<salt>$<nonce>$<ciphertext>`: keyed by a passphrase with Argon2id,
+//!   so that the config can be shared between machines.
+use argon2::{Algorithm, Argon2, Params, Version};
 use chacha20poly1305::aead::generic_array::GenericArray;
-use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::aead::{rand_core::RngCore, Aead, Payload};
 use chacha20poly1305::{aead::OsRng, AeadCore, ChaCha20Poly1305, KeyInit};
 use hex::{FromHex, ToHex};
+#[cfg(not(target_os = "windows"))]
+use hkdf::Hkdf;
+#[cfg(not(target_os = "windows"))]
 use machine_uid::machine_id::get_machine_id;
 use serde::{Deserialize, Serialize};
+#[cfg(not(target_os = "windows"))]
 use sha2::{Digest, Sha256};
 use thiserror::Error;
+use zeroize::Zeroizing;
 
-#[derive(Debug)]
+#[cfg(not(target_os = "windows"))]
+const MACHINE_KEY_INFO: &[u8] = b"njupt_wifi_login local machine key";
+#[cfg(not(target_os = "windows"))]
+const MACHINE_CHECK_INFO: &[u8] = b"njupt_wifi_login local machine check";
+
+#[cfg(not(target_os = "windows"))]
+#[derive(Debug, Clone)]
 pub struct LocalMachineDataProtection {
+    /// `None` for v1.
+    derivation: Option<MachineKeyDerivation>,
     nonce: Vec<u8>,
     secret: Vec<u8>,
+    /// The account the password belongs to, which is not serialized but bound by v2.
+    userid: String,
+}
+
+#[cfg(not(target_os = "windows"))]
+#[derive(Debug, Clone)]
+struct MachineKeyDerivation {
+    salt: Vec<u8>,
+    check: Vec<u8>,
 }
 
 #[derive(Error, Debug)]
@@ -20,36 +50,98 @@ pub enum LocalMachineDataProtectionError {
     MachineIdError { message: String },
     #[error("aead error")]
     AeadError,
+    #[error("machine identity changed since the password was encrypted, run `njupt_wifi_login config rekey` to enter it again")]
+    MachineIdentityChanged,
+    #[error("the encrypted password is corrupted or belongs to another account")]
+    Corrupted,
+}
+
+#[derive(Error, Debug)]
+pub enum PassphraseDataProtectionError {
+    #[error("aead error")]
+    AeadError,
+    #[error("key derivation error: {0}")]
+    KeyDerivationError(argon2::Error),
+    #[error("wrong passphrase")]
+    WrongPassphrase,
+}
+
+#[cfg(not(target_os = "windows"))]
+fn machine_id() -> Result<String, LocalMachineDataProtectionError> {
+    get_machine_id().map_err(|e| LocalMachineDataProtectionError::MachineIdError {
+        message: e.to_string(),
+    })
 }
 
+/// The associated data of v2, so that the password is only accepted for the account
+/// and the purpose it was encrypted for.
+#[cfg(not(target_os = "windows"))]
+fn associated_data(userid: &str) -> Vec<u8> {
+    format!("njupt_wifi_login;purpose=password;userid={}", userid).into_bytes()
+}
+
+#[cfg(not(target_os = "windows"))]
+impl MachineKeyDerivation {
+    /// Derive the key and the check value from the machine ID.
+    fn derive(salt: &[u8]) -> Result<(ChaCha20Poly1305, Vec<u8>), LocalMachineDataProtectionError> {
+        let hkdf = Hkdf::<Sha256>::new(Some(salt), machine_id()?.as_bytes());
+        let mut key = Zeroizing::new([0u8; 32]);
+        let mut check = vec![0u8; 8];
+        hkdf.expand(MACHINE_KEY_INFO, key.as_mut())
+            .and_then(|_| hkdf.expand(MACHINE_CHECK_INFO, &mut check))
+            .map_err(|_| LocalMachineDataProtectionError::AeadError)?;
+        Ok((
+            ChaCha20Poly1305::new(GenericArray::from_slice(key.as_ref())),
+            check,
+        ))
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
 impl LocalMachineDataProtection {
-    pub fn protect(s: &[u8]) -> Result<Self, LocalMachineDataProtectionError> {
-        let machine_id = Sha256::digest(
-            get_machine_id()
-                .map_err(|e| LocalMachineDataProtectionError::MachineIdError {
-                    message: e.to_string(),
-                })?
-                .into_bytes(),
-        );
-        let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(machine_id.as_ref()));
+    pub fn protect(s: &[u8], userid: &str) -> Result<Self, LocalMachineDataProtectionError> {
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let (cipher, check) = MachineKeyDerivation::derive(&salt)?;
         let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
         let ciphertext = cipher
-            .encrypt(&nonce, s)
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: s,
+                    aad: &associated_data(userid),
+                },
+            )
             .map_err(|_| LocalMachineDataProtectionError::AeadError)?;
         Ok(Self {
+            derivation: Some(MachineKeyDerivation { salt, check }),
             nonce: nonce.to_vec(),
-            secret: ciphertext.to_vec(),
+            secret: ciphertext,
+            userid: userid.to_string(),
         })
     }
 
     pub fn unprotect(&self) -> Result<Vec<u8>, LocalMachineDataProtectionError> {
-        let machine_id = Sha256::digest(
-            get_machine_id()
-                .map_err(|e| LocalMachineDataProtectionError::MachineIdError {
-                    message: e.to_string(),
-                })?
-                .into_bytes(),
-        );
+        let Some(derivation) = &self.derivation else {
+            return self.unprotect_v1();
+        };
+        let (cipher, check) = MachineKeyDerivation::derive(&derivation.salt)?;
+        if check != derivation.check {
+            return Err(LocalMachineDataProtectionError::MachineIdentityChanged);
+        }
+        cipher
+            .decrypt(
+                GenericArray::from_slice(&self.nonce),
+                Payload {
+                    msg: &self.secret,
+                    aad: &associated_data(&self.userid),
+                },
+            )
+            .map_err(|_| LocalMachineDataProtectionError::Corrupted)
+    }
+
+    fn unprotect_v1(&self) -> Result<Vec<u8>, LocalMachineDataProtectionError> {
+        let machine_id = Sha256::digest(machine_id()?.into_bytes());
         let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(machine_id.as_ref()));
         cipher
             .decrypt(
@@ -58,11 +150,28 @@ impl LocalMachineDataProtection {
             )
             .map_err(|_| LocalMachineDataProtectionError::AeadError)
     }
+
+    /// Whether it is the older format, which is not bound to the account.
+    pub fn is_v1(&self) -> bool {
+        self.derivation.is_none()
+    }
+
+    pub(crate) fn bind_userid(&mut self, userid: &str) {
+        self.userid = userid.to_string();
+    }
 }
 
+#[cfg(not(target_os = "windows"))]
 impl Serialize for LocalMachineDataProtection {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut data = "v1$m$".to_string();
+        let mut data = match &self.derivation {
+            Some(derivation) => format!(
+                "v2$m${}${}$",
+                derivation.salt.encode_hex::<String>(),
+                derivation.check.encode_hex::<String>()
+            ),
+            None => "v1$m$".to_string(),
+        };
         data.push_str(self.nonce.encode_hex::<String>().as_str());
         data.push('$');
         data.push_str(self.secret.encode_hex::<String>().as_str());
@@ -70,15 +179,138 @@ impl Serialize for LocalMachineDataProtection {
     }
 }
 
+#[cfg(not(target_os = "windows"))]
 impl<'de> Deserialize<'de> for LocalMachineDataProtection {
     fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let bytes = String::deserialize(deserializer)?;
         let parts: Vec<&str> = bytes.split('$').collect();
-        if parts.len() != 4 || parts[0] != "v1" || parts[1] != "m" {
+        let (derivation, rest) = match parts.as_slice() {
+            ["v1", "m", rest @ ..] if rest.len() == 2 => (None, rest),
+            ["v2", "m", salt, check, rest @ ..] if rest.len() == 2 => (
+                Some(MachineKeyDerivation {
+                    salt: Vec::from_hex(salt).map_err(serde::de::Error::custom)?,
+                    check: Vec::from_hex(check).map_err(serde::de::Error::custom)?,
+                }),
+                rest,
+            ),
+            _ => return Err(serde::de::Error::custom("invalid format")),
+        };
+        let nonce = parse_nonce(rest[0]).map_err(serde::de::Error::custom)?;
+        let secret = Vec::from_hex(rest[1]).map_err(serde::de::Error::custom)?;
+        Ok(Self {
+            derivation,
+            nonce,
+            secret,
+            userid: String::new(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PassphraseDataProtection {
+    params: Params,
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    secret: Vec<u8>,
+}
+
+impl PassphraseDataProtection {
+    pub fn protect(s: &[u8], passphrase: &str) -> Result<Self, PassphraseDataProtectionError> {
+        let params = Params::default();
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = passphrase_cipher(passphrase, &salt, params.clone())?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, s)
+            .map_err(|_| PassphraseDataProtectionError::AeadError)?;
+        Ok(Self {
+            params,
+            salt,
+            nonce: nonce.to_vec(),
+            secret: ciphertext,
+        })
+    }
+
+    pub fn unprotect(&self, passphrase: &str) -> Result<Vec<u8>, PassphraseDataProtectionError> {
+        let cipher = passphrase_cipher(passphrase, &self.salt, self.params.clone())?;
+        cipher
+            .decrypt(
+                GenericArray::from_slice(&self.nonce),
+                Payload::from(self.secret.as_ref()),
+            )
+            .map_err(|_| PassphraseDataProtectionError::WrongPassphrase)
+    }
+}
+
+fn passphrase_cipher(
+    passphrase: &str,
+    salt: &[u8],
+    params: Params,
+) -> Result<ChaCha20Poly1305, PassphraseDataProtectionError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(PassphraseDataProtectionError::KeyDerivationError)?;
+    Ok(ChaCha20Poly1305::new(GenericArray::from_slice(
+        key.as_ref(),
+    )))
+}
+
+impl Serialize for PassphraseDataProtection {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = format!(
+            "v1$p$m={},t={},p={}${}${}${}",
+            self.params.m_cost(),
+            self.params.t_cost(),
+            self.params.p_cost(),
+            self.salt.encode_hex::<String>(),
+            self.nonce.encode_hex::<String>(),
+            self.secret.encode_hex::<String>()
+        );
+        serializer.serialize_str(data.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for PassphraseDataProtection {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = String::deserialize(deserializer)?;
+        let parts: Vec<&str> = bytes.split('$').collect();
+        if parts.len() != 6 || parts[0] != "v1" || parts[1] != "p" {
             return Err(serde::de::Error::custom("invalid format"));
         }
-        let nonce = Vec::from_hex(parts[2]).map_err(serde::de::Error::custom)?;
-        let secret = Vec::from_hex(parts[3]).map_err(serde::de::Error::custom)?;
-        Ok(Self { nonce, secret })
+        let params = parse_params(parts[2])
+            .ok_or_else(|| serde::de::Error::custom("invalid key derivation parameters"))?;
+        let salt = Vec::from_hex(parts[3]).map_err(serde::de::Error::custom)?;
+        let nonce = parse_nonce(parts[4]).map_err(serde::de::Error::custom)?;
+        let secret = Vec::from_hex(parts[5]).map_err(serde::de::Error::custom)?;
+        Ok(Self {
+            params,
+            salt,
+            nonce,
+            secret,
+        })
+    }
+}
+
+fn parse_nonce(s: &str) -> Result<Vec<u8>, String> {
+    let nonce = Vec::from_hex(s).map_err(|e| e.to_string())?;
+    if nonce.len() != 12 {
+        return Err(format!("invalid nonce length {}", nonce.len()));
+    }
+    Ok(nonce)
+}
+
+/// Parse `m=<m>,t=<t>,p=<p>` of Argon2id.
+fn parse_params(s: &str) -> Option<Params> {
+    let mut costs = s.split(',').map(|cost| cost.split_once('='));
+    let mut next = |name: &str| match costs.next()? {
+        Some((key, value)) if key == name => value.parse::<u32>().ok(),
+        _ => None,
+    };
+    let (m_cost, t_cost, p_cost) = (next("m")?, next("t")?, next("p")?);
+    if costs.next().is_some() {
+        return None;
     }
+    Params::new(m_cost, t_cost, p_cost, None).ok()
 }
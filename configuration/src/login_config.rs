@@ -1,25 +1,428 @@
 use crate::credential::Credential;
+use crate::migration::CONFIG_VERSION;
+use crate::password::{ExternalPassword, Password};
 use byte_unit::Byte;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-#[derive(Serialize, Deserialize, Debug)]
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+/// The config file, see `njupt_wifi_login config schema` for its JSON Schema.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[schemars(title = "NJUPT WiFi Login config")]
 pub struct LoginConfig {
+    /// Version of the config layout, older files are upgraded on load.
+    #[serde(default = "default_version")]
+    #[schemars(range(max = CONFIG_VERSION))]
+    pub version: u32,
     #[serde(flatten)]
     pub credential: Credential,
+    /// Interval of proactive checks in seconds, 0 means no proactive checks.
     #[serde(default = "default_check_interval")]
     pub check_interval: u64,
+    /// Bind the requests to a specific interface (e.g. eth0), null to not specify.
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = 1))]
     pub interface: Option<String>,
+    /// Where and how much to log.
     #[serde(default)]
-    pub log_policy: LogFileConfig,
+    pub logging: LoggingConfig,
+    /// DNS servers for the connectivity checks and the portal, 8.8.8.8 and 114.114.114.114 over UDP if absent.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<DnsConfig>,
+    /// Serve Prometheus metrics at /metrics and a health check at /healthz, disabled if absent.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<MetricsConfig>,
+    /// Desktop notifications of login events, disabled if absent.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notifications: Option<NotificationsConfig>,
+    /// Commands to run on login events, with the details in `NJUPT_WIFI_*` environment variables.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<HooksConfig>,
+    /// Publish the status to an MQTT broker with Home Assistant discovery, and accept commands, disabled if absent.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<MqttConfig>,
+}
+
+impl LoginConfig {
+    /// The credential with the defaults of the other fields.
+    pub fn new(credential: Credential) -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            credential,
+            check_interval: default_check_interval(),
+            interface: None,
+            logging: Default::default(),
+            dns: None,
+            metrics: None,
+            notifications: None,
+            hooks: None,
+            mqtt: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct DnsConfig {
+    #[serde(default = "default_dns_servers")]
+    #[schemars(length(min = 1))]
+    pub servers: Vec<DnsServer>,
+    /// Seconds to wait for a response.
+    #[serde(default = "default_dns_timeout")]
+    #[schemars(range(min = 1))]
+    pub timeout: u64,
+    /// Times a query is tried before giving up.
+    #[serde(default = "default_dns_attempts")]
+    #[schemars(range(min = 1))]
+    pub attempts: usize,
+    /// How the servers are tried.
+    #[serde(default)]
+    pub ordering: DnsOrdering,
+    /// Which servers resolve which names.
+    #[serde(default)]
+    pub policy: DnsPolicy,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            servers: default_dns_servers(),
+            timeout: default_dns_timeout(),
+            attempts: default_dns_attempts(),
+            ordering: DnsOrdering::default(),
+            policy: DnsPolicy::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct DnsServer {
+    /// IP address of the server, without a port.
+    pub address: IpAddr,
+    /// 53, 853 for tls or 443 for https if absent.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1))]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub protocol: DnsProtocol,
+    /// Name in the certificate of the server, required by `tls` and `https`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = 1))]
+    pub tls_name: Option<String>,
+}
+
+impl DnsServer {
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(
+            self.address,
+            self.port.unwrap_or(self.protocol.default_port()),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsProtocol {
+    /// Plain DNS over UDP.
+    #[default]
+    Udp,
+    /// Plain DNS over TCP.
+    Tcp,
+    /// DNS over TLS.
+    Tls,
+    /// DNS over HTTPS, at `/dns-query`.
+    Https,
+}
+
+impl DnsProtocol {
+    pub fn default_port(self) -> u16 {
+        match self {
+            DnsProtocol::Udp | DnsProtocol::Tcp => 53,
+            DnsProtocol::Tls => 853,
+            DnsProtocol::Https => 443,
+        }
+    }
+
+    pub fn is_encrypted(self) -> bool {
+        matches!(self, DnsProtocol::Tls | DnsProtocol::Https)
+    }
+}
+
+/// Which servers resolve which names.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsPolicy {
+    /// Every name with all the servers.
+    #[default]
+    AllServers,
+    /// The connectivity checks with the encrypted servers only, and the portal
+    /// of NJUPT with the plain ones only, so that nothing is sent in plain text
+    /// outside the campus until a portal is found.
+    EncryptedProbes,
+}
+
+/// How the servers are tried, see `hickory_resolver::config::ServerOrderingStrategy`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsOrdering {
+    /// The fastest server with the fewest failures first.
+    #[default]
+    QueryStatistics,
+    /// In the order they are listed.
+    UserProvided,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct MqttConfig {
+    /// Host name or address of the broker.
+    #[schemars(length(min = 1))]
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    #[schemars(range(min = 1))]
+    pub port: u16,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Plain-text password or an external source, as the login password.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<ExternalPassword>")]
+    pub password: Option<Password>,
+    #[serde(default = "default_mqtt_client_id")]
+    #[schemars(length(min = 1))]
+    pub client_id: String,
+    /// Prefix of the `status`, `availability` and `command` topics.
+    #[serde(default = "default_mqtt_topic_prefix")]
+    #[schemars(length(min = 1))]
+    pub topic_prefix: String,
+    /// Prefix of the Home Assistant discovery topics, null to not publish them.
+    #[serde(default = "default_mqtt_discovery_prefix")]
+    pub discovery_prefix: Option<String>,
+}
+
+/// Each hook is the program and its arguments, run without a shell.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct HooksConfig {
+    /// Seconds to wait for a hook before it is killed.
+    #[serde(default = "default_hook_timeout")]
+    pub timeout: u64,
+    /// The network becomes usable, found by a check or after a login.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = 1))]
+    pub on_connected: Option<Vec<String>>,
+    /// Logged in.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = 1))]
+    pub on_login_success: Option<Vec<String>>,
+    /// Any failed login, including one refused in off hours.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = 1))]
+    pub on_login_failure: Option<Vec<String>>,
+    /// A login was refused in off hours.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = 1))]
+    pub on_off_hours: Option<Vec<String>>,
+    /// The network status differs from the one of the previous check.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = 1))]
+    pub on_status_change: Option<Vec<String>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
-pub struct LogFileConfig {
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct MetricsConfig {
+    /// Address and port to listen on, keep it on localhost unless the network is trusted.
+    #[serde(default = "default_metrics_listen")]
+    pub listen: SocketAddr,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            listen: default_metrics_listen(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, PartialEq, Eq)]
+pub struct NotificationsConfig {
+    /// Logged in to the campus network.
+    #[serde(default)]
+    pub logged_in: NotificationRule,
+    /// Logins are refused in off hours, with the time they end if known.
+    #[serde(default)]
+    pub off_hours: NotificationRule,
+    /// The authentication server rejected the account or password.
+    #[serde(default)]
+    pub credentials_rejected: NotificationRule,
+    /// A captive portal other than the one of NJUPT was detected.
+    #[serde(default)]
+    pub unknown_portal: NotificationRule,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotificationRule {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Minimum seconds between two notifications of the category, 0 means no limit.
+    #[serde(default = "default_notification_interval")]
+    pub min_interval: u64,
+}
+
+impl Default for NotificationRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_interval: default_notification_interval(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone)]
+pub struct LoggingConfig {
+    /// Where to write the log.
     #[serde(default)]
+    pub sink: LogSink,
+    /// Level of every module without its own level, `--log-level` takes precedence.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<LogLevel>,
+    /// Level per module path, e.g. `njupt_wifi_login::login: trace` or `hyper: warn`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub modules: BTreeMap<String, LogLevel>,
+    /// Format of the file and stderr sinks, the others have their own.
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Path of the log file, relative to the state directory, `--log-file` takes precedence.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = 1))]
+    pub file: Option<String>,
+    /// Rotate the log file when it exceeds the size, e.g. 3MiB.
+    #[serde(default)]
+    #[schemars(with = "Option<ByteSize>")]
     pub size_limit: Option<Byte>,
+    /// Number of rotated files to keep.
     #[serde(default)]
+    #[schemars(range(min = 1))]
     pub file_count: Option<u32>,
+    /// Compress the rotated files with gzip.
+    #[serde(default)]
+    pub compress: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogSink {
+    /// Rolling log files.
+    #[default]
+    File,
+    /// Standard error, e.g. for a service manager which collects it.
+    Stderr,
+    /// The native protocol of systemd-journald, only on Linux.
+    Journald,
+    /// The local syslog daemon through `/dev/log`, only on Unix.
+    Syslog,
+}
+
+#[derive(
+    Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// A number of bytes, or a size with a unit, e.g. 3MiB.
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum ByteSize {
+    Bytes(u64),
+    WithUnit(String),
+}
+
+const fn default_version() -> u32 {
+    CONFIG_VERSION
+}
+
+fn default_metrics_listen() -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::LOCALHOST, 9464))
+}
+
+fn default_dns_servers() -> Vec<DnsServer> {
+    [Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(114, 114, 114, 114)]
+        .into_iter()
+        .map(|address| DnsServer {
+            address: address.into(),
+            port: None,
+            protocol: DnsProtocol::Udp,
+            tls_name: None,
+        })
+        .collect()
+}
+
+const fn default_dns_timeout() -> u64 {
+    5
+}
+
+const fn default_dns_attempts() -> usize {
+    2
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+const fn default_notification_interval() -> u64 {
+    10 * 60
+}
+
+const fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_client_id() -> String {
+    "njupt_wifi_login".to_string()
+}
+
+fn default_mqtt_topic_prefix() -> String {
+    "njupt_wifi_login".to_string()
+}
+
+fn default_mqtt_discovery_prefix() -> Option<String> {
+    Some("homeassistant".to_string())
+}
+
+const fn default_hook_timeout() -> u64 {
+    30
 }
 
 const fn default_check_interval() -> u64 {
@@ -0,0 +1,93 @@
+//! Upgrade older config documents to the current version step by step.
+//!
+//! Files without a `version` key are version 0. To change the layout of the config,
+//! bump [`CONFIG_VERSION`] and append a step to [`MIGRATIONS`], which works on the
+//! generic tree, so that every format is upgraded the same way.
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Upgrades a document from version `index` to `index + 1`.
+type MigrationStep = fn(&mut Map<String, Value>) -> Result<(), MigrationError>;
+
+const MIGRATIONS: [MigrationStep; CONFIG_VERSION as usize] = [migrate_v0_to_v1, migrate_v1_to_v2];
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("expected a mapping at the top level")]
+    NotAMapping,
+    #[error("invalid version `{0}`, expected a non-negative integer")]
+    InvalidVersion(Value),
+    #[error(
+        "config version {0} is newer than the supported version {CONFIG_VERSION}, please upgrade"
+    )]
+    TooNew(u32),
+    #[error("`log_policy` and `logging` both set `{0}`, keep only one of them")]
+    Conflict(String),
+}
+
+/// v1 introduces the `version` key, the other fields are unchanged.
+fn migrate_v0_to_v1(_map: &mut Map<String, Value>) -> Result<(), MigrationError> {
+    Ok(())
+}
+
+/// v2 moves `log_policy` into the `logging` section, which also chooses the sink and levels.
+fn migrate_v1_to_v2(map: &mut Map<String, Value>) -> Result<(), MigrationError> {
+    match map.remove("log_policy") {
+        None | Some(Value::Null) => {}
+        Some(log_policy) => match map.get_mut("logging") {
+            // A malformed policy is moved as is, to be reported by the validation.
+            None | Some(Value::Null) => {
+                map.insert("logging".to_string(), log_policy);
+            }
+            // Written by hand for a newer version, merge the fields set only by the policy.
+            Some(Value::Object(logging)) => {
+                let Value::Object(log_policy) = log_policy else {
+                    return Err(MigrationError::Conflict("logging".to_string()));
+                };
+                for (field, value) in log_policy {
+                    match logging.get(&field) {
+                        Some(existing) if *existing != value => {
+                            return Err(MigrationError::Conflict(format!("logging.{}", field)));
+                        }
+                        _ => {
+                            logging.insert(field, value);
+                        }
+                    }
+                }
+            }
+            Some(_) => return Err(MigrationError::Conflict("logging".to_string())),
+        },
+    }
+    Ok(())
+}
+
+pub fn config_version(value: &Value) -> Result<u32, MigrationError> {
+    let map = value.as_object().ok_or(MigrationError::NotAMapping)?;
+    match map.get("version") {
+        None => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| MigrationError::InvalidVersion(version.clone())),
+    }
+}
+
+/// Upgrade the document in place, returns the version it was upgraded from,
+/// or `None` if it is already up to date.
+pub fn migrate(value: &mut Value) -> Result<Option<u32>, MigrationError> {
+    let from = config_version(value)?;
+    if from > CONFIG_VERSION {
+        return Err(MigrationError::TooNew(from));
+    }
+    if from == CONFIG_VERSION {
+        return Ok(None);
+    }
+    let map = value.as_object_mut().ok_or(MigrationError::NotAMapping)?;
+    for (version, step) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        step(map)?;
+        map.insert("version".to_string(), (version as u32 + 1).into());
+    }
+    Ok(Some(from))
+}
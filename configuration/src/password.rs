@@ -1,28 +1,66 @@
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use serde_with::serde_as;
-use std::{borrow::Cow, string::FromUtf8Error};
+use std::{
+    borrow::Cow,
+    env,
+    io::{self, IsTerminal, Read},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    str::Utf8Error,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 use thiserror::Error;
 
+use crate::secret::SecretString;
+
 #[cfg(target_os = "windows")]
 use crate::win32_data_protection::Win32ProtectedData;
 
+#[cfg(target_os = "linux")]
+use crate::linux_keyring::{KeyringError, KeyringReference};
+
 #[cfg(not(target_os = "windows"))]
+use crate::local_machine_data_protection::LocalMachineDataProtection;
 use crate::local_machine_data_protection::{
-    LocalMachineDataProtection, LocalMachineDataProtectionError,
+    LocalMachineDataProtectionError, PassphraseDataProtection, PassphraseDataProtectionError,
 };
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Environment variable holding the passphrase of [`PasswordScope::Passphrase`].
+pub const PASSPHRASE_ENV: &str = "NJUPT_WIFI_PASSPHRASE";
+/// Environment variable holding a shell command which prints the passphrase,
+/// e.g. `systemd-ask-password` or `pass show njupt_wifi_passphrase`.
+pub const PASSPHRASE_COMMAND_ENV: &str = "NJUPT_WIFI_PASSPHRASE_COMMAND";
+
+/// How long a password or passphrase command may run before it is killed,
+/// as long as `systemd-ask-password` waits by default.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// The passphrase is only asked once per process.
+static PASSPHRASE: Mutex<Option<SecretString>> = Mutex::new(None);
+static PASSPHRASE_PROMPT: AtomicBool = AtomicBool::new(true);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PasswordScope {
     Anywhere,
     LocalMachine,
     CurrentUser,
+    /// Encrypted with a key derived from a passphrase, so that the config can be shared
+    /// between machines.
+    Passphrase,
 }
 
 #[serde_as]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum Password {
-    Basic(String),
+    Basic(SecretString),
     #[cfg(target_os = "windows")]
     DataProtection {
         #[serde_as(as = "serde_with::base64::Base64")]
@@ -32,6 +70,108 @@ pub enum Password {
     LocalMachineDataProtection {
         data_protection: LocalMachineDataProtection,
     },
+    /// Listed after the machine-bound formats, which reject the `v1$p$` prefix.
+    PassphraseDataProtection {
+        data_protection: PassphraseDataProtection,
+    },
+    /// Kept in the Secret Service or the kernel keyring of the current user.
+    #[cfg(target_os = "linux")]
+    Keyring {
+        keyring: KeyringReference,
+    },
+    /// Read from an environment variable.
+    Env {
+        env: String,
+    },
+    /// Read the first line of a file, e.g. `${CREDENTIALS_DIRECTORY}/password` of systemd
+    /// or `/run/secrets/password` of Docker. `${NAME}` is replaced by the environment variable.
+    File {
+        file: PathBuf,
+    },
+    /// Run a command and read the first line of its output, e.g. `[pass, show, njupt]`.
+    Command {
+        command: Vec<String>,
+    },
+}
+
+/// Every source of [`Password`] written as a mapping, regardless of the platform,
+/// so that the exported schema is the same everywhere.
+const PASSWORD_SOURCES: [&str; 5] = ["data_protection", "keyring", "env", "file", "command"];
+/// Sources which are not bound to an account.
+const EXTERNAL_PASSWORD_SOURCES: [&str; 3] = ["env", "file", "command"];
+
+fn password_source_schema(key: &str) -> Value {
+    let schema = match key {
+        "data_protection" => json!({
+            "description": "Encrypted by `njupt_wifi_login config set-password`.",
+            "type": "string",
+        }),
+        "keyring" => json!({
+            "description": "Reference to the password in the keyring of the current user on Linux.",
+            "type": "string",
+            "pattern": "^(secret-service|keyutils):.+$",
+        }),
+        "env" => json!({
+            "description": "Name of the environment variable.",
+            "type": "string",
+        }),
+        "file" => json!({
+            "description": "Path of the file, whose first line is the password. `${NAME}` is replaced by the environment variable.",
+            "type": "string",
+        }),
+        "command" => json!({
+            "description": "Program and arguments, the first line of the output is the password.",
+            "type": "array",
+            "items": { "type": "string" },
+            "minItems": 1,
+        }),
+        _ => unreachable!("unknown password source `{}`", key),
+    };
+    json!({
+        "type": "object",
+        "required": [key],
+        "additionalProperties": false,
+        "properties": { key: schema },
+    })
+}
+
+fn password_schema(description: &str, sources: &[&str]) -> Schema {
+    let mut one_of = vec![json!({ "type": "string" })];
+    one_of.extend(sources.iter().map(|key| password_source_schema(key)));
+    json_schema!({
+        "description": description,
+        "oneOf": one_of,
+    })
+}
+
+impl JsonSchema for Password {
+    fn schema_name() -> Cow<'static, str> {
+        "Password".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        password_schema(
+            "Plain-text password, an encrypted one written by `njupt_wifi_login config set-password`, or an external source.",
+            &PASSWORD_SOURCES,
+        )
+    }
+}
+
+/// Schema of a [`Password`] which is not bound to an account,
+/// i.e. plain text or from an external source.
+pub struct ExternalPassword;
+
+impl JsonSchema for ExternalPassword {
+    fn schema_name() -> Cow<'static, str> {
+        "ExternalPassword".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        password_schema(
+            "Plain-text password or an external source.",
+            &EXTERNAL_PASSWORD_SOURCES,
+        )
+    }
 }
 
 #[derive(Error, Debug)]
@@ -41,55 +181,319 @@ pub enum PasswordError {
     Win32CryptographyError(#[from] windows::core::Error),
     #[error("scope not supported")]
     ScopeNotSupported(PasswordScope),
-    #[error("from utf8 error")]
-    FromUtf8Error(#[from] FromUtf8Error),
-    #[cfg(not(target_os = "windows"))]
-    #[error("local machine cryptography error")]
+    #[error("password is not valid UTF-8")]
+    InvalidUtf8(#[source] Utf8Error),
+    #[error("data protection error")]
     LocalMachineCryptographyError(#[from] LocalMachineDataProtectionError),
+    #[error("passphrase data protection error")]
+    PassphraseCryptographyError(#[from] PassphraseDataProtectionError),
+    #[cfg(target_os = "linux")]
+    #[error("keyring error")]
+    KeyringError(#[from] KeyringError),
+    #[error("environment variable `{0}` is not set")]
+    EnvNotSet(String),
+    #[error("failed to read password file `{0}`")]
+    ReadFile(PathBuf, #[source] io::Error),
+    #[error("password command is empty")]
+    EmptyCommand,
+    #[error("failed to run password command `{0}`")]
+    RunCommand(String, #[source] io::Error),
+    #[error("password command `{0}` failed with {1}")]
+    CommandFailed(String, ExitStatus),
+    #[error("password command `{0}` did not finish in {1:?}")]
+    CommandTimedOut(String, Duration),
+    #[error("passphrase is required, set `{PASSPHRASE_ENV}` or `{PASSPHRASE_COMMAND_ENV}`, or run in a terminal")]
+    PassphraseRequired,
+    #[error("failed to read passphrase")]
+    ReadPassphrase(#[source] io::Error),
 }
 
-impl ToString for Password {
-    fn to_string(&self) -> String {
-        self.get().unwrap_or_default().to_string()
+impl std::fmt::Display for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<redacted>")
     }
 }
 
 impl Password {
-    pub fn new_basic(s: String) -> Self {
+    pub fn new_basic(s: SecretString) -> Self {
         Password::Basic(s)
     }
 
-    pub fn try_new(s: String, scope: PasswordScope) -> Result<Self, PasswordError> {
+    /// `userid` is the account the password belongs to, some scopes bind the password to it.
+    pub fn try_new(
+        s: SecretString,
+        scope: PasswordScope,
+        userid: &str,
+    ) -> Result<Self, PasswordError> {
+        let plaintext = s.expose_secret().as_bytes();
         match scope {
             PasswordScope::Anywhere => Ok(Password::Basic(s)),
             #[cfg(target_os = "windows")]
             PasswordScope::LocalMachine => Ok(Password::DataProtection {
-                data_protection: Win32ProtectedData::protect_for_local_machine(s.as_bytes())?,
+                data_protection: Win32ProtectedData::protect_for_local_machine(plaintext)?,
             }),
             #[cfg(target_os = "windows")]
             PasswordScope::CurrentUser => Ok(Password::DataProtection {
-                data_protection: Win32ProtectedData::protect_for_current_user(s.as_bytes())?,
+                data_protection: Win32ProtectedData::protect_for_current_user(plaintext)?,
             }),
             #[cfg(not(target_os = "windows"))]
             PasswordScope::LocalMachine => Ok(Password::LocalMachineDataProtection {
-                data_protection: LocalMachineDataProtection::protect(s.as_bytes())?,
+                data_protection: LocalMachineDataProtection::protect(plaintext, userid)?,
+            }),
+            PasswordScope::Passphrase => Ok(Password::PassphraseDataProtection {
+                data_protection: PassphraseDataProtection::protect(
+                    plaintext,
+                    passphrase()?.expose_secret(),
+                )?,
+            }),
+            #[cfg(target_os = "linux")]
+            PasswordScope::CurrentUser => Ok(Password::Keyring {
+                keyring: KeyringReference::store(plaintext)?,
             }),
             #[allow(unreachable_patterns)]
             _ => Err(PasswordError::ScopeNotSupported(scope)),
         }
     }
 
-    pub fn get(&self) -> Result<Cow<'_, str>, PasswordError> {
+    /// The plaintext password, which is zeroed when dropped.
+    pub fn get(&self) -> Result<SecretString, PasswordError> {
         match &self {
-            Password::Basic(s) => Ok(Cow::Borrowed(s)),
+            Password::Basic(s) => Ok(s.clone()),
             #[cfg(target_os = "windows")]
             Password::DataProtection { data_protection } => {
-                Ok(Cow::Owned(String::from_utf8(data_protection.unprotect()?)?))
+                secret_from_utf8(data_protection.unprotect()?)
             }
             #[cfg(not(target_os = "windows"))]
             Password::LocalMachineDataProtection { data_protection } => {
-                Ok(Cow::Owned(String::from_utf8(data_protection.unprotect()?)?))
+                secret_from_utf8(data_protection.unprotect()?)
+            }
+            Password::PassphraseDataProtection { data_protection } => {
+                let secret = data_protection
+                    .unprotect(passphrase()?.expose_secret())
+                    .inspect_err(|e| {
+                        // Ask again next time rather than keep a wrong passphrase.
+                        if let PassphraseDataProtectionError::WrongPassphrase = e {
+                            forget_passphrase();
+                        }
+                    })?;
+                secret_from_utf8(secret)
+            }
+            #[cfg(target_os = "linux")]
+            Password::Keyring { keyring } => secret_from_utf8(keyring.load()?),
+            Password::Env { env } => env::var(env)
+                .map(SecretString::from)
+                .map_err(|_| PasswordError::EnvNotSet(env.clone())),
+            Password::File { file } => {
+                let file = expand_env(file)?;
+                let content =
+                    std::fs::read(&file).map_err(|e| PasswordError::ReadFile(file.clone(), e))?;
+                first_line(content)
             }
+            Password::Command { command } => {
+                let (program, args) = command.split_first().ok_or(PasswordError::EmptyCommand)?;
+                run_command(program, args, COMMAND_TIMEOUT)
+            }
+        }
+    }
+
+    /// The scope the password was stored with, if it can be told from the config.
+    pub fn scope(&self) -> Option<PasswordScope> {
+        match self {
+            Password::Basic(_) => Some(PasswordScope::Anywhere),
+            #[cfg(not(target_os = "windows"))]
+            Password::LocalMachineDataProtection { .. } => Some(PasswordScope::LocalMachine),
+            #[cfg(target_os = "linux")]
+            Password::Keyring { .. } => Some(PasswordScope::CurrentUser),
+            Password::PassphraseDataProtection { .. } => Some(PasswordScope::Passphrase),
+            _ => None,
         }
     }
+
+    /// Tell the password which account it belongs to, as it is not stored along with it.
+    pub(crate) fn bind_userid(&mut self, userid: &str) {
+        #[cfg(not(target_os = "windows"))]
+        if let Password::LocalMachineDataProtection { data_protection } = self {
+            data_protection.bind_userid(userid);
+        }
+        #[cfg(target_os = "windows")]
+        let _ = userid;
+    }
+
+    /// Whether it should be encrypted again with the current format, see `config rekey`.
+    pub fn is_outdated(&self) -> bool {
+        match self {
+            #[cfg(not(target_os = "windows"))]
+            Password::LocalMachineDataProtection { data_protection } => data_protection.is_v1(),
+            _ => false,
+        }
+    }
+
+    /// Whether the password is stored outside of the config file.
+    pub fn is_external(&self) -> bool {
+        matches!(
+            self,
+            Password::Env { .. } | Password::File { .. } | Password::Command { .. }
+        )
+    }
+}
+
+/// Do not ask for the passphrase in the terminal, e.g. when it is used by a TUI.
+pub fn disable_passphrase_prompt() {
+    PASSPHRASE_PROMPT.store(false, Ordering::Relaxed);
+}
+
+/// Use the passphrase instead of reading it, e.g. after asking the user to confirm it.
+pub fn set_passphrase(passphrase: SecretString) {
+    *PASSPHRASE.lock().unwrap() = Some(passphrase);
+}
+
+/// Whether the passphrase is given by the environment rather than asked for.
+pub fn is_passphrase_from_env() -> bool {
+    env::var_os(PASSPHRASE_ENV).is_some() || env::var_os(PASSPHRASE_COMMAND_ENV).is_some()
+}
+
+fn forget_passphrase() {
+    *PASSPHRASE.lock().unwrap() = None;
+}
+
+/// Read the passphrase from the environment variable, the agent command, or the terminal.
+fn passphrase() -> Result<SecretString, PasswordError> {
+    let mut cached = PASSPHRASE.lock().unwrap();
+    if let Some(passphrase) = cached.as_ref() {
+        return Ok(passphrase.clone());
+    }
+    let passphrase = if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        SecretString::from(passphrase)
+    } else if let Ok(command) = env::var(PASSPHRASE_COMMAND_ENV) {
+        #[cfg(target_os = "windows")]
+        let shell = ["cmd", "/C"];
+        #[cfg(not(target_os = "windows"))]
+        let shell = ["sh", "-c"];
+        run_command(shell[0], &[shell[1].to_string(), command], COMMAND_TIMEOUT)?
+    } else if PASSPHRASE_PROMPT.load(Ordering::Relaxed) && io::stdin().is_terminal() {
+        rpassword::prompt_password("Passphrase: ")
+            .map(SecretString::from)
+            .map_err(PasswordError::ReadPassphrase)?
+    } else {
+        return Err(PasswordError::PassphraseRequired);
+    };
+    *cached = Some(passphrase.clone());
+    Ok(passphrase)
+}
+
+/// Run a command and read the first line of its output, killing it after `timeout`.
+fn run_command(
+    program: &str,
+    args: &[String],
+    timeout: Duration,
+) -> Result<SecretString, PasswordError> {
+    let run_error = |e| PasswordError::RunCommand(program.to_string(), e);
+    let timed_out = || PasswordError::CommandTimedOut(program.to_string(), timeout);
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(run_error)?;
+    // Read in another thread, so that a full pipe does not block the child.
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = sender.send(stdout.read_to_end(&mut output).map(|_| output));
+    });
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(run_error)? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(timed_out());
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    // A process started by the command may still hold the output open.
+    let output = receiver
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .map_err(|_| timed_out())?
+        .map_err(run_error)?;
+    if !status.success() {
+        return Err(PasswordError::CommandFailed(program.to_string(), status));
+    }
+    first_line(output)
+}
+
+fn first_line(content: Vec<u8>) -> Result<SecretString, PasswordError> {
+    let mut s = secret_from_utf8(content)?;
+    let end = s.expose_secret().find(['\r', '\n']);
+    if let Some(end) = end {
+        s.truncate(end);
+    }
+    Ok(s)
+}
+
+/// Take over the buffer, rather than leave a copy of the plaintext behind.
+fn secret_from_utf8(bytes: Vec<u8>) -> Result<SecretString, PasswordError> {
+    match String::from_utf8(bytes) {
+        Ok(s) => Ok(SecretString::from(s)),
+        Err(e) => {
+            let utf8_error = e.utf8_error();
+            zeroize::Zeroize::zeroize(&mut e.into_bytes());
+            Err(PasswordError::InvalidUtf8(utf8_error))
+        }
+    }
+}
+
+/// Replace `${NAME}` with the environment variable.
+fn expand_env(path: &Path) -> Result<PathBuf, PasswordError> {
+    let path = path.to_string_lossy();
+    let mut expanded = String::new();
+    let mut rest = path.as_ref();
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&env::var(name).map_err(|_| PasswordError::EnvNotSet(name.to_string()))?);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(PathBuf::from(expanded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn command_is_killed_after_timeout() {
+        let marker =
+            env::temp_dir().join(format!("njupt_wifi_command_timeout_{}", std::process::id()));
+        let started = Instant::now();
+        let result = run_command(
+            "sh",
+            &[
+                "-c".to_string(),
+                format!("sleep 2; touch '{}'", marker.display()),
+            ],
+            Duration::from_millis(300),
+        );
+        assert!(matches!(result, Err(PasswordError::CommandTimedOut(..))));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        thread::sleep(Duration::from_secs(3));
+        assert!(!marker.exists(), "the command kept running");
+
+        let password = run_command(
+            "sh",
+            &["-c".to_string(), "echo secret".to_string()],
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert_eq!(password.expose_secret(), "secret");
+    }
 }
@@ -0,0 +1,147 @@
+//! JSON Schema of the config file, for editors to autocomplete and lint it.
+//!
+//! It is derived from the serde types of [`LoginConfig`], and the validation and the UCI
+//! loader look up the known fields and the allowed values here, instead of listing them again.
+use crate::login_config::LoginConfig;
+use schemars::{generate::SchemaSettings, transform::RecursiveTransform, Schema};
+use serde_json::Value;
+use std::sync::LazyLock;
+
+static SCHEMA: LazyLock<Value> = LazyLock::new(login_config_schema);
+
+pub fn login_config_schema() -> Value {
+    SchemaSettings::draft2020_12()
+        .with_transform(RecursiveTransform(deny_unknown_fields))
+        .into_generator()
+        .into_root_schema_for::<LoginConfig>()
+        .to_value()
+}
+
+/// Unknown fields are ignored on load, but editors should still flag them.
+fn deny_unknown_fields(schema: &mut Schema) {
+    if schema.get("properties").is_some() && schema.get("additionalProperties").is_none() {
+        schema.insert("additionalProperties".to_string(), false.into());
+    }
+}
+
+/// Follow `$ref`, and skip the `null` alternative of optional fields.
+fn resolve(schema: &'static Value) -> &'static Value {
+    if let Some(name) = schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| reference.strip_prefix("#/$defs/"))
+    {
+        return SCHEMA
+            .get("$defs")
+            .and_then(|definitions| definitions.get(name))
+            .map_or(schema, resolve);
+    }
+    if let Some(Value::Array(any_of)) = schema.get("anyOf") {
+        let mut alternatives = any_of
+            .iter()
+            .filter(|alternative| alternative.get("type") != Some(&Value::from("null")));
+        if let (Some(alternative), None) = (alternatives.next(), alternatives.next()) {
+            return resolve(alternative);
+        }
+    }
+    schema
+}
+
+/// Schema of the field at the dotted `path`, e.g. `logging.sink`, or the whole config if empty.
+/// `*` stands for the items of a list or the values of a mapping, e.g. `dns.servers.*.protocol`.
+pub(crate) fn field_schema(path: &str) -> Option<&'static Value> {
+    let mut schema = resolve(&SCHEMA);
+    for field in path.split('.').filter(|field| !field.is_empty()) {
+        let next = if field == "*" {
+            schema
+                .get("items")
+                .or_else(|| schema.get("additionalProperties"))?
+        } else {
+            schema.get("properties")?.get(field)?
+        };
+        schema = resolve(next);
+    }
+    Some(schema)
+}
+
+fn strings(value: Option<&'static Value>) -> Vec<&'static str> {
+    value
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect()
+}
+
+/// Fields of the mapping at `path`, in the order they are declared.
+pub(crate) fn field_names(path: &str) -> Vec<&'static str> {
+    field_schema(path)
+        .and_then(|schema| schema.get("properties"))
+        .and_then(Value::as_object)
+        .map(|properties| properties.keys().map(String::as_str).collect())
+        .unwrap_or_default()
+}
+
+/// Fields of the mapping at `path` without a default.
+pub(crate) fn required_fields(path: &str) -> Vec<&'static str> {
+    strings(field_schema(path).and_then(|schema| schema.get("required")))
+}
+
+/// Values allowed by the enum at `path`. Documented variants are listed
+/// as `oneOf` with a `const` each, the others together in an `enum`.
+pub(crate) fn enum_values(path: &str) -> Vec<&'static str> {
+    let Some(schema) = field_schema(path) else {
+        return Vec::new();
+    };
+    let mut values = strings(schema.get("enum"));
+    for alternative in schema
+        .get("oneOf")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        values.extend(alternative.get("const").and_then(Value::as_str));
+        values.extend(strings(alternative.get("enum")));
+    }
+    values
+}
+
+/// The alternatives of the field at `path` which are mappings of a single key,
+/// e.g. the sources of a password, with the schema of the value.
+pub(crate) fn keyed_alternatives(path: &str) -> Vec<(&'static str, &'static Value)> {
+    field_schema(path)
+        .and_then(|schema| schema.get("oneOf"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|alternative| {
+            let properties = alternative.get("properties")?.as_object()?;
+            match properties.iter().next() {
+                Some((key, schema)) if properties.len() == 1 => {
+                    Some((key.as_str(), resolve(schema)))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// JSON types accepted by `schema`, including those of its alternatives.
+pub(crate) fn accepted_types(schema: &'static Value) -> Vec<&'static str> {
+    let schema = resolve(schema);
+    let mut types = match schema.get("type") {
+        Some(Value::String(name)) => vec![name.as_str()],
+        other => strings(other),
+    };
+    for keyword in ["oneOf", "anyOf"] {
+        for alternative in schema
+            .get(keyword)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            types.extend(accepted_types(alternative));
+        }
+    }
+    types
+}
@@ -0,0 +1,326 @@
+//! Minimal reader for OpenWrt UCI files (eg. `/etc/config/njupt_wifi`).
+//!
+//! ```text
+//! config njupt_wifi 'main'
+//!     option isp 'CT'
+//!     option userid 'B22999999'
+//!     option password 'password123456'
+//!     option check_interval '1200'
+//! ```
+use crate::login_config::LoginConfig;
+use crate::schema::{accepted_types, field_schema, keyed_alternatives};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use thiserror::Error;
+
+/// Type of the section which holds the login config.
+pub const UCI_SECTION_TYPE: &str = "njupt_wifi";
+
+#[derive(Debug, Clone, Default)]
+pub struct UciSection {
+    pub section_type: String,
+    pub name: Option<String>,
+    pub options: BTreeMap<String, String>,
+    pub lists: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Error, Debug)]
+pub enum UciError {
+    #[error("syntax error at line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("no `{0}` section found")]
+    SectionNotFound(String),
+    #[error("invalid value `{value}` for option `{name}`")]
+    InvalidValue { name: String, value: String },
+    #[error("invalid config")]
+    InvalidConfig(#[from] serde_yaml::Error),
+}
+
+/// Split a line into words, handling quotes, escapes and comments like the `uci` tool does.
+fn tokenize(line: &str, line_number: usize) -> Result<Vec<String>, UciError> {
+    let syntax_error = |message: &str| UciError::Syntax {
+        line: line_number,
+        message: message.to_string(),
+    };
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.peek() {
+            None | Some('#') => break,
+            _ => {}
+        }
+        let mut word = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            match c {
+                '\'' => loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(syntax_error("unterminated single quote")),
+                    }
+                },
+                '"' => loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => word.push(c),
+                            None => return Err(syntax_error("unterminated double quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(syntax_error("unterminated double quote")),
+                    }
+                },
+                '\\' => match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err(syntax_error("unexpected end of line after `\\`")),
+                },
+                _ => word.push(c),
+            }
+        }
+        words.push(word);
+    }
+    Ok(words)
+}
+
+pub fn parse_uci(s: &str) -> Result<Vec<UciSection>, UciError> {
+    let mut sections: Vec<UciSection> = Vec::new();
+    for (index, line) in s.lines().enumerate() {
+        let line_number = index + 1;
+        let syntax_error = |message: &str| UciError::Syntax {
+            line: line_number,
+            message: message.to_string(),
+        };
+        let words = tokenize(line, line_number)?;
+        let Some((keyword, args)) = words.split_first() else {
+            continue;
+        };
+        match keyword.as_str() {
+            "package" => {}
+            "config" => {
+                let (section_type, name) = match args {
+                    [section_type] => (section_type.clone(), None),
+                    [section_type, name] => (section_type.clone(), Some(name.clone())),
+                    _ => return Err(syntax_error("expected `config <type> [name]`")),
+                };
+                sections.push(UciSection {
+                    section_type,
+                    name,
+                    ..Default::default()
+                });
+            }
+            "option" | "list" => {
+                let [name, value] = args else {
+                    return Err(syntax_error(&format!(
+                        "expected `{} <name> <value>`",
+                        keyword
+                    )));
+                };
+                let section = sections
+                    .last_mut()
+                    .ok_or_else(|| syntax_error("option outside of a section"))?;
+                if keyword == "option" {
+                    section.options.insert(name.clone(), value.clone());
+                } else {
+                    section
+                        .lists
+                        .entry(name.clone())
+                        .or_default()
+                        .push(value.clone());
+                }
+            }
+            _ => return Err(syntax_error(&format!("unknown keyword `{}`", keyword))),
+        }
+    }
+    Ok(sections)
+}
+
+pub fn login_config_from_uci(s: &str) -> Result<LoginConfig, UciError> {
+    let sections = parse_uci(s)?;
+    let section = sections
+        .into_iter()
+        .find(|x| x.section_type == UCI_SECTION_TYPE)
+        .ok_or_else(|| UciError::SectionNotFound(UCI_SECTION_TYPE.to_string()))?;
+    login_config_from_uci_section(&section)
+}
+
+/// Prefixes of the options of the sections of `LoginConfig`, e.g. `log_level` for
+/// `logging.level`. Options without one of them are top-level fields.
+const UCI_SECTIONS: [(&str, &str); 5] = [
+    ("log_", "logging"),
+    ("dns_", "dns"),
+    ("metrics_", "metrics"),
+    ("mqtt_", "mqtt"),
+    ("hook_", "hooks"),
+];
+
+/// Where an option is written to: its section, the key in it, the source of a password
+/// (e.g. `env` for `password_env`) and the schema of the value.
+struct UciField {
+    section: &'static str,
+    key: String,
+    source: Option<&'static str>,
+    schema: &'static serde_json::Value,
+}
+
+/// Look the option up in the schema of `LoginConfig`, `None` for unknown options.
+fn uci_field(name: &str) -> Option<UciField> {
+    let (section, key) = UCI_SECTIONS
+        .iter()
+        .find_map(|(prefix, section)| name.strip_prefix(prefix).map(|key| (*section, key)))
+        .unwrap_or(("", name));
+    let path = |key: &str| match section {
+        "" => key.to_string(),
+        section => format!("{}.{}", section, key),
+    };
+    if let Some(schema) = field_schema(&path(key)).filter(|_| !key.is_empty()) {
+        return Some(UciField {
+            section,
+            key: key.to_string(),
+            source: None,
+            schema,
+        });
+    }
+    let (key, source) = key.split_once('_')?;
+    keyed_alternatives(&path(key))
+        .into_iter()
+        .find(|(alternative, _)| *alternative == source)
+        .map(|(source, schema)| UciField {
+            section,
+            key: key.to_string(),
+            source: Some(source),
+            schema,
+        })
+}
+
+/// Convert an option to the type of its field, `None` for fields which are not scalars.
+fn uci_value(name: &str, value: &str, field: &UciField) -> Result<Option<Value>, UciError> {
+    let invalid = || UciError::InvalidValue {
+        name: name.to_string(),
+        value: value.to_string(),
+    };
+    let types = accepted_types(field.schema);
+    if types.contains(&"string") {
+        Ok(Some(value.into()))
+    } else if types.contains(&"integer") {
+        value
+            .parse::<u64>()
+            .map(|n| Some(n.into()))
+            .map_err(|_| invalid())
+    } else if types.contains(&"boolean") {
+        // Booleans are written as in other UCI configs.
+        match value {
+            "1" | "true" | "yes" | "on" => Ok(Some(true.into())),
+            "0" | "false" | "no" | "off" => Ok(Some(false.into())),
+            _ => Err(invalid()),
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+/// Map the flat UCI options to the layout of `LoginConfig`,
+/// so the same defaults and validation as YAML apply.
+pub fn login_config_from_uci_section(section: &UciSection) -> Result<LoginConfig, UciError> {
+    let mut sections: BTreeMap<&str, Mapping> = BTreeMap::new();
+    let mut insert = |field: UciField, value: Value| {
+        let value = match field.source {
+            Some(source) => Value::Mapping(Mapping::from_iter([(source.into(), value)])),
+            None => value,
+        };
+        sections
+            .entry(field.section)
+            .or_default()
+            .insert(field.key.into(), value);
+    };
+    for (name, value) in section.options.iter() {
+        // Unknown options are ignored, as they may be used by LuCI or other tools.
+        let Some(field) = uci_field(name) else {
+            continue;
+        };
+        if let Some(value) = uci_value(name, value, &field)? {
+            insert(field, value);
+        }
+    }
+    for (name, values) in section.lists.iter() {
+        let Some(field) = uci_field(name) else {
+            continue;
+        };
+        if accepted_types(field.schema).contains(&"array") {
+            let values: Vec<Value> = values.iter().map(|arg| arg.as_str().into()).collect();
+            insert(field, values.into());
+        }
+    }
+    let mut logging = sections.remove("logging").unwrap_or_default();
+    let mut dns = sections.remove("dns").unwrap_or_default();
+    if let Some(modules) = section.lists.get("log_module") {
+        let mut levels = Mapping::new();
+        for module in modules {
+            let (module, level) = module
+                .split_once('=')
+                .ok_or_else(|| UciError::InvalidValue {
+                    name: "log_module".to_string(),
+                    value: module.clone(),
+                })?;
+            levels.insert(module.into(), level.into());
+        }
+        logging.insert("modules".into(), levels.into());
+    }
+    // `list dns_server '223.5.5.5'` or with a port, with the protocol of `option dns_protocol`,
+    // or with its own protocol and certificate name, e.g. `'tls://223.5.5.5#dns.alidns.com'`.
+    if let Some(servers) = section.lists.get("dns_server") {
+        let mut entries = Vec::new();
+        for server in servers {
+            let (protocol, rest) = match server.split_once("://") {
+                Some((protocol, rest)) => (Some(protocol), rest),
+                None => (
+                    section.options.get("dns_protocol").map(String::as_str),
+                    server.as_str(),
+                ),
+            };
+            let (rest, tls_name) = match rest.split_once('#') {
+                Some((rest, tls_name)) => (rest, Some(tls_name)),
+                None => (rest, None),
+            };
+            let (address, port) = match rest.parse::<SocketAddr>() {
+                Ok(address) => (address.ip(), Some(address.port())),
+                Err(_) => match rest.parse::<IpAddr>() {
+                    Ok(address) => (address, None),
+                    Err(_) => {
+                        return Err(UciError::InvalidValue {
+                            name: "dns_server".to_string(),
+                            value: server.clone(),
+                        })
+                    }
+                },
+            };
+            let mut entry = Mapping::new();
+            entry.insert("address".into(), address.to_string().into());
+            if let Some(port) = port {
+                entry.insert("port".into(), port.into());
+            }
+            if let Some(protocol) = protocol {
+                entry.insert("protocol".into(), protocol.into());
+            }
+            if let Some(tls_name) = tls_name {
+                entry.insert("tls_name".into(), tls_name.into());
+            }
+            entries.push(Value::Mapping(entry));
+        }
+        dns.insert("servers".into(), entries.into());
+    }
+    sections.insert("logging", logging);
+    sections.insert("dns", dns);
+    let mut mapping = sections.remove("").unwrap_or_default();
+    for (name, section) in sections {
+        if !section.is_empty() {
+            mapping.insert(name.into(), section.into());
+        }
+    }
+    Ok(serde_yaml::from_value(Value::Mapping(mapping))?)
+}
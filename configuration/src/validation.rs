@@ -0,0 +1,847 @@
+//! Field-level validation of config files.
+//!
+//! serde reports a typo in `isp` or a malformed `password` as a generic error,
+//! and loses the location because of `#[serde(flatten)]`. This module checks the
+//! document field by field, so that each error points to the offending line.
+use crate::config_file::ConfigFormat;
+use crate::login_config::LoginConfig;
+use crate::migration::migrate;
+use crate::password::Password;
+use crate::schema::{enum_values, field_names, keyed_alternatives, required_fields};
+use byte_unit::Byte;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+#[derive(Debug, Clone, Default)]
+pub struct FieldError {
+    /// Dotted path of the field, empty for the whole document.
+    pub field: String,
+    /// 1-based line and column.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+    pub allowed: Vec<String>,
+    pub suggestion: Option<String>,
+}
+
+impl FieldError {
+    fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+            ..Default::default()
+        }
+    }
+
+    fn with_allowed(mut self, allowed: &[&str], value: &str) -> Self {
+        self.allowed = allowed.iter().map(|s| s.to_string()).collect();
+        self.suggestion = suggest(value, allowed).map(|s| s.to_string());
+        self
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.field.is_empty() {
+            write!(f, "`{}`", self.field)?;
+        }
+        if let Some((line, column)) = self.location {
+            if !self.field.is_empty() {
+                f.write_str(" ")?;
+            }
+            write!(f, "at line {}, column {}", line, column)?;
+        }
+        if !self.field.is_empty() || self.location.is_some() {
+            f.write_str(": ")?;
+        }
+        f.write_str(&self.message)?;
+        if !self.allowed.is_empty() {
+            write!(f, " (allowed values: {})", self.allowed.join(", "))?;
+        }
+        if let Some(suggestion) = self.suggestion.as_ref() {
+            write!(f, ", did you mean `{}`?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigValidationErrors(pub Vec<FieldError>);
+
+impl fmt::Display for ConfigValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.as_slice() {
+            [error] => write!(f, "{}", error),
+            errors => {
+                write!(f, "{} errors found", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  - {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigValidationErrors {}
+
+impl LoginConfig {
+    /// Check the values which are well-typed but not usable.
+    pub fn validate(&self) -> Result<(), ConfigValidationErrors> {
+        let mut errors = Vec::new();
+        if self.credential.userid().trim().is_empty() {
+            errors.push(FieldError::new("userid", "must not be empty"));
+        }
+        if self.interface.as_deref().is_some_and(str::is_empty) {
+            errors.push(FieldError::new(
+                "interface",
+                "must not be empty, use null to not specify it",
+            ));
+        }
+        if self.logging.file_count == Some(0) {
+            errors.push(FieldError::new("logging.file_count", "must be at least 1"));
+        }
+        if self.logging.file.as_deref().is_some_and(str::is_empty) {
+            errors.push(FieldError::new(
+                "logging.file",
+                "must not be empty, use null for the default",
+            ));
+        }
+        if self.logging.modules.keys().any(|module| module.is_empty()) {
+            errors.push(FieldError::new(
+                "logging.modules",
+                "module paths must not be empty",
+            ));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigValidationErrors(errors))
+        }
+    }
+}
+
+/// Parse and validate a config document, reporting every problem with its location.
+pub fn validate_config_source(
+    format: ConfigFormat,
+    s: &str,
+) -> Result<LoginConfig, ConfigValidationErrors> {
+    let mut errors = match parse_value(format, s) {
+        Ok(Some(mut value)) => match migrate(&mut value) {
+            Ok(_) => check_document(&value),
+            Err(e) => vec![FieldError::new("version", e.to_string())],
+        },
+        Ok(None) => Vec::new(),
+        Err(error) => return Err(ConfigValidationErrors(vec![error])),
+    };
+    if errors.is_empty() {
+        match format.parse(s) {
+            Ok(config) => match config.validate() {
+                Ok(()) => return Ok(config),
+                Err(ConfigValidationErrors(e)) => errors = e,
+            },
+            Err(e) => errors.push(FieldError::new("", e.to_string())),
+        }
+    }
+    for error in errors.iter_mut() {
+        if error.location.is_none() && !error.field.is_empty() {
+            error.location = locate(format, s, &error.field);
+        }
+    }
+    Err(ConfigValidationErrors(errors))
+}
+
+/// Parse into a generic tree, or `None` for formats which are checked by their own parser.
+fn parse_value(format: ConfigFormat, s: &str) -> Result<Option<Value>, FieldError> {
+    let syntax_error = |message: String, location: Option<(usize, usize)>| {
+        // serde_yaml and serde_json put the location in the message as well.
+        let message = match location {
+            Some((line, column)) => {
+                message.replacen(&format!(" at line {} column {}", line, column), "", 1)
+            }
+            None => message,
+        };
+        FieldError {
+            location,
+            ..FieldError::new("", message)
+        }
+    };
+    match format {
+        ConfigFormat::Yaml => serde_yaml::from_str(s).map(Some).map_err(|e| {
+            let location = e.location().map(|l| (l.line(), l.column()));
+            syntax_error(e.to_string(), location)
+        }),
+        ConfigFormat::Toml => toml::from_str(s).map(Some).map_err(|e| {
+            let location = e.span().map(|span| line_column(s, span.start));
+            syntax_error(e.message().to_string(), location)
+        }),
+        ConfigFormat::Json => serde_json::from_str(s)
+            .map(Some)
+            .map_err(|e| syntax_error(e.to_string(), Some((e.line(), e.column())))),
+        ConfigFormat::Uci => Ok(None),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "a list",
+        Value::Object(_) => "a mapping",
+    }
+}
+
+fn check_unknown_fields(
+    map: &Map<String, Value>,
+    prefix: &str,
+    known: &[&str],
+    errors: &mut Vec<FieldError>,
+) {
+    for key in map.keys().filter(|key| !known.contains(&key.as_str())) {
+        let mut error = FieldError::new(format!("{}{}", prefix, key), "unknown field");
+        error.suggestion = suggest(key, known).map(|s| s.to_string());
+        errors.push(error);
+    }
+}
+
+fn check_document(value: &Value) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let map = match value {
+        Value::Object(map) => map,
+        value => {
+            errors.push(FieldError::new(
+                "",
+                format!("expected a mapping, found {}", type_name(value)),
+            ));
+            return errors;
+        }
+    };
+    check_unknown_fields(map, "", &field_names(""), &mut errors);
+    for field in required_fields("") {
+        if !map.contains_key(field) {
+            errors.push(FieldError::new(field, "missing required field"));
+        }
+    }
+    if let Some(userid) = map.get("userid") {
+        if !userid.is_string() {
+            errors.push(FieldError::new(
+                "userid",
+                format!("expected a string, found {}", type_name(userid)),
+            ));
+        }
+    }
+    if let Some(isp) = map.get("isp") {
+        let isp_types = enum_values("isp");
+        match isp.as_str() {
+            Some(s) if isp_types.contains(&s) => {}
+            Some(s) => errors.push(
+                FieldError::new("isp", format!("unknown ISP `{}`", s)).with_allowed(&isp_types, s),
+            ),
+            None => errors.push(
+                FieldError::new(
+                    "isp",
+                    format!("expected a string, found {}", type_name(isp)),
+                )
+                .with_allowed(&isp_types, ""),
+            ),
+        }
+    }
+    if let Some(password) = map.get("password") {
+        check_password("password", password, &mut errors);
+    }
+    if let Some(check_interval) = map.get("check_interval") {
+        if !check_interval.is_u64() {
+            errors.push(FieldError::new(
+                "check_interval",
+                format!(
+                    "expected a non-negative integer of seconds, found {}",
+                    type_name(check_interval)
+                ),
+            ));
+        }
+    }
+    if let Some(interface) = map.get("interface") {
+        if !interface.is_string() && !interface.is_null() {
+            errors.push(FieldError::new(
+                "interface",
+                format!("expected a string or null, found {}", type_name(interface)),
+            ));
+        }
+    }
+    match map.get("logging") {
+        Some(Value::Object(logging)) => check_logging(logging, &mut errors),
+        Some(Value::Null) | None => {}
+        Some(value) => errors.push(FieldError::new(
+            "logging",
+            format!("expected a mapping, found {}", type_name(value)),
+        )),
+    }
+    match map.get("dns") {
+        Some(Value::Object(dns)) => check_dns(dns, &mut errors),
+        Some(Value::Null) | None => {}
+        Some(value) => errors.push(FieldError::new(
+            "dns",
+            format!("expected a mapping, found {}", type_name(value)),
+        )),
+    }
+    match map.get("metrics") {
+        Some(Value::Object(metrics)) => check_metrics(metrics, &mut errors),
+        Some(Value::Null) | None => {}
+        Some(value) => errors.push(FieldError::new(
+            "metrics",
+            format!("expected a mapping, found {}", type_name(value)),
+        )),
+    }
+    match map.get("notifications") {
+        Some(Value::Object(notifications)) => check_notifications(notifications, &mut errors),
+        Some(Value::Null) | None => {}
+        Some(value) => errors.push(FieldError::new(
+            "notifications",
+            format!("expected a mapping, found {}", type_name(value)),
+        )),
+    }
+    match map.get("hooks") {
+        Some(Value::Object(hooks)) => check_hooks(hooks, &mut errors),
+        Some(Value::Null) | None => {}
+        Some(value) => errors.push(FieldError::new(
+            "hooks",
+            format!("expected a mapping, found {}", type_name(value)),
+        )),
+    }
+    match map.get("mqtt") {
+        Some(Value::Object(mqtt)) => check_mqtt(mqtt, &mut errors),
+        Some(Value::Null) | None => {}
+        Some(value) => errors.push(FieldError::new(
+            "mqtt",
+            format!("expected a mapping, found {}", type_name(value)),
+        )),
+    }
+    errors
+}
+
+/// `field` is the path of a password, whose sources are taken from the schema.
+fn check_password(field: &str, password: &Value, errors: &mut Vec<FieldError>) {
+    let map = match password {
+        Value::String(_) => return,
+        Value::Object(map) => map,
+        value => {
+            errors.push(FieldError::new(
+                field,
+                format!(
+                    "expected a plain-text string or a mapping, found {}",
+                    type_name(value)
+                ),
+            ));
+            return;
+        }
+    };
+    let known: Vec<_> = keyed_alternatives(field)
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    check_unknown_fields(map, &format!("{}.", field), &known, errors);
+    let sources: Vec<_> = known
+        .iter()
+        .filter_map(|key| map.get(*key).map(|value| (*key, value)))
+        .collect();
+    let (key, value) = match sources.as_slice() {
+        [source] => *source,
+        [] => {
+            // Unknown keys are reported with suggestions above.
+            errors
+                .push(FieldError::new(field, "expected one of the keys").with_allowed(&known, ""));
+            return;
+        }
+        _ => {
+            errors.push(FieldError::new(
+                field,
+                format!(
+                    "only one of {} is allowed",
+                    sources
+                        .iter()
+                        .map(|(key, _)| format!("`{}`", key))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ));
+            return;
+        }
+    };
+    let field = format!("{}.{}", field, key);
+    match key {
+        "data_protection" => {
+            let value = Value::Object(Map::from_iter([(key.to_string(), value.clone())]));
+            if serde_json::from_value::<Password>(value).is_err() {
+                errors.push(FieldError::new(
+                    field,
+                    "not a valid encrypted password on this platform, set the password again",
+                ));
+            }
+        }
+        "keyring" => {
+            let value = Value::Object(Map::from_iter([(key.to_string(), value.clone())]));
+            if serde_json::from_value::<Password>(value).is_err() {
+                errors.push(FieldError::new(
+                    field,
+                    "expected `secret-service:<id>` or `keyutils:<id>`, which is only supported on Linux",
+                ));
+            }
+        }
+        "command" => {
+            let valid = value.as_array().is_some_and(|command| {
+                !command.is_empty() && command.iter().all(|arg| arg.is_string())
+            });
+            if !valid {
+                errors.push(FieldError::new(
+                    field,
+                    "expected a non-empty list of the program and its arguments",
+                ));
+            }
+        }
+        _ => {
+            if !value.is_string() {
+                errors.push(FieldError::new(
+                    field,
+                    format!("expected a string, found {}", type_name(value)),
+                ));
+            }
+        }
+    }
+}
+
+/// Check a string which must be one of `allowed`.
+/// `schema_path` is `field` with `*` for list items and mapping values, e.g. `dns.servers.*.protocol`.
+fn check_enum(field: &str, schema_path: &str, value: &Value, errors: &mut Vec<FieldError>) {
+    let allowed = enum_values(schema_path);
+    let allowed = allowed.as_slice();
+    match value.as_str() {
+        Some(s) if allowed.contains(&s) => {}
+        Some(s) => errors.push(
+            FieldError::new(field, format!("unknown value `{}`", s)).with_allowed(allowed, s),
+        ),
+        None => errors.push(
+            FieldError::new(
+                field,
+                format!("expected a string, found {}", type_name(value)),
+            )
+            .with_allowed(allowed, ""),
+        ),
+    }
+}
+
+fn check_logging(logging: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    check_unknown_fields(logging, "logging.", &field_names("logging"), errors);
+    if let Some(sink) = logging.get("sink") {
+        check_enum("logging.sink", "logging.sink", sink, errors);
+    }
+    if let Some(level) = logging.get("level").filter(|level| !level.is_null()) {
+        check_enum("logging.level", "logging.level", level, errors);
+    }
+    if let Some(format) = logging.get("format") {
+        check_enum("logging.format", "logging.format", format, errors);
+    }
+    match logging.get("modules") {
+        Some(Value::Object(modules)) => {
+            for (module, level) in modules {
+                check_enum(
+                    &format!("logging.modules.{}", module),
+                    "logging.modules.*",
+                    level,
+                    errors,
+                );
+            }
+        }
+        Some(Value::Null) | None => {}
+        Some(value) => errors.push(FieldError::new(
+            "logging.modules",
+            format!(
+                "expected a mapping of module paths to levels, found {}",
+                type_name(value)
+            ),
+        )),
+    }
+    if let Some(file) = logging.get("file") {
+        if !file.is_string() && !file.is_null() {
+            errors.push(FieldError::new(
+                "logging.file",
+                format!("expected a string or null, found {}", type_name(file)),
+            ));
+        }
+    }
+    if let Some(size_limit) = logging.get("size_limit") {
+        if serde_json::from_value::<Option<Byte>>(size_limit.clone()).is_err() {
+            errors.push(FieldError::new(
+                "logging.size_limit",
+                "expected a size in bytes or with a unit (e.g. 3MiB)",
+            ));
+        }
+    }
+    if let Some(file_count) = logging.get("file_count") {
+        if !file_count.is_null()
+            && file_count
+                .as_u64()
+                .and_then(|n| u32::try_from(n).ok())
+                .is_none()
+        {
+            errors.push(FieldError::new(
+                "logging.file_count",
+                format!(
+                    "expected a non-negative integer, found {}",
+                    type_name(file_count)
+                ),
+            ));
+        }
+    }
+    if let Some(compress) = logging.get("compress") {
+        if !compress.is_boolean() {
+            errors.push(FieldError::new(
+                "logging.compress",
+                format!("expected a boolean, found {}", type_name(compress)),
+            ));
+        }
+    }
+}
+
+fn check_dns(dns: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    check_unknown_fields(dns, "dns.", &field_names("dns"), errors);
+    match dns.get("servers") {
+        Some(Value::Array(servers)) if !servers.is_empty() => {
+            for (index, server) in servers.iter().enumerate() {
+                let prefix = format!("dns.servers.{}.", index);
+                match server {
+                    Value::Object(server) => check_dns_server(&prefix, server, errors),
+                    value => errors.push(FieldError::new(
+                        prefix.trim_end_matches('.'),
+                        format!("expected a mapping, found {}", type_name(value)),
+                    )),
+                }
+            }
+        }
+        Some(_) => errors.push(FieldError::new(
+            "dns.servers",
+            "expected a non-empty list of servers",
+        )),
+        None => {}
+    }
+    for field in ["timeout", "attempts"] {
+        if let Some(value) = dns.get(field) {
+            if value.as_u64().is_none_or(|n| n == 0) {
+                errors.push(FieldError::new(
+                    format!("dns.{}", field),
+                    format!("expected a positive integer, found {}", type_name(value)),
+                ));
+            }
+        }
+    }
+    if let Some(ordering) = dns.get("ordering") {
+        check_enum("dns.ordering", "dns.ordering", ordering, errors);
+    }
+    if let Some(policy) = dns.get("policy") {
+        check_enum("dns.policy", "dns.policy", policy, errors);
+        if policy.as_str() == Some("encrypted_probes") {
+            // Without `servers`, only the plain default servers are used.
+            let protocols: Vec<&str> = dns
+                .get("servers")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .map(|server| {
+                    server
+                        .get("protocol")
+                        .and_then(Value::as_str)
+                        .unwrap_or("udp")
+                })
+                .collect();
+            let encrypted = |protocol: &&str| matches!(*protocol, "tls" | "https");
+            if !protocols.iter().any(encrypted) || protocols.iter().all(encrypted) {
+                errors.push(FieldError::new(
+                    "dns.policy",
+                    "encrypted_probes needs an encrypted (tls or https) and a plain (udp or tcp) server",
+                ));
+            }
+        }
+    }
+}
+
+fn check_dns_server(prefix: &str, server: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    check_unknown_fields(server, prefix, &field_names("dns.servers.*"), errors);
+    match server.get("address") {
+        Some(address)
+            if address
+                .as_str()
+                .and_then(|s| s.parse::<IpAddr>().ok())
+                .is_some() => {}
+        Some(_) => errors.push(FieldError::new(
+            format!("{}address", prefix),
+            "expected an IP address without a port, e.g. 223.5.5.5",
+        )),
+        None => errors.push(FieldError::new(
+            format!("{}address", prefix),
+            "missing required field",
+        )),
+    }
+    if let Some(port) = server.get("port").filter(|port| !port.is_null()) {
+        if port.as_u64().and_then(|n| u16::try_from(n).ok()).is_none() {
+            errors.push(FieldError::new(
+                format!("{}port", prefix),
+                format!("expected a port number, found {}", type_name(port)),
+            ));
+        }
+    }
+    let protocol = server.get("protocol");
+    if let Some(protocol) = protocol {
+        check_enum(
+            &format!("{}protocol", prefix),
+            "dns.servers.*.protocol",
+            protocol,
+            errors,
+        );
+    }
+    let encrypted = matches!(
+        protocol.and_then(Value::as_str),
+        Some("tls") | Some("https")
+    );
+    match server
+        .get("tls_name")
+        .filter(|tls_name| !tls_name.is_null())
+    {
+        Some(Value::String(tls_name)) if !tls_name.is_empty() => {}
+        Some(value) => errors.push(FieldError::new(
+            format!("{}tls_name", prefix),
+            format!("expected a host name, found {}", type_name(value)),
+        )),
+        None if encrypted => errors.push(FieldError::new(
+            format!("{}tls_name", prefix),
+            "required by tls and https to verify the certificate of the server",
+        )),
+        None => {}
+    }
+}
+
+fn check_metrics(metrics: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    check_unknown_fields(metrics, "metrics.", &field_names("metrics"), errors);
+    if let Some(listen) = metrics.get("listen") {
+        if listen
+            .as_str()
+            .and_then(|s| s.parse::<SocketAddr>().ok())
+            .is_none()
+        {
+            errors.push(FieldError::new(
+                "metrics.listen",
+                "expected an address with a port, e.g. 127.0.0.1:9464",
+            ));
+        }
+    }
+}
+
+fn check_notifications(notifications: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    let categories = field_names("notifications");
+    check_unknown_fields(notifications, "notifications.", &categories, errors);
+    for (category, rule) in notifications
+        .iter()
+        .filter(|(category, _)| categories.contains(&category.as_str()))
+    {
+        let prefix = format!("notifications.{}.", category);
+        let rule = match rule {
+            Value::Object(rule) => rule,
+            value => {
+                errors.push(FieldError::new(
+                    format!("notifications.{}", category),
+                    format!("expected a mapping, found {}", type_name(value)),
+                ));
+                continue;
+            }
+        };
+        let rule_fields = field_names(&format!("notifications.{}", category));
+        check_unknown_fields(rule, &prefix, &rule_fields, errors);
+        if let Some(enabled) = rule.get("enabled").filter(|enabled| !enabled.is_boolean()) {
+            errors.push(FieldError::new(
+                format!("{}enabled", prefix),
+                format!("expected a boolean, found {}", type_name(enabled)),
+            ));
+        }
+        if let Some(min_interval) = rule
+            .get("min_interval")
+            .filter(|min_interval| !min_interval.is_u64())
+        {
+            errors.push(FieldError::new(
+                format!("{}min_interval", prefix),
+                format!(
+                    "expected a non-negative integer of seconds, found {}",
+                    type_name(min_interval)
+                ),
+            ));
+        }
+    }
+}
+
+fn check_hooks(hooks: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    let hook_fields = field_names("hooks");
+    check_unknown_fields(hooks, "hooks.", &hook_fields, errors);
+    for (name, value) in hooks {
+        if name == "timeout" {
+            if !value.is_u64() {
+                errors.push(FieldError::new(
+                    "hooks.timeout",
+                    format!(
+                        "expected a non-negative integer of seconds, found {}",
+                        type_name(value)
+                    ),
+                ));
+            }
+            continue;
+        }
+        if !hook_fields.contains(&name.as_str()) || value.is_null() {
+            continue;
+        }
+        let valid = value.as_array().is_some_and(|command| {
+            !command.is_empty() && command.iter().all(|arg| arg.is_string())
+        });
+        if !valid {
+            errors.push(FieldError::new(
+                format!("hooks.{}", name),
+                "expected a non-empty list of the program and its arguments",
+            ));
+        }
+    }
+}
+
+fn check_mqtt(mqtt: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    check_unknown_fields(mqtt, "mqtt.", &field_names("mqtt"), errors);
+    match mqtt.get("host") {
+        Some(Value::String(host)) if !host.is_empty() => {}
+        Some(value) => errors.push(FieldError::new(
+            "mqtt.host",
+            format!(
+                "expected a host name or address, found {}",
+                type_name(value)
+            ),
+        )),
+        None => errors.push(FieldError::new("mqtt.host", "missing required field")),
+    }
+    if let Some(port) = mqtt.get("port") {
+        if port.as_u64().and_then(|n| u16::try_from(n).ok()).is_none() {
+            errors.push(FieldError::new(
+                "mqtt.port",
+                format!("expected a port number, found {}", type_name(port)),
+            ));
+        }
+    }
+    if let Some(username) = mqtt.get("username") {
+        if !username.is_string() && !username.is_null() {
+            errors.push(FieldError::new(
+                "mqtt.username",
+                format!("expected a string or null, found {}", type_name(username)),
+            ));
+        }
+    }
+    if let Some(password) = mqtt.get("password").filter(|password| !password.is_null()) {
+        check_password("mqtt.password", password, errors);
+    }
+    for field in ["client_id", "topic_prefix", "discovery_prefix"] {
+        let Some(value) = mqtt.get(field) else {
+            continue;
+        };
+        if field == "discovery_prefix" && value.is_null() {
+            continue;
+        }
+        let valid = value
+            .as_str()
+            .is_some_and(|s| !s.is_empty() && !s.contains(['+', '#']));
+        if !valid {
+            errors.push(FieldError::new(
+                format!("mqtt.{}", field),
+                "expected a non-empty string without the wildcards `+` and `#`",
+            ));
+        }
+    }
+}
+
+/// The closest candidate within a small edit distance, ignoring case.
+fn suggest<'a>(value: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    if value.is_empty() {
+        return None;
+    }
+    let value = value.to_ascii_lowercase();
+    candidates
+        .iter()
+        .map(|candidate| {
+            (
+                edit_distance(&value, &candidate.to_ascii_lowercase()),
+                *candidate,
+            )
+        })
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(row[j + 1])
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+fn line_column(s: &str, offset: usize) -> (usize, usize) {
+    let before = &s[..offset.min(s.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+/// Find where a field is written, by looking for its key line by line.
+/// Nested fields are searched after their parent.
+fn locate(format: ConfigFormat, s: &str, field: &str) -> Option<(usize, usize)> {
+    let mut start_line = 0;
+    let mut found = None;
+    for key in field.split('.') {
+        let position = s
+            .lines()
+            .enumerate()
+            .skip(start_line)
+            .find_map(|(index, line)| {
+                let trimmed = line.trim_start();
+                let column = line.len() - trimmed.len();
+                let matched = match format {
+                    ConfigFormat::Yaml => trimmed
+                        .strip_prefix(key)
+                        .is_some_and(|rest| rest.trim_start().starts_with(':')),
+                    ConfigFormat::Toml => {
+                        trimmed
+                            .strip_prefix(key)
+                            .is_some_and(|rest| rest.trim_start().starts_with('='))
+                            || trimmed.starts_with(&format!("[{}]", key))
+                    }
+                    ConfigFormat::Json => {
+                        return line
+                            .find(&format!("\"{}\"", key))
+                            .map(|column| (index, column));
+                    }
+                    ConfigFormat::Uci => false,
+                };
+                matched.then_some((index, column))
+            });
+        // Fall back to the parent, e.g. for inline mappings.
+        let Some((index, column)) = position else {
+            break;
+        };
+        start_line = index + 1;
+        found = Some((index + 1, column + 1));
+    }
+    found
+}
@@ -10,7 +10,7 @@ use windows::Win32::Security::Cryptography::CryptProtectData;
 use windows::Win32::Security::Cryptography::CryptUnprotectData;
 use windows::Win32::Security::Cryptography::CRYPTPROTECT_LOCAL_MACHINE;
 use windows::Win32::Security::Cryptography::CRYPT_INTEGER_BLOB;
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(transparent)]
 #[repr(transparent)]
 pub struct Win32ProtectedData {
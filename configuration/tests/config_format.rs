@@ -0,0 +1,155 @@
+use njupt_wifi_login_configuration::{
+    config_file::{read_config_file, write_config_file, ConfigFormat},
+    login_config::LoginConfig,
+};
+
+const BASIC: &str = r#"
+userid: B22999999
+password: password123456
+isp: CT
+check_interval: 600
+interface: eth0
+logging:
+  sink: stderr
+  level: debug
+  modules:
+    hyper: warn
+  format: json
+  size_limit: 3145728
+  file_count: 2
+  compress: true
+dns:
+  servers:
+    - address: 223.5.5.5
+    - address: "2400:3200::1"
+      port: 53
+      protocol: tcp
+    - address: 223.5.5.5
+      protocol: tls
+      tls_name: dns.alidns.com
+  ordering: user_provided
+  policy: encrypted_probes
+"#;
+
+#[cfg(not(target_os = "windows"))]
+const LOCAL_MACHINE: &str = r#"
+userid: B22999999
+password:
+  data_protection: v1$m$000102030405060708090a0b$0c0d0e0f
+isp: EDU
+"#;
+
+const EXTERNAL: [&str; 3] = [
+    "userid: a\nisp: EDU\npassword:\n  env: NJUPT_WIFI_PASSWORD\n",
+    "userid: a\nisp: EDU\npassword:\n  file: ${CREDENTIALS_DIRECTORY}/password\n",
+    "userid: a\nisp: EDU\npassword:\n  command: [pass, show, njupt]\n",
+];
+
+fn round_trip(yaml: &str) {
+    let config: LoginConfig = serde_yaml::from_str(yaml).unwrap();
+    let expected = serde_yaml::to_string(&config).unwrap();
+    for format in [ConfigFormat::Yaml, ConfigFormat::Toml, ConfigFormat::Json] {
+        let s = format.serialize(&config).unwrap();
+        let parsed = format.parse(&s).unwrap();
+        assert_eq!(
+            serde_yaml::to_string(&parsed).unwrap(),
+            expected,
+            "{:?}:\n{}",
+            format,
+            s
+        );
+    }
+}
+
+#[test]
+fn basic_password_round_trip() {
+    round_trip(BASIC);
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn local_machine_password_round_trip() {
+    round_trip(LOCAL_MACHINE);
+}
+
+#[test]
+fn external_password_round_trip() {
+    for yaml in EXTERNAL {
+        round_trip(yaml);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn local_machine_password_is_kept_as_is() {
+    let config: LoginConfig = serde_yaml::from_str(LOCAL_MACHINE).unwrap();
+    let protected = "v1$m$000102030405060708090a0b$0c0d0e0f";
+    let toml = ConfigFormat::Toml.serialize(&config).unwrap();
+    assert!(toml.contains(protected), "{}", toml);
+    let json = ConfigFormat::Json.serialize(&config).unwrap();
+    assert!(json.contains(protected), "{}", json);
+}
+
+#[test]
+fn format_from_path() {
+    use std::path::Path;
+    assert_eq!(
+        ConfigFormat::from_path(Path::new("a.yml")),
+        ConfigFormat::Yaml
+    );
+    assert_eq!(
+        ConfigFormat::from_path(Path::new("a.YAML")),
+        ConfigFormat::Yaml
+    );
+    assert_eq!(
+        ConfigFormat::from_path(Path::new("a.toml")),
+        ConfigFormat::Toml
+    );
+    assert_eq!(
+        ConfigFormat::from_path(Path::new("a.json")),
+        ConfigFormat::Json
+    );
+    assert_eq!(ConfigFormat::from_path(Path::new("a")), ConfigFormat::Yaml);
+    assert_eq!(
+        ConfigFormat::from_path(Path::new("/etc/config/njupt_wifi")),
+        ConfigFormat::Uci
+    );
+}
+
+#[test]
+fn write_replaces_file() {
+    let dir = std::env::temp_dir().join(format!("njupt_wifi_write_{}", std::process::id()));
+    let path = dir.join("njupt_wifi.yml");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        &path,
+        "a longer file which is replaced rather than truncated\n",
+    )
+    .unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    }
+
+    let config: LoginConfig = serde_yaml::from_str(BASIC).unwrap();
+    write_config_file(&path, &config).unwrap();
+    let written = read_config_file(&path).unwrap();
+    assert_eq!(
+        serde_yaml::to_string(&written).unwrap(),
+        serde_yaml::to_string(&config).unwrap()
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    // No temporary file is left behind.
+    let entries: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|x| x.unwrap().file_name())
+        .collect();
+    assert_eq!(entries, ["njupt_wifi.yml"]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
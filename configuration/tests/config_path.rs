@@ -0,0 +1,73 @@
+#![cfg(unix)]
+use njupt_wifi_login_configuration::config_path::{
+    config_candidates, discover_config_path, find_config_in_dir, ConfigLocation, CONFIG_PATH_ENV,
+};
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "njupt_wifi_config_path_{}_{}",
+        name,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn find_in_dir_tries_every_extension() {
+    let dir = temp_dir("find");
+    assert_eq!(find_config_in_dir(&dir), dir.join("njupt_wifi.yml"));
+    std::fs::write(dir.join("njupt_wifi.json"), "{}").unwrap();
+    assert_eq!(find_config_in_dir(&dir), dir.join("njupt_wifi.json"));
+    std::fs::write(dir.join("njupt_wifi.toml"), "").unwrap();
+    assert_eq!(find_config_in_dir(&dir), dir.join("njupt_wifi.toml"));
+    std::fs::write(dir.join("njupt_wifi.yaml"), "").unwrap();
+    assert_eq!(find_config_in_dir(&dir), dir.join("njupt_wifi.yaml"));
+    std::fs::write(dir.join("njupt_wifi.yml"), "").unwrap();
+    assert_eq!(find_config_in_dir(&dir), dir.join("njupt_wifi.yml"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// A single test, since the environment is shared by the test threads.
+#[test]
+fn discovery() {
+    let config_home = temp_dir("xdg");
+    std::env::remove_var(CONFIG_PATH_ENV);
+    std::env::set_var("XDG_CONFIG_HOME", &config_home);
+
+    let candidates = config_candidates().unwrap();
+    let user_dir = config_home.join("njupt_wifi");
+    let user_candidates: Vec<_> = candidates
+        .iter()
+        .filter(|x| x.location == ConfigLocation::UserConfigDir)
+        .map(|x| x.path.clone())
+        .collect();
+    assert_eq!(
+        user_candidates,
+        ["yml", "yaml", "toml", "json"].map(|x| user_dir.join(format!("njupt_wifi.{}", x)))
+    );
+    let locations: Vec<_> = candidates.iter().map(|x| x.location).collect();
+    assert_eq!(locations.len(), 12);
+    assert!(locations.is_sorted_by_key(|x| match x {
+        ConfigLocation::UserConfigDir => 0,
+        ConfigLocation::SystemConfigDir => 1,
+        _ => 2,
+    }));
+
+    // A TOML file in the user config directory is found as well.
+    std::fs::create_dir_all(&user_dir).unwrap();
+    std::fs::write(user_dir.join("njupt_wifi.toml"), "").unwrap();
+    let found = discover_config_path().unwrap();
+    assert_eq!(found.path, user_dir.join("njupt_wifi.toml"));
+    assert_eq!(found.location, ConfigLocation::UserConfigDir);
+
+    std::env::set_var(CONFIG_PATH_ENV, "/nonexistent/njupt_wifi.json");
+    let found = discover_config_path().unwrap();
+    assert_eq!(found.path, PathBuf::from("/nonexistent/njupt_wifi.json"));
+    assert_eq!(found.location, ConfigLocation::Environment);
+
+    std::env::remove_var(CONFIG_PATH_ENV);
+    std::env::remove_var("XDG_CONFIG_HOME");
+    let _ = std::fs::remove_dir_all(&config_home);
+}
@@ -0,0 +1,266 @@
+#![cfg(target_os = "linux")]
+//! Runs against a private D-Bus session bus with a stand-in Secret Service,
+//! so that nothing is written to the desktop keyring of the user running the tests.
+use njupt_wifi_login_configuration::{
+    linux_keyring::{KeyringBackend, KeyringError},
+    password::{Password, PasswordError, PasswordScope},
+};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+};
+use zbus::object_server::{ObjectServer, SignalContext};
+use zbus::zvariant::{OwnedObjectPath, Value};
+
+const COLLECTION_PATH: &str = "/org/freedesktop/secrets/collection/login";
+const PROMPT_PATH: &str = "/org/freedesktop/secrets/prompt/unlock";
+
+type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+#[derive(Default)]
+struct State {
+    unlocked: bool,
+    prompted: bool,
+    items: Vec<(HashMap<String, String>, Vec<u8>)>,
+}
+
+type SharedState = Arc<Mutex<State>>;
+
+fn path(s: impl Into<String>) -> OwnedObjectPath {
+    OwnedObjectPath::try_from(s.into()).unwrap()
+}
+
+struct Service(SharedState);
+
+#[zbus::interface(name = "org.freedesktop.Secret.Service")]
+impl Service {
+    fn open_session(
+        &self,
+        algorithm: &str,
+        _input: Value<'_>,
+    ) -> (Value<'static>, OwnedObjectPath) {
+        assert_eq!(algorithm, "plain");
+        (Value::from(""), path("/org/freedesktop/secrets/session/1"))
+    }
+
+    fn read_alias(&self, name: &str) -> OwnedObjectPath {
+        path(if name == "default" {
+            COLLECTION_PATH
+        } else {
+            "/"
+        })
+    }
+
+    fn search_items(
+        &self,
+        attributes: HashMap<String, String>,
+    ) -> (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) {
+        let state = self.0.lock().unwrap();
+        let found = state
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, (item_attributes, _))| {
+                attributes
+                    .iter()
+                    .all(|(key, value)| item_attributes.get(key) == Some(value))
+            })
+            .map(|(index, _)| path(format!("{}/{}", COLLECTION_PATH, index)))
+            .collect();
+        if state.unlocked {
+            (found, Vec::new())
+        } else {
+            (Vec::new(), found)
+        }
+    }
+
+    fn unlock(&self, objects: Vec<OwnedObjectPath>) -> (Vec<OwnedObjectPath>, OwnedObjectPath) {
+        if self.0.lock().unwrap().unlocked {
+            (objects, path("/"))
+        } else {
+            (Vec::new(), path(PROMPT_PATH))
+        }
+    }
+}
+
+struct Prompt(SharedState);
+
+#[zbus::interface(name = "org.freedesktop.Secret.Prompt")]
+impl Prompt {
+    async fn prompt(
+        &self,
+        _window_id: &str,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        {
+            let mut state = self.0.lock().unwrap();
+            state.unlocked = true;
+            state.prompted = true;
+        }
+        Self::completed(&ctxt, false, Value::from(Vec::<OwnedObjectPath>::new())).await?;
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn completed(
+        ctxt: &SignalContext<'_>,
+        dismissed: bool,
+        result: Value<'_>,
+    ) -> zbus::Result<()>;
+}
+
+struct Collection(SharedState);
+
+#[zbus::interface(name = "org.freedesktop.Secret.Collection")]
+impl Collection {
+    async fn create_item(
+        &self,
+        properties: HashMap<String, Value<'_>>,
+        secret: Secret,
+        _replace: bool,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> zbus::fdo::Result<(OwnedObjectPath, OwnedObjectPath)> {
+        let attributes: HashMap<String, String> = properties
+            .get("org.freedesktop.Secret.Item.Attributes")
+            .expect("attributes are set")
+            .try_clone()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let index = {
+            let mut state = self.0.lock().unwrap();
+            assert!(state.unlocked, "the collection is unlocked before writing");
+            state.items.push((attributes, secret.2));
+            state.items.len() - 1
+        };
+        let item = path(format!("{}/{}", COLLECTION_PATH, index));
+        server.at(&item, Item(self.0.clone(), index)).await?;
+        Ok((item, path("/")))
+    }
+}
+
+struct Item(SharedState, usize);
+
+#[zbus::interface(name = "org.freedesktop.Secret.Item")]
+impl Item {
+    fn get_secret(&self, session: OwnedObjectPath) -> Secret {
+        let value = self.0.lock().unwrap().items[self.1].1.clone();
+        (session, Vec::new(), value, "text/plain".to_string())
+    }
+}
+
+struct SessionBus(Child);
+
+impl Drop for SessionBus {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Start a private session bus, or `None` if `dbus-daemon` is not installed.
+fn start_session_bus() -> Option<(SessionBus, String)> {
+    let mut child = Command::new("dbus-daemon")
+        .args(["--session", "--nofork", "--print-address"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut address = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut address)
+        .unwrap();
+    Some((SessionBus(child), address.trim().to_string()))
+}
+
+fn store_and_load_with_secret_service(address: &str) {
+    let state = SharedState::default();
+    let _service = zbus::blocking::connection::Builder::address(address)
+        .unwrap()
+        .name("org.freedesktop.secrets")
+        .unwrap()
+        .serve_at("/org/freedesktop/secrets", Service(state.clone()))
+        .unwrap()
+        .serve_at(COLLECTION_PATH, Collection(state.clone()))
+        .unwrap()
+        .serve_at(PROMPT_PATH, Prompt(state.clone()))
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let password = Password::try_new(
+        "in secret service".to_string().into(),
+        PasswordScope::CurrentUser,
+        "B22999999",
+    )
+    .unwrap();
+    let Password::Keyring { keyring } = &password else {
+        panic!("expected a keyring reference, found {:?}", password);
+    };
+    assert_eq!(keyring.backend(), KeyringBackend::SecretService);
+    {
+        let state = state.lock().unwrap();
+        assert!(state.prompted);
+        assert_eq!(state.items.len(), 1);
+        assert_eq!(state.items[0].1, b"in secret service");
+        assert_eq!(state.items[0].0["application"], "njupt_wifi_login");
+    }
+
+    // Only the reference is written to the config.
+    let yaml = serde_yaml::to_string(&password).unwrap();
+    assert!(yaml.starts_with("keyring: secret-service:"), "{}", yaml);
+    assert!(!yaml.contains("in secret service"));
+    let password: Password = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(password.get().unwrap().expose_secret(), "in secret service");
+
+    let missing: Password = serde_yaml::from_str("keyring: secret-service:missing").unwrap();
+    assert!(matches!(
+        missing.get(),
+        Err(PasswordError::KeyringError(KeyringError::NotFound(_)))
+    ));
+}
+
+fn store_and_load_with_keyutils() {
+    let password = Password::try_new(
+        "in kernel keyring".to_string().into(),
+        PasswordScope::CurrentUser,
+        "B22999999",
+    )
+    .unwrap();
+    let Password::Keyring { keyring } = &password else {
+        panic!("expected a keyring reference, found {:?}", password);
+    };
+    assert_eq!(keyring.backend(), KeyringBackend::KeyUtils);
+    let yaml = serde_yaml::to_string(&password).unwrap();
+    assert!(yaml.starts_with("keyring: keyutils:"), "{}", yaml);
+    let password: Password = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(password.get().unwrap().expose_secret(), "in kernel keyring");
+}
+
+// Both backends are chosen by the session bus address, so they share one test
+// to not race on the environment variable.
+#[test]
+fn current_user_scope() {
+    match start_session_bus() {
+        Some((_bus, address)) => {
+            std::env::set_var("DBUS_SESSION_BUS_ADDRESS", &address);
+            store_and_load_with_secret_service(&address);
+        }
+        None => eprintln!("dbus-daemon is not found, skip the secret service backend"),
+    }
+
+    // Without a session bus, the kernel keyring is used.
+    std::env::set_var(
+        "DBUS_SESSION_BUS_ADDRESS",
+        "unix:path=/nonexistent/njupt_wifi_test_bus",
+    );
+    store_and_load_with_keyutils();
+}
+
+#[test]
+fn invalid_reference() {
+    assert!(serde_yaml::from_str::<Password>("keyring: gnome:0123").is_err());
+    assert!(serde_yaml::from_str::<Password>("keyring: keyutils:").is_err());
+}
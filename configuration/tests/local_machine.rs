@@ -0,0 +1,95 @@
+#![cfg(not(target_os = "windows"))]
+use chacha20poly1305::{aead::Aead, aead::Payload, ChaCha20Poly1305, KeyInit};
+use hkdf::Hkdf;
+use njupt_wifi_login_configuration::{
+    credential::{Credential, IspType},
+    login_config::LoginConfig,
+    password::{Password, PasswordError, PasswordScope},
+};
+use sha2::Sha256;
+use std::error::Error;
+
+fn config_with(password: &str) -> String {
+    format!(
+        "userid: B22999999\nisp: EDU\npassword:\n  data_protection: {}\n",
+        password
+    )
+}
+
+fn decryption_error(yaml: &str) -> String {
+    let config: LoginConfig = serde_yaml::from_str(yaml).unwrap();
+    match config.credential.password().get() {
+        Err(e @ PasswordError::LocalMachineCryptographyError(_)) => e.source().unwrap().to_string(),
+        other => panic!("expected a decryption error, found {:?}", other),
+    }
+}
+
+#[test]
+fn new_passwords_use_v2() {
+    let password = Password::try_new(
+        "secret".to_string().into(),
+        PasswordScope::LocalMachine,
+        "B22999999",
+    )
+    .unwrap();
+    let credential = Credential::new("B22999999".to_string(), password, IspType::EDU);
+    let yaml = serde_yaml::to_string(&credential).unwrap();
+    assert!(yaml.contains("data_protection: v2$m$"), "{}", yaml);
+    assert!(!credential.password().is_outdated());
+
+    let config: LoginConfig = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(
+        config.credential.password().get().unwrap().expose_secret(),
+        "secret"
+    );
+
+    // The password is bound to the account, so it cannot be copied to another one.
+    let moved = yaml.replace("B22999999", "B22888888");
+    assert!(decryption_error(&moved).contains("another account"));
+}
+
+#[test]
+fn changed_machine_identity_is_reported() {
+    // Encrypted on another machine.
+    let salt = [7u8; 16];
+    let hkdf = Hkdf::<Sha256>::new(Some(&salt), b"another machine");
+    let (mut key, mut check) = ([0u8; 32], [0u8; 8]);
+    hkdf.expand(b"njupt_wifi_login local machine key", &mut key)
+        .unwrap();
+    hkdf.expand(b"njupt_wifi_login local machine check", &mut check)
+        .unwrap();
+    let nonce = [0u8; 12];
+    let secret = ChaCha20Poly1305::new(&key.into())
+        .encrypt(
+            &nonce.into(),
+            Payload {
+                msg: b"secret",
+                aad: b"njupt_wifi_login;purpose=password;userid=B22999999",
+            },
+        )
+        .unwrap();
+    let protected = format!(
+        "v2$m${}${}${}${}",
+        hex::encode(salt),
+        hex::encode(check),
+        hex::encode(nonce),
+        hex::encode(secret)
+    );
+    let error = decryption_error(&config_with(&protected));
+    assert!(error.contains("machine identity changed"), "{}", error);
+    assert!(error.contains("config rekey"), "{}", error);
+}
+
+#[test]
+fn v1_is_outdated() {
+    let config: LoginConfig =
+        serde_yaml::from_str(&config_with("v1$m$000102030405060708090a0b$0c0d0e0f")).unwrap();
+    assert!(config.credential.password().is_outdated());
+}
+
+#[test]
+fn invalid_nonce_is_rejected() {
+    for protected in ["v2$m$00$00$0001$00", "v1$m$0001$00"] {
+        assert!(serde_yaml::from_str::<LoginConfig>(&config_with(protected)).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use njupt_wifi_login_configuration::{
+    config_file::{read_config_file, upgrade_config_file},
+    migration::{config_version, migrate, MigrationError, CONFIG_VERSION},
+};
+use serde_json::{json, Value};
+
+fn v0() -> Value {
+    json!({
+        "isp": "CT",
+        "userid": "B22999999",
+        "password": "password123456",
+        "check_interval": 600,
+        "log_policy": { "file_count": 2 },
+    })
+}
+
+#[test]
+fn missing_version_is_v0() {
+    assert_eq!(config_version(&v0()).unwrap(), 0);
+}
+
+fn v2() -> Value {
+    json!({
+        "version": 2,
+        "isp": "CT",
+        "userid": "B22999999",
+        "password": "password123456",
+        "check_interval": 600,
+        "logging": { "file_count": 2 },
+    })
+}
+
+#[test]
+fn v0_to_v2() {
+    let mut value = v0();
+    assert_eq!(migrate(&mut value).unwrap(), Some(0));
+    assert_eq!(value, v2());
+}
+
+#[test]
+fn v1_to_v2_moves_log_policy() {
+    let mut value = v0();
+    value["version"] = 1.into();
+    assert_eq!(migrate(&mut value).unwrap(), Some(1));
+    assert_eq!(value, v2());
+
+    let mut value = json!({ "version": 1, "log_policy": null });
+    migrate(&mut value).unwrap();
+    assert_eq!(value, json!({ "version": 2 }));
+}
+
+#[test]
+fn v1_to_v2_merges_existing_logging() {
+    let mut value = json!({
+        "version": 1,
+        "log_policy": { "file_count": 2, "compress": true },
+        "logging": { "sink": "syslog", "compress": true },
+    });
+    migrate(&mut value).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "version": 2,
+            "logging": { "sink": "syslog", "compress": true, "file_count": 2 },
+        })
+    );
+
+    let mut value = json!({
+        "version": 1,
+        "log_policy": { "file_count": 2 },
+        "logging": { "file_count": 5 },
+    });
+    match migrate(&mut value) {
+        Err(err @ MigrationError::Conflict(_)) => assert_eq!(
+            err.to_string(),
+            "`log_policy` and `logging` both set `logging.file_count`, keep only one of them"
+        ),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let mut value = json!({ "version": 1, "log_policy": "daily", "logging": {} });
+    assert!(matches!(
+        migrate(&mut value),
+        Err(MigrationError::Conflict(field)) if field == "logging"
+    ));
+}
+
+#[test]
+fn current_version_is_unchanged() {
+    let mut value = v0();
+    value["version"] = CONFIG_VERSION.into();
+    let expected = value.clone();
+    assert_eq!(migrate(&mut value).unwrap(), None);
+    assert_eq!(value, expected);
+}
+
+#[test]
+fn newer_version_is_rejected() {
+    let mut value = v0();
+    value["version"] = (CONFIG_VERSION + 1).into();
+    assert!(matches!(
+        migrate(&mut value),
+        Err(MigrationError::TooNew(version)) if version == CONFIG_VERSION + 1
+    ));
+}
+
+#[test]
+fn invalid_version_is_rejected() {
+    let mut value = v0();
+    value["version"] = "1".into();
+    assert!(matches!(
+        migrate(&mut value),
+        Err(MigrationError::InvalidVersion(_))
+    ));
+}
+
+#[test]
+fn upgrade_file_with_backup() {
+    let dir = std::env::temp_dir().join(format!("njupt_wifi_migration_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("njupt_wifi.yml");
+    let original = "isp: CT\nuserid: B22999999\npassword: password123456\n";
+    std::fs::write(&path, original).unwrap();
+
+    let (from, backup_path) = upgrade_config_file(&path, true).unwrap().unwrap();
+    assert_eq!(from, 0);
+    let backup_path = backup_path.unwrap();
+    assert_eq!(backup_path, dir.join("njupt_wifi.yml.v0.bak"));
+    assert_eq!(std::fs::read_to_string(&backup_path).unwrap(), original);
+    assert_eq!(read_config_file(&path).unwrap().version, CONFIG_VERSION);
+    assert!(upgrade_config_file(&path, true).unwrap().is_none());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
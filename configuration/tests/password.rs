@@ -0,0 +1,98 @@
+use njupt_wifi_login_configuration::password::{
+    set_passphrase, Password, PasswordError, PasswordScope, PASSPHRASE_ENV,
+};
+use std::error::Error;
+
+fn parse(yaml: &str) -> Password {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+#[test]
+fn env() {
+    std::env::set_var("NJUPT_WIFI_TEST_PASSWORD_ENV", "from env");
+    let password = parse("env: NJUPT_WIFI_TEST_PASSWORD_ENV");
+    assert!(password.is_external());
+    assert_eq!(password.get().unwrap().expose_secret(), "from env");
+
+    let password = parse("env: NJUPT_WIFI_TEST_PASSWORD_UNSET");
+    assert!(matches!(password.get(), Err(PasswordError::EnvNotSet(_))));
+}
+
+#[test]
+fn file_with_env_in_path() {
+    let dir = std::env::temp_dir().join(format!("njupt_wifi_password_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("password"), "from file\nignored\n").unwrap();
+    std::env::set_var("NJUPT_WIFI_TEST_CREDENTIALS_DIRECTORY", &dir);
+
+    let password = parse("file: ${NJUPT_WIFI_TEST_CREDENTIALS_DIRECTORY}/password");
+    assert_eq!(password.get().unwrap().expose_secret(), "from file");
+    let password = parse("file: ${NJUPT_WIFI_TEST_CREDENTIALS_DIRECTORY}/missing");
+    assert!(matches!(password.get(), Err(PasswordError::ReadFile(..))));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn command() {
+    let password = parse("command: [sh, -c, 'printf \"from command\\nignored\"']");
+    assert_eq!(password.get().unwrap().expose_secret(), "from command");
+
+    let password = parse("command: [sh, -c, 'exit 3']");
+    assert!(matches!(
+        password.get(),
+        Err(PasswordError::CommandFailed(..))
+    ));
+    let password = parse("command: []");
+    assert!(matches!(password.get(), Err(PasswordError::EmptyCommand)));
+}
+
+#[test]
+fn basic_is_not_external() {
+    assert!(!parse("plain").is_external());
+}
+
+#[test]
+fn passphrase() {
+    set_passphrase("correct horse".to_string().into());
+    let password = Password::try_new(
+        "portable".to_string().into(),
+        PasswordScope::Passphrase,
+        "B22999999",
+    )
+    .unwrap();
+    let yaml = serde_yaml::to_string(&password).unwrap();
+    assert!(
+        yaml.starts_with("data_protection: v1$p$m=19456,t=2,p=1$"),
+        "{}",
+        yaml
+    );
+    let password = parse(&yaml);
+    assert_eq!(password.scope(), Some(PasswordScope::Passphrase));
+    assert_eq!(password.get().unwrap().expose_secret(), "portable");
+
+    set_passphrase("battery staple".to_string().into());
+    match password.get() {
+        Err(e @ PasswordError::PassphraseCryptographyError(_)) => {
+            assert_eq!(e.source().unwrap().to_string(), "wrong passphrase")
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    // The wrong passphrase is forgotten, so it is read from the environment again.
+    std::env::set_var(PASSPHRASE_ENV, "correct horse");
+    assert_eq!(password.get().unwrap().expose_secret(), "portable");
+
+    assert!(serde_yaml::from_str::<Password>("data_protection: v1$p$m=1,t=2$00$00$00").is_err());
+}
+
+#[test]
+fn plaintext_is_redacted() {
+    let password = parse("plain");
+    assert!(!format!("{:?}", password).contains("plain"));
+    assert_eq!(password.to_string(), "<redacted>");
+    assert_eq!(format!("{:?}", password.get().unwrap()), "<redacted>");
+    assert_eq!(password.get().unwrap().expose_secret(), "plain");
+    // Written back as is.
+    assert_eq!(serde_yaml::to_string(&password).unwrap(), "plain\n");
+}
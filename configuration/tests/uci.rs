@@ -0,0 +1,299 @@
+use njupt_wifi_login_configuration::{
+    login_config::LoginConfig,
+    uci::{login_config_from_uci, parse_uci, UciError},
+};
+
+/// As written by `uci commit` and edited by hand.
+const SAMPLE: &str = r#"
+# /etc/config/njupt_wifi
+
+config globals 'globals'
+	option enabled '1'
+
+config njupt_wifi 'main'
+	option isp 'CT'
+	option userid B22999999
+	option password "pass word\"with 'quotes'\\"
+	option check_interval '1200'   # seconds
+	option interface 'phy0-sta0'
+	option luci_hint 'ignored by the daemon'
+	option log_sink 'syslog'
+	option log_level 'debug'
+	option log_format 'json'
+	option log_file '/tmp/log/njupt_wifi.log'
+	option log_size_limit '1MiB'
+	option log_file_count '2'
+	option log_compress 'yes'
+	list log_module 'hyper=warn'
+	list log_module 'njupt_wifi_login=trace'
+	option metrics_listen '127.0.0.1:9292'
+	option hook_timeout '10'
+	list hook_on_connected 'logger'
+	list hook_on_connected 'connected to NJUPT'
+	list hook_on_off_hours '/usr/bin/notify'
+	option mqtt_host 'broker.lan'
+	option mqtt_port '8883'
+	option mqtt_username 'router'
+	option mqtt_password 'secret'
+	option mqtt_client_id 'openwrt'
+	option mqtt_topic_prefix 'home/njupt'
+	option mqtt_discovery_prefix 'homeassistant'
+	option dns_protocol 'tcp'
+	list dns_server '223.5.5.5'
+	list dns_server '[2400:3200::1]:5353'
+	list dns_server 'tls://223.5.5.5#dns.alidns.com'
+	option dns_timeout '3'
+	option dns_attempts '1'
+	option dns_ordering 'user_provided'
+	option dns_policy 'encrypted_probes'
+
+config njupt_wifi 'backup'
+	option isp 'EDU'
+	option userid 'B22000000'
+	option password 'another'
+"#;
+
+/// What `SAMPLE` is expected to be mapped to.
+const EXPECTED: &str = r#"
+isp: CT
+userid: B22999999
+password: "pass word\"with 'quotes'\\"
+check_interval: 1200
+interface: phy0-sta0
+logging:
+  sink: syslog
+  level: debug
+  modules:
+    hyper: warn
+    njupt_wifi_login: trace
+  format: json
+  file: /tmp/log/njupt_wifi.log
+  size_limit: 1MiB
+  file_count: 2
+  compress: true
+metrics:
+  listen: 127.0.0.1:9292
+hooks:
+  timeout: 10
+  on_connected: [logger, connected to NJUPT]
+  on_off_hours: [/usr/bin/notify]
+mqtt:
+  host: broker.lan
+  port: 8883
+  username: router
+  password: secret
+  client_id: openwrt
+  topic_prefix: home/njupt
+  discovery_prefix: homeassistant
+dns:
+  servers:
+    - address: 223.5.5.5
+      protocol: tcp
+    - address: "2400:3200::1"
+      port: 5353
+      protocol: tcp
+    - address: 223.5.5.5
+      protocol: tls
+      tls_name: dns.alidns.com
+  timeout: 3
+  attempts: 1
+  ordering: user_provided
+  policy: encrypted_probes
+"#;
+
+fn to_yaml(config: &LoginConfig) -> String {
+    serde_yaml::to_string(config).unwrap()
+}
+
+fn syntax_error_line(s: &str) -> usize {
+    match parse_uci(s).unwrap_err() {
+        UciError::Syntax { line, .. } => line,
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn sections() {
+    let sections = parse_uci(SAMPLE).unwrap();
+    let names: Vec<_> = sections
+        .iter()
+        .map(|x| (x.section_type.as_str(), x.name.as_deref()))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ("globals", Some("globals")),
+            ("njupt_wifi", Some("main")),
+            ("njupt_wifi", Some("backup")),
+        ]
+    );
+    assert_eq!(sections[0].options["enabled"], "1");
+    assert_eq!(sections[2].options["userid"], "B22000000");
+}
+
+#[test]
+fn quotes_escapes_and_comments() {
+    let sections = parse_uci(
+        "config njupt_wifi # trailing comment\n\
+         \toption a 'single \"double\" # not a comment'\n\
+         \toption b \"double 'single' \\\"escaped\\\" \\\\\"\n\
+         \toption c un\\ quoted\\'\n\
+         \toption d 'mi'x\"ed\"\n\
+         \toption e ''\n\
+         # option f 'commented out'\n\
+         \toption g value#comment\n",
+    )
+    .unwrap();
+    let options = &sections[0].options;
+    assert_eq!(sections[0].name, None);
+    assert_eq!(options["a"], "single \"double\" # not a comment");
+    assert_eq!(options["b"], "double 'single' \"escaped\" \\");
+    assert_eq!(options["c"], "un quoted'");
+    assert_eq!(options["d"], "mixed");
+    assert_eq!(options["e"], "");
+    assert!(!options.contains_key("f"));
+    assert_eq!(options["g"], "value#comment");
+}
+
+#[test]
+fn lists_keep_order() {
+    let sections = parse_uci(
+        "config njupt_wifi\n\
+         \tlist password_command 'pass'\n\
+         \tlist password_command 'show'\n\
+         \tlist password_command 'njupt wifi'\n",
+    )
+    .unwrap();
+    assert_eq!(
+        sections[0].lists["password_command"],
+        ["pass", "show", "njupt wifi"]
+    );
+
+    let config = login_config_from_uci(
+        "config njupt_wifi\n\
+         \toption isp EDU\n\
+         \toption userid B22999999\n\
+         \tlist password_command 'pass'\n\
+         \tlist password_command 'show njupt'\n",
+    )
+    .unwrap();
+    assert!(to_yaml(&config).contains("password:\n  command:\n  - pass\n  - show njupt\n"));
+}
+
+#[test]
+fn malformed_lines() {
+    // Unterminated quotes.
+    assert_eq!(
+        syntax_error_line("config njupt_wifi\n\toption isp 'CT\n"),
+        2
+    );
+    assert_eq!(
+        syntax_error_line("config njupt_wifi\n\n\toption isp \"CT\\\"\n"),
+        3
+    );
+    assert_eq!(syntax_error_line("config njupt_wifi\n\toption isp CT\\"), 2);
+    // Wrong number of words.
+    assert_eq!(syntax_error_line("config\n"), 1);
+    assert_eq!(syntax_error_line("config a b c\n"), 1);
+    assert_eq!(syntax_error_line("config a\n\toption isp\n"), 2);
+    assert_eq!(syntax_error_line("config a\n\tlist x 'y' 'z'\n"), 2);
+    // Options before any section, and unknown keywords.
+    assert_eq!(syntax_error_line("# header\n\toption isp CT\n"), 2);
+    assert_eq!(syntax_error_line("config a\n\n\n\tset isp=CT\n"), 4);
+
+    let err = parse_uci("config a\n\toption isp 'CT\n").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "syntax error at line 2: unterminated single quote"
+    );
+}
+
+#[test]
+fn login_config_mapping() {
+    let config = login_config_from_uci(SAMPLE).unwrap();
+    assert_eq!(config.credential.userid(), "B22999999");
+    let expected: LoginConfig = serde_yaml::from_str(EXPECTED).unwrap();
+    assert_eq!(to_yaml(&config), to_yaml(&expected));
+}
+
+#[test]
+fn unknown_options_are_ignored() {
+    let config = login_config_from_uci(
+        "package njupt_wifi\n\
+         config njupt_wifi\n\
+         \toption isp EDU\n\
+         \toption userid B22999999\n\
+         \toption password x\n\
+         \toption enabled 1\n\
+         \tlist extra 'a'\n",
+    )
+    .unwrap();
+    let expected: LoginConfig =
+        serde_yaml::from_str("isp: EDU\nuserid: B22999999\npassword: x\n").unwrap();
+    assert_eq!(to_yaml(&config), to_yaml(&expected));
+}
+
+#[test]
+fn password_sources() {
+    let config = login_config_from_uci(
+        "config njupt_wifi\n\
+         \toption isp EDU\n\
+         \toption userid B22999999\n\
+         \toption password_env 'NJUPT_WIFI_PASSWORD'\n",
+    )
+    .unwrap();
+    assert!(to_yaml(&config).contains("password:\n  env: NJUPT_WIFI_PASSWORD\n"));
+
+    let config = login_config_from_uci(
+        "config njupt_wifi\n\
+         \toption isp EDU\n\
+         \toption userid B22999999\n\
+         \toption password_file '/etc/njupt_wifi.password'\n",
+    )
+    .unwrap();
+    assert!(to_yaml(&config).contains("password:\n  file: /etc/njupt_wifi.password\n"));
+}
+
+#[test]
+fn invalid_values() {
+    let base = "config njupt_wifi\n\toption isp EDU\n\toption userid a\n\toption password x\n";
+    for (line, name, value) in [
+        ("\toption check_interval 'soon'\n", "check_interval", "soon"),
+        ("\toption log_compress 'maybe'\n", "log_compress", "maybe"),
+        ("\toption log_file_count '-1'\n", "log_file_count", "-1"),
+        ("\toption mqtt_port 'mqtt'\n", "mqtt_port", "mqtt"),
+        ("\toption hook_timeout '1m'\n", "hook_timeout", "1m"),
+        ("\toption dns_timeout '5s'\n", "dns_timeout", "5s"),
+        ("\tlist log_module 'hyper'\n", "log_module", "hyper"),
+        (
+            "\tlist dns_server 'dns.google'\n",
+            "dns_server",
+            "dns.google",
+        ),
+    ] {
+        match login_config_from_uci(&format!("{}{}", base, line)).unwrap_err() {
+            UciError::InvalidValue {
+                name: actual_name,
+                value: actual_value,
+            } => assert_eq!((actual_name.as_str(), actual_value.as_str()), (name, value)),
+            err => panic!("unexpected error for {}: {}", name, err),
+        }
+    }
+    // Left to serde, like a YAML config.
+    assert!(matches!(
+        login_config_from_uci(&format!("{}\toption dns_policy 'sometimes'\n", base)),
+        Err(UciError::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        login_config_from_uci("config njupt_wifi\n\toption isp EDU\n"),
+        Err(UciError::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn section_not_found() {
+    assert!(matches!(
+        login_config_from_uci("config other\n\toption isp EDU\n"),
+        Err(UciError::SectionNotFound(_))
+    ));
+}
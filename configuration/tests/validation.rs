@@ -0,0 +1,321 @@
+use njupt_wifi_login_configuration::{
+    config_file::ConfigFormat,
+    login_config::{DnsOrdering, DnsPolicy, DnsProtocol},
+    validation::validate_config_source,
+};
+
+#[test]
+fn valid_config() {
+    let config = validate_config_source(
+        ConfigFormat::Yaml,
+        "isp: CT\nuserid: B22999999\npassword: password123456\n",
+    )
+    .unwrap();
+    assert_eq!(config.credential.userid(), "B22999999");
+}
+
+#[test]
+fn unknown_isp_with_suggestion() {
+    let errors = validate_config_source(
+        ConfigFormat::Yaml,
+        "userid: B22999999\npassword: x\nisp: cmc\n",
+    )
+    .unwrap_err();
+    let error = &errors.0[0];
+    assert_eq!(error.field, "isp");
+    assert_eq!(error.location, Some((3, 1)));
+    assert_eq!(error.allowed, ["EDU", "CMCC", "CT"]);
+    assert_eq!(error.suggestion.as_deref(), Some("CMCC"));
+}
+
+#[test]
+fn nested_field_location() {
+    let errors = validate_config_source(
+        ConfigFormat::Toml,
+        "version = 2\nisp = \"EDU\"\nuserid = \"a\"\npassword = \"x\"\n\n[logging]\nfile_cont = 2\n",
+    )
+    .unwrap_err();
+    let error = &errors.0[0];
+    assert_eq!(error.field, "logging.file_cont");
+    assert_eq!(error.location, Some((7, 1)));
+    assert_eq!(error.suggestion.as_deref(), Some("file_count"));
+}
+
+#[test]
+fn semantic_errors_after_parsing() {
+    let errors = validate_config_source(
+        ConfigFormat::Json,
+        "{\n  \"isp\": \"EDU\",\n  \"userid\": \"\",\n  \"password\": \"x\"\n}\n",
+    )
+    .unwrap_err();
+    let error = &errors.0[0];
+    assert_eq!(error.field, "userid");
+    assert_eq!(error.location, Some((3, 3)));
+}
+
+#[test]
+fn logging_levels() {
+    let errors = validate_config_source(
+        ConfigFormat::Yaml,
+        "version: 2\nuserid: a\npassword: x\nisp: EDU\nlogging:\n  sink: journal\n  modules:\n    hyper: warning\n",
+    )
+    .unwrap_err();
+    assert_eq!(errors.0.len(), 2, "{}", errors);
+    assert_eq!(errors.0[0].field, "logging.sink");
+    assert_eq!(errors.0[0].suggestion.as_deref(), Some("journald"));
+    assert_eq!(errors.0[1].field, "logging.modules.hyper");
+    assert_eq!(errors.0[1].location, Some((8, 5)));
+}
+
+#[test]
+fn metrics_listen() {
+    let source = "version: 2\nuserid: a\npassword: x\nisp: EDU\nmetrics:\n  listen: localhost\n";
+    let errors = validate_config_source(ConfigFormat::Yaml, source).unwrap_err();
+    assert_eq!(errors.0.len(), 1, "{}", errors);
+    assert_eq!(errors.0[0].field, "metrics.listen");
+
+    let config = validate_config_source(
+        ConfigFormat::Yaml,
+        &source.replace("localhost", "\"[::1]:9464\""),
+    )
+    .unwrap();
+    assert_eq!(config.metrics.unwrap().listen.port(), 9464);
+}
+
+#[test]
+fn notification_rules() {
+    let errors = validate_config_source(
+        ConfigFormat::Yaml,
+        "version: 2\nuserid: a\npassword: x\nisp: EDU\nnotifications:\n  logged_in: false\n  off_hours:\n    min_interval: -1\n  unknown_portals: {}\n",
+    )
+    .unwrap_err();
+    let fields: Vec<&str> = errors.0.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(
+        fields,
+        [
+            "notifications.unknown_portals",
+            "notifications.logged_in",
+            "notifications.off_hours.min_interval",
+        ],
+        "{}",
+        errors
+    );
+    assert_eq!(errors.0[0].suggestion.as_deref(), Some("unknown_portal"));
+
+    let config = validate_config_source(
+        ConfigFormat::Yaml,
+        "version: 2\nuserid: a\npassword: x\nisp: EDU\nnotifications:\n  logged_in:\n    enabled: false\n",
+    )
+    .unwrap();
+    let notifications = config.notifications.unwrap();
+    assert!(!notifications.logged_in.enabled);
+    assert!(notifications.off_hours.enabled);
+    assert_eq!(notifications.off_hours.min_interval, 600);
+}
+
+#[test]
+fn hook_commands() {
+    let errors = validate_config_source(
+        ConfigFormat::Yaml,
+        "version: 2\nuserid: a\npassword: x\nisp: EDU\nhooks:\n  timeout: 10\n  on_connected: []\n  on_login_success: mbsync -a\n  on_off_hours: null\n  on_logout: [true]\n",
+    )
+    .unwrap_err();
+    let fields: Vec<&str> = errors.0.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(
+        fields,
+        [
+            "hooks.on_logout",
+            "hooks.on_connected",
+            "hooks.on_login_success",
+        ],
+        "{}",
+        errors
+    );
+
+    let config = validate_config_source(
+        ConfigFormat::Yaml,
+        "version: 2\nuserid: a\npassword: x\nisp: EDU\nhooks:\n  on_connected: [systemctl, restart, wg-quick@wg0]\n",
+    )
+    .unwrap();
+    let hooks = config.hooks.unwrap();
+    assert_eq!(hooks.timeout, 30);
+    assert_eq!(hooks.on_connected.unwrap()[0], "systemctl");
+    assert!(hooks.on_login_failure.is_none());
+}
+
+#[test]
+fn mqtt_broker() {
+    let errors = validate_config_source(
+        ConfigFormat::Yaml,
+        "version: 2\nuserid: a\npassword: x\nisp: EDU\nmqtt:\n  port: 70000\n  password: { command: [] }\n  topic_prefix: dorm/#\n  discovery_prefix: null\n",
+    )
+    .unwrap_err();
+    let fields: Vec<&str> = errors.0.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(
+        fields,
+        [
+            "mqtt.host",
+            "mqtt.port",
+            "mqtt.password.command",
+            "mqtt.topic_prefix",
+        ],
+        "{}",
+        errors
+    );
+
+    let config = validate_config_source(
+        ConfigFormat::Yaml,
+        "version: 2\nuserid: a\npassword: x\nisp: EDU\nmqtt:\n  host: localhost\n  username: njupt\n  password: { env: MQTT_PASSWORD }\n",
+    )
+    .unwrap();
+    let mqtt = config.mqtt.unwrap();
+    assert_eq!(mqtt.port, 1883);
+    assert_eq!(mqtt.topic_prefix, "njupt_wifi_login");
+    assert_eq!(mqtt.discovery_prefix.as_deref(), Some("homeassistant"));
+    assert!(mqtt.password.is_some());
+}
+
+#[test]
+fn dns_servers() {
+    let errors = validate_config_source(
+        ConfigFormat::Yaml,
+        "version: 2\nuserid: a\npassword: x\nisp: EDU\ndns:\n  servers:\n    - address: 223.5.5.5:53\n    - address: 114.114.114.114\n      protocol: quic\n  timeout: 0\n  ordering: fastest\n",
+    )
+    .unwrap_err();
+    let fields: Vec<&str> = errors.0.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(
+        fields,
+        [
+            "dns.servers.0.address",
+            "dns.servers.1.protocol",
+            "dns.timeout",
+            "dns.ordering",
+        ],
+        "{}",
+        errors
+    );
+
+    let config = validate_config_source(
+        ConfigFormat::Yaml,
+        "version: 2\nuserid: a\npassword: x\nisp: EDU\ndns:\n  servers:\n    - address: 223.5.5.5\n    - address: \"::1\"\n      port: 5353\n      protocol: tcp\n",
+    )
+    .unwrap();
+    let dns = config.dns.unwrap();
+    assert_eq!(dns.servers[0].socket_addr().to_string(), "223.5.5.5:53");
+    assert_eq!(dns.servers[1].socket_addr().to_string(), "[::1]:5353");
+    assert_eq!(dns.servers[1].protocol, DnsProtocol::Tcp);
+    assert_eq!(dns.timeout, 5);
+    assert_eq!(dns.ordering, DnsOrdering::QueryStatistics);
+}
+
+#[test]
+fn encrypted_dns_policy() {
+    let errors = validate_config_source(
+        ConfigFormat::Yaml,
+        "version: 2\nuserid: a\npassword: x\nisp: EDU\ndns:\n  servers:\n    - address: 223.5.5.5\n      protocol: tls\n  policy: encrypted_probes\n",
+    )
+    .unwrap_err();
+    let fields: Vec<&str> = errors.0.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(
+        fields,
+        ["dns.servers.0.tls_name", "dns.policy"],
+        "{}",
+        errors
+    );
+
+    let config = validate_config_source(
+        ConfigFormat::Yaml,
+        "version: 2\nuserid: a\npassword: x\nisp: EDU\ndns:\n  servers:\n    - address: 223.5.5.5\n      protocol: tls\n      tls_name: dns.alidns.com\n    - address: 1.1.1.1\n      protocol: https\n      tls_name: cloudflare-dns.com\n    - address: 114.114.114.114\n  policy: encrypted_probes\n",
+    )
+    .unwrap();
+    let dns = config.dns.unwrap();
+    assert_eq!(dns.servers[0].socket_addr().to_string(), "223.5.5.5:853");
+    assert_eq!(dns.servers[1].socket_addr().to_string(), "1.1.1.1:443");
+    assert!(dns.servers[1].protocol.is_encrypted());
+    assert!(!dns.servers[2].protocol.is_encrypted());
+    assert_eq!(dns.policy, DnsPolicy::EncryptedProbes);
+}
+
+/// The known fields and values are taken from the schema, which must cover every field.
+#[test]
+fn every_field_is_known() {
+    let config = validate_config_source(
+        ConfigFormat::Yaml,
+        r#"
+version: 2
+userid: B22999999
+password:
+  env: NJUPT_WIFI_PASSWORD
+isp: CMCC
+check_interval: 600
+interface: eth0
+logging:
+  sink: stderr
+  level: info
+  modules:
+    hyper: warn
+  format: json
+  file: njupt_wifi.log
+  size_limit: 3MiB
+  file_count: 2
+  compress: true
+dns:
+  servers:
+    - address: 223.5.5.5
+      port: 53
+      protocol: tcp
+      tls_name: null
+  timeout: 3
+  attempts: 1
+  ordering: user_provided
+  policy: all_servers
+metrics:
+  listen: 127.0.0.1:9464
+notifications:
+  logged_in:
+    enabled: true
+    min_interval: 60
+  off_hours:
+    enabled: false
+  credentials_rejected: {}
+  unknown_portal:
+    min_interval: 0
+hooks:
+  timeout: 10
+  on_connected: [logger, connected]
+  on_login_success: [logger, logged in]
+  on_login_failure: [logger, failed]
+  on_off_hours: [logger, off hours]
+  on_status_change: [logger, changed]
+mqtt:
+  host: broker.lan
+  port: 1883
+  username: router
+  password:
+    file: /etc/njupt_wifi/mqtt.password
+  client_id: router
+  topic_prefix: njupt
+  discovery_prefix: null
+"#,
+    );
+    assert!(config.is_ok(), "{}", config.unwrap_err());
+
+    let errors = validate_config_source(
+        ConfigFormat::Yaml,
+        "userid: a\npassword: x\nisp: EDU\nlogging:\n  sink: sterr\n",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors.0[0].allowed,
+        ["file", "stderr", "journald", "syslog"]
+    );
+    assert_eq!(errors.0[0].suggestion.as_deref(), Some("stderr"));
+
+    // The broker password is plain text or from an external source, as in the schema.
+    let errors = validate_config_source(
+        ConfigFormat::Yaml,
+        "userid: a\npassword: x\nisp: EDU\nmqtt:\n  host: broker.lan\n  password:\n    keyring: keyutils:mqtt\n",
+    )
+    .unwrap_err();
+    assert_eq!(errors.0[0].field, "mqtt.password.keyring");
+}
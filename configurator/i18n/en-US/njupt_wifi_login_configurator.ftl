@@ -4,18 +4,19 @@ isp-ct = CT
 password-scope-anywhere = Anywhere
 password-scope-local-machine = LocalMachine
 password-scope-current-user = CurrentUser
+password-scope-passphrase = Passphrase
 isp = ISP
 user-id = UserID
 password = Password
 password-scope = PasswordScope
 enable = Enable
 save = Save
-tips-not-effective-until-rebooting = Note: The configuration won't take effect until rebooting.
+tips-applied-automatically = Note: A running instance will pick up the saved configuration automatically.
 error-failed-to-encrypt-password = Error: Failed to encrypt password: {$details}
 error-failed-to-write-config = Error: Failed to write config: {$details}
 error-failed-to-set-auto-launch = Error: Failed to modify AutoLaunch setting: {$details}
 info-applied-successfully = Info: Applied successfully.
-tips-password-scope = Tips: PasswordScope setting affects the key of the encryption algorithm used to store the password. When "Anywhere" is selected, the account password can be read on any device; when "LocalMachine" is selected, the information associated with the device ID is used as the key for password storage, making it possible to decrypt the account password stored in the configuration only on the current device; when "CurrentUser" is selected, the information associated with the current local user ID will be used as the key for password storage, allowing only the current user to decrypt the account password stored in the configuration.
+tips-password-scope = Tips: PasswordScope setting affects the key of the encryption algorithm used to store the password. When "Anywhere" is selected, the account password can be read on any device; when "LocalMachine" is selected, the information associated with the device ID is used as the key for password storage, making it possible to decrypt the account password stored in the configuration only on the current device; when "CurrentUser" is selected, the information associated with the current local user ID will be used as the key for password storage, allowing only the current user to decrypt the account password stored in the configuration; when "Passphrase" is selected, a key derived from the passphrase in the NJUPT_WIFI_PASSPHRASE environment variable (or printed by the NJUPT_WIFI_PASSPHRASE_COMMAND command) is used, so that the configuration can be shared between your own devices.
 error-failed-to-disable-other-launchers = Error: Failed to disable {$launcher}: {$details}
 launcher = Launcher
 desktop-launcher-name = Desktop launcher
@@ -30,8 +31,13 @@ error-not-running = Error: Not running.
 status-running = Status: Running
 status-stopped = Status: Stopped
 error-selected-password-scope-not-supported-by-launcher = Error: Selected password scope is not supported by {$launcher}.
+error-no-launcher-available = Error: No launcher is available on this system.
 windows-service-launcher-name = Windows Service
+systemd-system-launcher-name = systemd (System)
+systemd-user-launcher-name = systemd (User)
 check-interval = Check Interval (s)
 tips-check-interval = Tips: In addition to monitoring network changes, the program will also periodically perform proactive connectivity checks to ensure network availability. This option specifies the time interval for regular checks. (0 means disabled.)
 interface = Interface
 tips-interface = Tips: Bind the request to a specific interface (e.g., eth0). Leave empty to not specify.
+tui-key-hints = ↑/↓ Move  ←/→ Change  F1 Tips  Ctrl+S Save  F5 Start  F6 Stop  Esc Quit
+info-config-path = Config file: {$path}
@@ -4,18 +4,19 @@ isp-ct = 中国电信
 password-scope-anywhere = 任何位置
 password-scope-local-machine = 本地机器
 password-scope-current-user = 当前用户
+password-scope-passphrase = 口令
 isp = 运营商
 user-id = 用户ID
 password = 密码
 password-scope = 密码范围
 enable = 启用
 save = 保存
-tips-not-effective-until-rebooting = 注意：在重启前配置不会生效。
+tips-applied-automatically = 注意：正在运行的实例会自动应用保存的配置。
 error-failed-to-encrypt-password = 错误：加密存储密码失败：{$details}
 error-failed-to-write-config = 错误：写出配置失败：{$details}
 error-failed-to-set-auto-launch = 错误：修改自动启动设置失败：{$details}
 info-applied-successfully = 信息：应用成功。
-tips-password-scope = 提示：密码范围设置影响了用于存储密码的加密算法的密钥。选择“任何位置”时，账户密码可以在任何设备上被读取；选择“本地机器”时，将利用与设备ID相关联的信息作为密钥加密存储，使得仅在当前设备上能够解密存储在配置文件中的账户密码；选择“当前用户”时，则将采用与当前本地用户ID相关联的信息作为密钥加密存储，仅允许当前用户解密配置文件中存储的账户密码。
+tips-password-scope = 提示：密码范围设置影响了用于存储密码的加密算法的密钥。选择“任何位置”时，账户密码可以在任何设备上被读取；选择“本地机器”时，将利用与设备ID相关联的信息作为密钥加密存储，使得仅在当前设备上能够解密存储在配置文件中的账户密码；选择“当前用户”时，则将采用与当前本地用户ID相关联的信息作为密钥加密存储，仅允许当前用户解密配置文件中存储的账户密码；选择“口令”时，将使用由环境变量 NJUPT_WIFI_PASSPHRASE 中的口令（或命令 NJUPT_WIFI_PASSPHRASE_COMMAND 输出的口令）派生的密钥加密存储，使得配置文件可以在自己的多台设备间共享。
 error-failed-to-disable-other-launchers = 错误: 无法禁用 {$launcher}：{$details}
 launcher = 启动器
 desktop-launcher-name = 桌面启动器
@@ -30,8 +31,13 @@ error-not-running = 错误：程序不在运行状态。
 status-running = 状态：正在运行
 status-stopped = 状态：已停止
 error-selected-password-scope-not-supported-by-launcher = 错误：选择的密码范围不被 {$launcher} 支持。
+error-no-launcher-available = 错误：此系统上没有可用的启动器。
 windows-service-launcher-name = Windows 服务
+systemd-system-launcher-name = systemd（系统）
+systemd-user-launcher-name = systemd（用户）
 check-interval = 检查间隔（秒）
 tips-check-interval = 提示：除了监听网络变动以外，程序还会定期主动进行连接性检测以确保网络可用。这一选项指定了定期检测的时间间隔。（0 表示禁用）
 interface = 接口
 tips-interface = 提示：将请求包绑定到特定的接口上（如 eth0）上。留空表示不指定。
+tui-key-hints = ↑/↓ 移动  ←/→ 切换  F1 提示  Ctrl+S 保存  F5 启动  F6 停止  Esc 退出
+info-config-path = 配置文件：{$path}
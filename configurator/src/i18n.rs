@@ -9,7 +9,7 @@ use rust_embed::RustEmbed;
 struct Localizations;
 
 use once_cell::sync::Lazy;
-pub(crate) static LANGUAGE_LOADER: Lazy<FluentLanguageLoader> = Lazy::new(|| {
+pub static LANGUAGE_LOADER: Lazy<FluentLanguageLoader> = Lazy::new(|| {
     let language_loader: FluentLanguageLoader = fluent_language_loader!();
     let requested_languages = DesktopLanguageRequester::requested_languages();
     let _result = i18n_embed::select(&language_loader, &Localizations, &requested_languages);
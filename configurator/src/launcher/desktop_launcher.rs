@@ -50,6 +50,14 @@ impl DesktopLauncher {
                     if process.cmd().iter().any(|x| x == "service") {
                         continue;
                     }
+                    // ignore process managed by systemd
+                    if process
+                        .environ()
+                        .iter()
+                        .any(|x| x.starts_with("NOTIFY_SOCKET="))
+                    {
+                        continue;
+                    }
                     return Some(f(process));
                 }
             }
@@ -109,7 +117,8 @@ impl super::Launcher for DesktopLauncher {
         Ok(self.for_process(|_| ()).is_some())
     }
 
-    fn is_password_scope_supported(&self, _scope: &PasswordScope) -> bool {
-        true
+    fn is_password_scope_supported(&self, scope: &PasswordScope) -> bool {
+        // Started at login without a terminal, nothing can supply a passphrase.
+        !matches!(scope, PasswordScope::Passphrase)
     }
 }
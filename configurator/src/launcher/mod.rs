@@ -1,10 +1,13 @@
 mod desktop_launcher;
 mod launcher_trait;
+mod systemd_launcher;
 mod windows_service_launcher;
 use std::{env, ffi::OsString, io, path::PathBuf};
 
 pub use desktop_launcher::DesktopLauncher;
 pub use launcher_trait::Launcher;
+#[cfg(target_os = "linux")]
+pub use systemd_launcher::SystemdLauncher;
 #[cfg(all(feature = "windows-service-mode", target_os = "windows"))]
 pub use windows_service_launcher::WindowsServiceLauncher;
 
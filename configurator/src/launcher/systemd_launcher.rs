@@ -0,0 +1,133 @@
+#![cfg(target_os = "linux")]
+use njupt_wifi_login_configuration::password::PasswordScope;
+use std::{
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use crate::fl;
+
+use super::get_core_path;
+
+pub struct SystemdLauncher {
+    core_path: PathBuf,
+    unit_name: String,
+    user: bool,
+}
+
+impl SystemdLauncher {
+    pub fn new(user: bool) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let core_path = get_core_path()?;
+        // Make sure systemd is available.
+        Command::new("systemctl")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        Ok(Self {
+            core_path,
+            unit_name: "njupt_wifi_login".to_string(),
+            user,
+        })
+    }
+
+    fn systemctl(&self) -> Command {
+        let mut command = Command::new("systemctl");
+        if self.user {
+            command.arg("--user");
+        }
+        command
+    }
+
+    /// Installing a system unit writes to `/etc/systemd/system`, which requires root.
+    /// systemctl itself asks polkit for authorization, so it is not elevated here.
+    fn core_systemd_command(&self) -> Command {
+        let mut command = if self.user || unsafe { libc::geteuid() } == 0 {
+            Command::new(self.core_path.as_path())
+        } else {
+            let mut command = Command::new("pkexec");
+            command.arg(self.core_path.as_path());
+            command
+        };
+        command.args(["systemd", "--name", self.unit_name.as_str()]);
+        if self.user {
+            command.arg("--user");
+        }
+        command
+    }
+
+    fn unit(&self) -> String {
+        format!("{}.service", self.unit_name)
+    }
+}
+
+impl super::Launcher for SystemdLauncher {
+    fn name(&self) -> String {
+        if self.user {
+            fl!("systemd-user-launcher-name")
+        } else {
+            fl!("systemd-system-launcher-name")
+        }
+    }
+
+    fn enable(&self) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+        let status = self.core_systemd_command().arg("install").status()?;
+        if !status.success() {
+            return Err(format!("Failed to install the unit: {}", status).into());
+        }
+        Ok(())
+    }
+
+    fn disable(&self) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+        let status = self.core_systemd_command().arg("uninstall").status()?;
+        if !status.success() {
+            return Err(format!("Failed to uninstall the unit: {}", status).into());
+        }
+        Ok(())
+    }
+
+    fn start(&self) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+        let status = self.systemctl().args(["start", &self.unit()]).status()?;
+        if !status.success() {
+            return Err(format!("Failed to start the unit: {}", status).into());
+        }
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+        let status = self.systemctl().args(["stop", &self.unit()]).status()?;
+        if !status.success() {
+            return Err(format!("Failed to stop the unit: {}", status).into());
+        }
+        Ok(())
+    }
+
+    fn is_enabled(&self) -> Result<bool, Box<dyn std::error::Error + Sync + Send>> {
+        let status = self
+            .systemctl()
+            .args(["is-enabled", "--quiet", &self.unit()])
+            .stderr(Stdio::null())
+            .status()?;
+        Ok(status.success())
+    }
+
+    fn is_running(&self) -> Result<bool, Box<dyn std::error::Error + Sync + Send>> {
+        let status = self
+            .systemctl()
+            .args(["is-active", "--quiet", &self.unit()])
+            .stderr(Stdio::null())
+            .status()?;
+        Ok(status.success())
+    }
+
+    fn is_password_scope_supported(&self, scope: &PasswordScope) -> bool {
+        // The system unit runs as root, which can read the machine ID,
+        // but not any secret bound to the current user.
+        // Neither unit is given a passphrase.
+        match scope {
+            PasswordScope::Passphrase => false,
+            PasswordScope::CurrentUser => self.user,
+            _ => true,
+        }
+    }
+}
@@ -99,6 +99,10 @@ impl super::Launcher for WindowsServiceLauncher {
     }
 
     fn is_password_scope_supported(&self, scope: &PasswordScope) -> bool {
-        !matches!(scope, PasswordScope::CurrentUser)
+        // The service cannot read a secret bound to the current user, and is not given a passphrase.
+        !matches!(
+            scope,
+            PasswordScope::CurrentUser | PasswordScope::Passphrase
+        )
     }
 }
@@ -0,0 +1,3 @@
+pub mod i18n;
+pub mod launcher;
+pub mod settings;
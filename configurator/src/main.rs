@@ -1,43 +1,24 @@
 #![windows_subsystem = "windows"]
 mod custom_font_scope;
-mod i18n;
-mod launcher;
 use custom_font_scope::CustomFontScope;
-use display_error_chain::ErrorChainExt;
 use druid::widget::{
     Align, Button, Checkbox, CrossAxisAlignment, Flex, FlexParams, Label, LineBreaking, RadioGroup,
     TextBox,
 };
 use druid::{AppLauncher, Data, Lens, Widget, WidgetExt, WindowDesc};
 use include_bytes_zstd::include_bytes_zstd;
-use launcher::Launcher;
 use njupt_wifi_login_configuration::{
-    credential::{Credential, IspType},
-    login_config::LoginConfig,
-    password::{Password, PasswordScope},
+    credential::IspType,
+    password::{disable_passphrase_prompt, PasswordScope},
+};
+use njupt_wifi_login_configurator::{
+    fl,
+    settings::{
+        initial_message, load_settings, save_settings, start_daemon, stop_daemon,
+        ConfiguratorSettings, LAUNCHERS,
+    },
 };
-use once_cell::sync::Lazy;
-use std::env;
-use std::error::Error;
-use std::path::PathBuf;
 const WINDOW_TITLE: &str = "NJUPT WiFi Login Configurator";
-static LAUNCHERS: Lazy<Vec<Box<dyn Launcher + Send + Sync>>> = Lazy::new(|| {
-    let mut launchers: Vec<Box<dyn Launcher + Send + Sync>> = Vec::new();
-    if let Ok(launcher) = launcher::DesktopLauncher::new() {
-        launchers.push(Box::new(launcher));
-    }
-    #[cfg(all(feature = "windows-service-mode", target_os = "windows"))]
-    if let Ok(launcher) = launcher::WindowsServiceLauncher::new() {
-        launchers.push(Box::new(launcher));
-    }
-    launchers
-});
-static CONFIG_PATH: Lazy<PathBuf> = Lazy::new(|| {
-    let mut path = env::current_exe().unwrap();
-    path.pop();
-    path.push("njupt_wifi.yml");
-    path
-});
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy, Data, Default)]
 pub enum IspTypeState {
@@ -53,6 +34,7 @@ pub enum PasswordScopeState {
     #[default]
     LocalMachine,
     CurrentUser,
+    Passphrase,
 }
 
 #[derive(Clone, Data, Lens, Default)]
@@ -69,19 +51,60 @@ struct ConfiguratorState {
     interface: String,
 }
 
-fn read_my_config() -> Result<LoginConfig, Box<dyn Error>> {
-    let f = std::fs::File::open(CONFIG_PATH.as_path())?;
-    let config: LoginConfig = serde_yaml::from_reader(f)?;
-    Ok(config)
+impl From<ConfiguratorSettings> for ConfiguratorState {
+    fn from(settings: ConfiguratorSettings) -> Self {
+        Self {
+            userid: settings.userid,
+            password: settings.password,
+            isp: match settings.isp {
+                IspType::EDU => IspTypeState::EDU,
+                IspType::CMCC => IspTypeState::CMCC,
+                IspType::CT => IspTypeState::CT,
+            },
+            password_scope: match settings.password_scope {
+                PasswordScope::Anywhere => PasswordScopeState::Anywhere,
+                PasswordScope::LocalMachine => PasswordScopeState::LocalMachine,
+                PasswordScope::CurrentUser => PasswordScopeState::CurrentUser,
+                PasswordScope::Passphrase => PasswordScopeState::Passphrase,
+            },
+            launcher_index: settings.launcher_index,
+            enabled: settings.enabled,
+            message: String::new(),
+            running: settings.running,
+            check_interval: settings.check_interval,
+            interface: settings.interface,
+        }
+    }
 }
 
-fn write_my_config(d: &LoginConfig) -> Result<(), Box<dyn Error>> {
-    let f = std::fs::File::create(CONFIG_PATH.as_path())?;
-    serde_yaml::to_writer(f, d)?;
-    Ok(())
+impl From<&ConfiguratorState> for ConfiguratorSettings {
+    fn from(data: &ConfiguratorState) -> Self {
+        Self {
+            userid: data.userid.clone(),
+            password: data.password.clone(),
+            isp: match data.isp {
+                IspTypeState::EDU => IspType::EDU,
+                IspTypeState::CMCC => IspType::CMCC,
+                IspTypeState::CT => IspType::CT,
+            },
+            password_scope: match data.password_scope {
+                PasswordScopeState::Anywhere => PasswordScope::Anywhere,
+                PasswordScopeState::LocalMachine => PasswordScope::LocalMachine,
+                PasswordScopeState::CurrentUser => PasswordScope::CurrentUser,
+                PasswordScopeState::Passphrase => PasswordScope::Passphrase,
+            },
+            check_interval: data.check_interval.clone(),
+            interface: data.interface.clone(),
+            launcher_index: data.launcher_index,
+            enabled: data.enabled,
+            running: data.running,
+        }
+    }
 }
 
 fn main() {
+    // There is no terminal to ask for the passphrase, it can only come from the environment.
+    disable_passphrase_prompt();
     // describe the main window
     let main_window = WindowDesc::new(CustomFontScope::new(build_root_widget(), |text| {
         use druid::piet::Text;
@@ -94,41 +117,8 @@ fn main() {
     .window_size((550.0, 460.0));
 
     // create the initial app state
-    let mut initial_state = ConfiguratorState::default();
-
-    if let Ok(config) = read_my_config() {
-        let isp_state = match config.credential.isp() {
-            IspType::EDU => IspTypeState::EDU,
-            IspType::CMCC => IspTypeState::CMCC,
-            IspType::CT => IspTypeState::CT,
-        };
-        initial_state.isp = isp_state;
-        initial_state.userid = config.credential.userid().to_string();
-        initial_state.password = config.credential.password().to_string();
-        #[allow(irrefutable_let_patterns)]
-        if let Password::Basic(_) = config.credential.password() {
-            initial_state.password_scope = PasswordScopeState::Anywhere;
-        }
-        initial_state.check_interval = config.check_interval.to_string();
-        initial_state.interface = config.interface.unwrap_or_default();
-    }
-    initial_state.enabled = false;
-    initial_state.running = false;
-    initial_state.launcher_index = 0;
-    for (index, launcher) in LAUNCHERS.iter().enumerate() {
-        if launcher.is_enabled().unwrap_or(false) {
-            initial_state.enabled = true;
-            initial_state.running = launcher.is_running().unwrap_or(false);
-            initial_state.launcher_index = index;
-            if initial_state.password_scope == PasswordScopeState::LocalMachine
-                && !launcher.is_password_scope_supported(&PasswordScope::LocalMachine)
-            {
-                initial_state.password_scope = PasswordScopeState::Anywhere;
-            }
-            break;
-        }
-    }
-    initial_state.message = fl!("tips-not-effective-until-rebooting");
+    let mut initial_state = ConfiguratorState::from(load_settings());
+    initial_state.message = initial_message();
 
     // start the application
     AppLauncher::with_window(main_window)
@@ -192,6 +182,10 @@ fn build_root_widget() -> impl Widget<ConfiguratorState> {
             fl!("password-scope-current-user"),
             PasswordScopeState::CurrentUser,
         ),
+        (
+            fl!("password-scope-passphrase"),
+            PasswordScopeState::Passphrase,
+        ),
     ])
     .lens(ConfiguratorState::password_scope)
     .expand_width();
@@ -295,124 +289,23 @@ fn build_root_widget() -> impl Widget<ConfiguratorState> {
 
     let start_button = Button::new(fl!("start"))
         .on_click(|_ctx, data: &mut ConfiguratorState, _env| {
-            let current_launcher = &LAUNCHERS[data.launcher_index];
-            if current_launcher.is_running().unwrap_or(data.running) {
-                data.running = true;
-                data.message = fl!("error-already-running");
-                return;
-            }
-            if let Err(e) = current_launcher.start() {
-                data.message = fl!(
-                    "error-failed-to-start",
-                    details = e.as_ref().chain().to_string()
-                );
-                return;
-            }
-            data.running = true;
-            data.message = fl!("info-started-successfully");
+            let mut settings = ConfiguratorSettings::from(&*data);
+            data.message = start_daemon(&mut settings).unwrap_or_else(|e| e);
+            data.running = settings.running;
         })
         .fix_size(72.0, 36.0);
 
     let stop_button = Button::new(fl!("stop"))
         .on_click(|_ctx, data: &mut ConfiguratorState, _env| {
-            let current_launcher = &LAUNCHERS[data.launcher_index];
-            if !current_launcher.is_running().unwrap_or(data.running) {
-                data.running = false;
-                data.message = fl!("error-not-running");
-                return;
-            }
-            if let Err(e) = current_launcher.stop() {
-                data.message = fl!(
-                    "error-failed-to-stop",
-                    details = e.as_ref().chain().to_string()
-                );
-                return;
-            }
-            data.running = false;
-            data.message = fl!("info-stopped-successfully");
+            let mut settings = ConfiguratorSettings::from(&*data);
+            data.message = stop_daemon(&mut settings).unwrap_or_else(|e| e);
+            data.running = settings.running;
         })
         .fix_size(72.0, 36.0);
 
     let save_button = Button::new(fl!("save"))
         .on_click(|_ctx, data: &mut ConfiguratorState, _env| {
-            let current_launcher = &LAUNCHERS[data.launcher_index];
-            let isp = match data.isp {
-                IspTypeState::EDU => IspType::EDU,
-                IspTypeState::CMCC => IspType::CMCC,
-                IspTypeState::CT => IspType::CT,
-            };
-            let password_scope = match data.password_scope {
-                PasswordScopeState::Anywhere => PasswordScope::Anywhere,
-                PasswordScopeState::LocalMachine => PasswordScope::LocalMachine,
-                PasswordScopeState::CurrentUser => PasswordScope::CurrentUser,
-            };
-            if !current_launcher.is_password_scope_supported(&password_scope) {
-                data.message = fl!(
-                    "error-selected-password-scope-not-supported-by-launcher",
-                    launcher = current_launcher.name()
-                );
-                return;
-            }
-            let password = Password::try_new(data.password.clone(), password_scope);
-            if let Err(e) = password {
-                data.message = fl!(
-                    "error-failed-to-encrypt-password",
-                    details = e.chain().to_string()
-                );
-                return;
-            }
-            let password = password.unwrap();
-            let old_config = read_my_config().ok();
-            let config = LoginConfig {
-                credential: Credential::new(data.userid.clone(), password, isp),
-                check_interval: data.check_interval.parse().unwrap_or(20 * 60),
-                interface: if data.interface.is_empty() {
-                    None
-                } else {
-                    Some(data.interface.clone())
-                },
-                log_policy: old_config
-                    .as_ref()
-                    .map(|c| c.log_policy.clone())
-                    .unwrap_or_default(),
-            };
-            if let Err(e) = write_my_config(&config) {
-                data.message = fl!(
-                    "error-failed-to-write-config",
-                    details = e.as_ref().chain().to_string()
-                );
-                return;
-            }
-            for (index, launcher) in LAUNCHERS.iter().enumerate() {
-                if index == data.launcher_index {
-                    continue;
-                }
-                if launcher.is_enabled().unwrap_or(false) {
-                    if let Err(e) = launcher.disable() {
-                        data.message = fl!(
-                            "error-failed-to-disable-other-launchers",
-                            launcher = launcher.name(),
-                            details = e.as_ref().chain().to_string()
-                        );
-                        return;
-                    }
-                }
-            }
-            let auto_launch_result = if data.enabled {
-                current_launcher.enable()
-            } else if current_launcher.is_enabled().unwrap_or(true) {
-                current_launcher.disable()
-            } else {
-                Ok(())
-            };
-            if let Err(e) = auto_launch_result {
-                data.message = fl!(
-                    "error-failed-to-set-auto-launch",
-                    details = e.as_ref().chain().to_string()
-                );
-                return;
-            }
-            data.message = fl!("info-applied-successfully");
+            data.message = save_settings(&ConfiguratorSettings::from(&*data)).unwrap_or_else(|e| e);
         })
         .fix_size(72.0, 36.0);
 
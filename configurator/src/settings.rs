@@ -0,0 +1,252 @@
+use crate::fl;
+use crate::launcher::{self, Launcher};
+use display_error_chain::ErrorChainExt;
+use njupt_wifi_login_configuration::{
+    config_file::{read_config_file, write_config_file, ConfigFileError},
+    config_path::{discover_config_path, ConfigPath},
+    credential::{Credential, IspType},
+    login_config::LoginConfig,
+    migration::CONFIG_VERSION,
+    password::{Password, PasswordScope},
+};
+use once_cell::sync::Lazy;
+
+pub static LAUNCHERS: Lazy<Vec<Box<dyn Launcher + Send + Sync>>> = Lazy::new(|| {
+    let mut launchers: Vec<Box<dyn Launcher + Send + Sync>> = Vec::new();
+    if let Ok(launcher) = launcher::DesktopLauncher::new() {
+        launchers.push(Box::new(launcher));
+    }
+    #[cfg(all(feature = "windows-service-mode", target_os = "windows"))]
+    if let Ok(launcher) = launcher::WindowsServiceLauncher::new() {
+        launchers.push(Box::new(launcher));
+    }
+    #[cfg(target_os = "linux")]
+    for user in [false, true] {
+        if let Ok(launcher) = launcher::SystemdLauncher::new(user) {
+            launchers.push(Box::new(launcher));
+        }
+    }
+    launchers
+});
+pub static CONFIG_PATH: Lazy<ConfigPath> = Lazy::new(|| discover_config_path().unwrap());
+
+pub fn read_my_config() -> Result<LoginConfig, ConfigFileError> {
+    read_config_file(CONFIG_PATH.path.as_path())
+}
+
+pub fn write_my_config(d: &LoginConfig) -> Result<(), ConfigFileError> {
+    write_config_file(CONFIG_PATH.path.as_path(), d)
+}
+
+/// Settings shown by the configurator front-ends.
+#[derive(Debug, Clone)]
+pub struct ConfiguratorSettings {
+    pub userid: String,
+    pub password: String,
+    pub isp: IspType,
+    pub password_scope: PasswordScope,
+    pub check_interval: String,
+    pub interface: String,
+    pub launcher_index: usize,
+    pub enabled: bool,
+    pub running: bool,
+}
+
+impl Default for ConfiguratorSettings {
+    fn default() -> Self {
+        Self {
+            userid: String::new(),
+            password: String::new(),
+            isp: IspType::EDU,
+            password_scope: PasswordScope::LocalMachine,
+            check_interval: String::new(),
+            interface: String::new(),
+            launcher_index: 0,
+            enabled: false,
+            running: false,
+        }
+    }
+}
+
+/// Message shown when a front-end starts.
+pub fn initial_message() -> String {
+    format!(
+        "{}\n{}",
+        fl!("tips-applied-automatically"),
+        fl!("info-config-path", path = CONFIG_PATH.to_string())
+    )
+}
+
+/// Load settings from the config file and the state of launchers.
+pub fn load_settings() -> ConfiguratorSettings {
+    let mut settings = ConfiguratorSettings::default();
+    if let Ok(config) = read_my_config() {
+        settings.isp = config.credential.isp();
+        settings.userid = config.credential.userid().to_string();
+        settings.password = config
+            .credential
+            .password()
+            .get()
+            .map(|p| p.expose_secret().to_string())
+            .unwrap_or_default();
+        if let Some(scope) = config.credential.password().scope() {
+            settings.password_scope = scope;
+        }
+        settings.check_interval = config.check_interval.to_string();
+        settings.interface = config.interface.unwrap_or_default();
+    }
+    for (index, launcher) in LAUNCHERS.iter().enumerate() {
+        if launcher.is_enabled().unwrap_or(false) {
+            settings.enabled = true;
+            settings.running = launcher.is_running().unwrap_or(false);
+            settings.launcher_index = index;
+            if settings.password_scope == PasswordScope::LocalMachine
+                && !launcher.is_password_scope_supported(&PasswordScope::LocalMachine)
+            {
+                settings.password_scope = PasswordScope::Anywhere;
+            }
+            break;
+        }
+    }
+    settings
+}
+
+/// The selected launcher, or the message to show if none is available,
+/// e.g. without a desktop session and without `systemctl`.
+fn current_launcher(
+    settings: &ConfiguratorSettings,
+) -> Result<&'static (dyn Launcher + Send + Sync), String> {
+    LAUNCHERS
+        .get(settings.launcher_index)
+        .map(|launcher| launcher.as_ref())
+        .ok_or_else(|| fl!("error-no-launcher-available"))
+}
+
+/// Write the config file and apply the launcher settings.
+/// Returns the message to show on success or on failure.
+pub fn save_settings(settings: &ConfiguratorSettings) -> Result<String, String> {
+    let current_launcher = current_launcher(settings)?;
+    if !current_launcher.is_password_scope_supported(&settings.password_scope) {
+        return Err(fl!(
+            "error-selected-password-scope-not-supported-by-launcher",
+            launcher = current_launcher.name()
+        ));
+    }
+    // Sections which cannot be edited here are kept as they are.
+    let old_config = read_my_config().ok();
+    // Keep an external password source or the stored one, unless the password is changed.
+    // An encrypted password is also bound to the account.
+    let keep_password = old_config.as_ref().is_some_and(|config| {
+        let c = &config.credential;
+        let p = c.password();
+        (p.is_external()
+            || (p.scope() == Some(settings.password_scope)
+                && !p.is_outdated()
+                && c.userid() == settings.userid))
+            && p.get()
+                .is_ok_and(|p| p.expose_secret() == settings.password)
+    });
+    let new_credential =
+        |password| Credential::new(settings.userid.clone(), password, settings.isp);
+    let mut config = match old_config {
+        Some(config) if keep_password => LoginConfig {
+            credential: new_credential(config.credential.into_password()),
+            ..config
+        },
+        old_config => {
+            let password = Password::try_new(
+                settings.password.clone().into(),
+                settings.password_scope,
+                &settings.userid,
+            )
+            .map_err(|e| {
+                fl!(
+                    "error-failed-to-encrypt-password",
+                    details = e.chain().to_string()
+                )
+            })?;
+            match old_config {
+                Some(config) => LoginConfig {
+                    credential: new_credential(password),
+                    ..config
+                },
+                None => LoginConfig::new(new_credential(password)),
+            }
+        }
+    };
+    config.version = CONFIG_VERSION;
+    config.check_interval = settings.check_interval.parse().unwrap_or(20 * 60);
+    config.interface = if settings.interface.is_empty() {
+        None
+    } else {
+        Some(settings.interface.clone())
+    };
+    write_my_config(&config).map_err(|e| {
+        fl!(
+            "error-failed-to-write-config",
+            details = e.chain().to_string()
+        )
+    })?;
+    for (index, launcher) in LAUNCHERS.iter().enumerate() {
+        if index == settings.launcher_index {
+            continue;
+        }
+        if launcher.is_enabled().unwrap_or(false) {
+            launcher.disable().map_err(|e| {
+                fl!(
+                    "error-failed-to-disable-other-launchers",
+                    launcher = launcher.name(),
+                    details = e.as_ref().chain().to_string()
+                )
+            })?;
+        }
+    }
+    let auto_launch_result = if settings.enabled {
+        current_launcher.enable()
+    } else if current_launcher.is_enabled().unwrap_or(true) {
+        current_launcher.disable()
+    } else {
+        Ok(())
+    };
+    auto_launch_result.map_err(|e| {
+        fl!(
+            "error-failed-to-set-auto-launch",
+            details = e.as_ref().chain().to_string()
+        )
+    })?;
+    Ok(fl!("info-applied-successfully"))
+}
+
+/// Start the daemon with the selected launcher, updating `running` accordingly.
+pub fn start_daemon(settings: &mut ConfiguratorSettings) -> Result<String, String> {
+    let current_launcher = current_launcher(settings)?;
+    if current_launcher.is_running().unwrap_or(settings.running) {
+        settings.running = true;
+        return Err(fl!("error-already-running"));
+    }
+    current_launcher.start().map_err(|e| {
+        fl!(
+            "error-failed-to-start",
+            details = e.as_ref().chain().to_string()
+        )
+    })?;
+    settings.running = true;
+    Ok(fl!("info-started-successfully"))
+}
+
+/// Stop the daemon with the selected launcher, updating `running` accordingly.
+pub fn stop_daemon(settings: &mut ConfiguratorSettings) -> Result<String, String> {
+    let current_launcher = current_launcher(settings)?;
+    if !current_launcher.is_running().unwrap_or(settings.running) {
+        settings.running = false;
+        return Err(fl!("error-not-running"));
+    }
+    current_launcher.stop().map_err(|e| {
+        fl!(
+            "error-failed-to-stop",
+            details = e.as_ref().chain().to_string()
+        )
+    })?;
+    settings.running = false;
+    Ok(fl!("info-stopped-successfully"))
+}
@@ -0,0 +1,315 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use njupt_wifi_login_configuration::{
+    credential::IspType,
+    password::{disable_passphrase_prompt, PasswordScope},
+};
+use njupt_wifi_login_configurator::{
+    fl,
+    settings::{
+        initial_message, load_settings, save_settings, start_daemon, stop_daemon,
+        ConfiguratorSettings, LAUNCHERS,
+    },
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+const WINDOW_TITLE: &str = "NJUPT WiFi Login Configurator";
+const STATUS_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+const LABEL_WIDTH: usize = 24;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Field {
+    Isp,
+    UserId,
+    Password,
+    PasswordScope,
+    CheckInterval,
+    Interface,
+    Launcher,
+    Enabled,
+}
+
+impl Field {
+    const ALL: [Field; 8] = [
+        Field::Isp,
+        Field::UserId,
+        Field::Password,
+        Field::PasswordScope,
+        Field::CheckInterval,
+        Field::Interface,
+        Field::Launcher,
+        Field::Enabled,
+    ];
+
+    fn label(self) -> String {
+        match self {
+            Field::Isp => fl!("isp"),
+            Field::UserId => fl!("user-id"),
+            Field::Password => fl!("password"),
+            Field::PasswordScope => fl!("password-scope"),
+            Field::CheckInterval => fl!("check-interval"),
+            Field::Interface => fl!("interface"),
+            Field::Launcher => fl!("launcher"),
+            Field::Enabled => fl!("enable"),
+        }
+    }
+
+    fn tips(self) -> Option<String> {
+        match self {
+            Field::PasswordScope => Some(fl!("tips-password-scope")),
+            Field::CheckInterval => Some(fl!("tips-check-interval")),
+            Field::Interface => Some(fl!("tips-interface")),
+            _ => None,
+        }
+    }
+}
+
+struct App {
+    settings: ConfiguratorSettings,
+    focus: usize,
+    message: String,
+    last_status_refresh: Instant,
+}
+
+impl App {
+    fn new() -> Self {
+        Self {
+            settings: load_settings(),
+            focus: 0,
+            message: initial_message(),
+            last_status_refresh: Instant::now(),
+        }
+    }
+
+    fn focused(&self) -> Field {
+        Field::ALL[self.focus]
+    }
+
+    fn refresh_status(&mut self) {
+        if let Some(launcher) = LAUNCHERS.get(self.settings.launcher_index) {
+            self.settings.running = launcher.is_running().unwrap_or(self.settings.running);
+        }
+        self.last_status_refresh = Instant::now();
+    }
+
+    fn text_field(&mut self, field: Field) -> Option<&mut String> {
+        match field {
+            Field::UserId => Some(&mut self.settings.userid),
+            Field::Password => Some(&mut self.settings.password),
+            Field::CheckInterval => Some(&mut self.settings.check_interval),
+            Field::Interface => Some(&mut self.settings.interface),
+            _ => None,
+        }
+    }
+
+    /// Cycle the value of a choice field, `forward` selects the next one.
+    fn cycle(&mut self, forward: bool) {
+        let field = self.focused();
+        let settings = &mut self.settings;
+        match field {
+            Field::Isp => {
+                let choices = [IspType::EDU, IspType::CMCC, IspType::CT];
+                let index = match settings.isp {
+                    IspType::EDU => 0,
+                    IspType::CMCC => 1,
+                    IspType::CT => 2,
+                };
+                settings.isp = choices[step(index, choices.len(), forward)];
+            }
+            Field::PasswordScope => {
+                let choices = [
+                    PasswordScope::Anywhere,
+                    PasswordScope::LocalMachine,
+                    PasswordScope::CurrentUser,
+                    PasswordScope::Passphrase,
+                ];
+                let index = choices
+                    .iter()
+                    .position(|scope| *scope == settings.password_scope)
+                    .unwrap_or(0);
+                settings.password_scope = choices[step(index, choices.len(), forward)];
+            }
+            Field::Launcher if !LAUNCHERS.is_empty() => {
+                settings.launcher_index = step(settings.launcher_index, LAUNCHERS.len(), forward);
+                self.refresh_status();
+            }
+            Field::Enabled => settings.enabled = !settings.enabled,
+            _ => {}
+        }
+    }
+
+    /// Handle a key press, returns `false` to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Char('c') if ctrl => return false,
+            KeyCode::Char('s') if ctrl => {
+                self.message = save_settings(&self.settings).unwrap_or_else(|e| e);
+            }
+            KeyCode::F(1) => {
+                if let Some(tips) = self.focused().tips() {
+                    self.message = tips;
+                }
+            }
+            KeyCode::F(5) => {
+                self.message = start_daemon(&mut self.settings).unwrap_or_else(|e| e);
+            }
+            KeyCode::F(6) => {
+                self.message = stop_daemon(&mut self.settings).unwrap_or_else(|e| e);
+            }
+            KeyCode::Down | KeyCode::Tab | KeyCode::Enter => {
+                self.focus = step(self.focus, Field::ALL.len(), true);
+            }
+            KeyCode::Up | KeyCode::BackTab => {
+                self.focus = step(self.focus, Field::ALL.len(), false);
+            }
+            KeyCode::Left => self.cycle(false),
+            KeyCode::Right => self.cycle(true),
+            KeyCode::Char(' ') if self.focused() == Field::Enabled => self.cycle(true),
+            KeyCode::Char(c) if !ctrl => {
+                if let Some(text) = self.text_field(self.focused()) {
+                    text.push(c);
+                }
+            }
+            KeyCode::Backspace => {
+                if let Some(text) = self.text_field(self.focused()) {
+                    text.pop();
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn value(&self, field: Field) -> String {
+        let settings = &self.settings;
+        match field {
+            Field::Isp => match settings.isp {
+                IspType::EDU => fl!("isp-edu"),
+                IspType::CMCC => fl!("isp-cmcc"),
+                IspType::CT => fl!("isp-ct"),
+            },
+            Field::UserId => settings.userid.clone(),
+            Field::Password => "*".repeat(settings.password.chars().count()),
+            Field::PasswordScope => match settings.password_scope {
+                PasswordScope::Anywhere => fl!("password-scope-anywhere"),
+                PasswordScope::LocalMachine => fl!("password-scope-local-machine"),
+                PasswordScope::CurrentUser => fl!("password-scope-current-user"),
+                PasswordScope::Passphrase => fl!("password-scope-passphrase"),
+            },
+            Field::CheckInterval => settings.check_interval.clone(),
+            Field::Interface => settings.interface.clone(),
+            Field::Launcher => LAUNCHERS
+                .get(settings.launcher_index)
+                .map(|launcher| launcher.name())
+                .unwrap_or_default(),
+            Field::Enabled => if settings.enabled { "[x]" } else { "[ ]" }.to_string(),
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [form_area, message_area, status_area] = Layout::vertical([
+            Constraint::Length(Field::ALL.len() as u16 + 2),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let lines: Vec<Line> = Field::ALL
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let focused = index == self.focus;
+                let is_choice = !matches!(
+                    field,
+                    Field::UserId | Field::Password | Field::CheckInterval | Field::Interface
+                );
+                let value = if focused && is_choice && *field != Field::Enabled {
+                    format!("< {} >", self.value(*field))
+                } else if focused && !is_choice {
+                    format!("{}_", self.value(*field))
+                } else {
+                    self.value(*field)
+                };
+                let style = if focused {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                Line::from(vec![
+                    Span::raw(format!("{:<width$}", field.label(), width = LABEL_WIDTH)),
+                    Span::styled(value, style),
+                ])
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(WINDOW_TITLE)),
+            form_area,
+        );
+
+        frame.render_widget(
+            Paragraph::new(self.message.as_str())
+                .wrap(Wrap { trim: true })
+                .block(Block::default().borders(Borders::ALL)),
+            message_area,
+        );
+
+        let (status, color) = if self.settings.running {
+            (fl!("status-running"), Color::Green)
+        } else {
+            (fl!("status-stopped"), Color::Red)
+        };
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled(status, Style::default().fg(color)),
+                Span::raw("  "),
+                Span::raw(fl!("tui-key-hints")),
+            ])),
+            status_area,
+        );
+    }
+}
+
+fn step(index: usize, len: usize, forward: bool) -> usize {
+    if forward {
+        (index + 1) % len
+    } else {
+        (index + len - 1) % len
+    }
+}
+
+fn run(terminal: &mut DefaultTerminal) -> io::Result<()> {
+    let mut app = App::new();
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        let timeout = STATUS_REFRESH_INTERVAL.saturating_sub(app.last_status_refresh.elapsed());
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !app.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+        if app.last_status_refresh.elapsed() >= STATUS_REFRESH_INTERVAL {
+            app.refresh_status();
+        }
+    }
+}
+
+fn main() -> io::Result<()> {
+    // The terminal is in raw mode, the passphrase can only come from the environment.
+    disable_passphrase_prompt();
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal);
+    ratatui::restore();
+    result
+}
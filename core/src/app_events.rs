@@ -1,10 +1,31 @@
+use crate::login::{NetworkStatus, WifiLoginError};
+use chrono::{DateTime, Utc};
 use tokio::task::AbortHandle;
 
-pub trait AppEvents {
+pub trait AppEvents: Send + Sync {
     fn on_started(&self);
     fn on_stopping(&self);
     fn on_stopped(&self);
-    fn register_abort_handle(&mut self, handle: AbortHandle);
+    fn on_network_status(&self, _status: &NetworkStatus) {
+        // Do nothing by default.
+    }
+    fn on_login_result(&self, _result: Result<(), &WifiLoginError>) {
+        // Do nothing by default.
+    }
+    fn on_logout(&self, _result: Result<(), &WifiLoginError>) {
+        // Do nothing by default.
+    }
+    /// Logins are refused in off hours, `until` is when they are expected to end if known.
+    fn on_off_hours(&self, _until: Option<DateTime<Utc>>) {
+        // Do nothing by default.
+    }
+    /// The event loop is running, sent between actions and every [`HEARTBEAT_INTERVAL`] while idle.
+    ///
+    /// [`HEARTBEAT_INTERVAL`]: crate::metrics::HEARTBEAT_INTERVAL
+    fn on_heartbeat(&self) {
+        // Do nothing by default.
+    }
+    fn register_abort_handle(&self, handle: AbortHandle);
 }
 
 pub struct DefaultAppEvents;
@@ -18,7 +39,46 @@ impl AppEvents for DefaultAppEvents {
     fn on_stopped(&self) {
         // Do nothing.
     }
-    fn register_abort_handle(&mut self, _handle: AbortHandle) {
+    fn register_abort_handle(&self, _handle: AbortHandle) {
         // Do nothing.
     }
 }
+
+/// Deliver every event to each of the listeners in order,
+/// e.g. the service manager and the desktop notifications.
+pub struct AppEventsGroup(pub Vec<Box<dyn AppEvents>>);
+impl AppEvents for AppEventsGroup {
+    fn on_started(&self) {
+        self.0.iter().for_each(|events| events.on_started());
+    }
+    fn on_stopping(&self) {
+        self.0.iter().for_each(|events| events.on_stopping());
+    }
+    fn on_stopped(&self) {
+        self.0.iter().for_each(|events| events.on_stopped());
+    }
+    fn on_network_status(&self, status: &NetworkStatus) {
+        self.0
+            .iter()
+            .for_each(|events| events.on_network_status(status));
+    }
+    fn on_login_result(&self, result: Result<(), &WifiLoginError>) {
+        self.0
+            .iter()
+            .for_each(|events| events.on_login_result(result));
+    }
+    fn on_logout(&self, result: Result<(), &WifiLoginError>) {
+        self.0.iter().for_each(|events| events.on_logout(result));
+    }
+    fn on_off_hours(&self, until: Option<DateTime<Utc>>) {
+        self.0.iter().for_each(|events| events.on_off_hours(until));
+    }
+    fn on_heartbeat(&self) {
+        self.0.iter().for_each(|events| events.on_heartbeat());
+    }
+    fn register_abort_handle(&self, handle: AbortHandle) {
+        self.0
+            .iter()
+            .for_each(|events| events.register_abort_handle(handle.clone()));
+    }
+}
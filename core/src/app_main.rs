@@ -1,52 +1,130 @@
-use crate::app_events::AppEvents;
-use crate::login::{self, get_network_status, send_login_request, WifiLoginError};
+use crate::app_events::{AppEvents, AppEventsGroup};
+use crate::config_file::validate_config;
+use crate::dns::resolver::CustomTrustDnsResolver;
+use crate::history::{History, HistoryEntry, Trigger};
+use crate::hooks::{AppHookEvents, HooksContext};
+use crate::login::{
+    self, get_network_status, send_login_request, send_logout_request, WifiLoginError,
+};
+use crate::metrics::{Metrics, HEARTBEAT_INTERVAL};
+use crate::metrics_server::MetricsServerHandle;
+use crate::mqtt::MqttHandle;
+use crate::notification::AppNotificationEvents;
 use crate::off_hours_cache::OffHoursCache;
 use display_error_chain::ErrorChainExt;
 use log::*;
-use njupt_wifi_login_configuration::login_config::LoginConfig;
-use std::sync::Arc;
+use njupt_wifi_login_configuration::{config_file::read_config_file, login_config::LoginConfig};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Notify};
 use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
 
 #[derive(Debug)]
 pub enum ActionInfo {
-    CheckAndLogin(),
+    CheckAndLogin(Trigger),
+    /// Check and login, bypassing the debounce.
+    ForceCheckAndLogin(Trigger),
+    /// Logout, and skip logins until a forced check or the network is found usable.
+    Logout(Trigger),
+    ReloadConfig(),
+    /// Stop after the current action is finished.
+    Stop(),
 }
 
-pub struct AppMain {
+/// Everything derived from a config, swapped as a whole when the config is reloaded.
+struct AppContext {
     config: LoginConfig,
+    dns_resolver: Arc<CustomTrustDnsResolver>,
+}
+
+impl AppContext {
+    fn new(config: LoginConfig) -> Self {
+        let dns_resolver = login::new_dns_resolver(&config);
+        Self {
+            config,
+            dns_resolver,
+        }
+    }
+}
+
+type SharedAppContext = Arc<RwLock<Arc<AppContext>>>;
+
+pub struct AppMain {
+    context: SharedAppContext,
+    config_path: Option<PathBuf>,
     off_hours_cache: Arc<Mutex<OffHoursCache>>,
+    history: History,
+    metrics: Arc<Metrics>,
+    logged_out: AtomicBool,
+    /// Wakes the regular check, so that a new interval takes effect right away.
+    config_reloaded: Arc<Notify>,
 }
 impl AppMain {
-    pub fn new(config: LoginConfig) -> AppMain {
+    pub fn new(config: LoginConfig, config_path: Option<PathBuf>, history: History) -> AppMain {
+        let metrics = Arc::new(Metrics::new());
+        metrics.set_check_interval(Duration::from_secs(config.check_interval));
         AppMain {
-            config,
+            context: Arc::new(RwLock::new(Arc::new(AppContext::new(config)))),
+            config_path,
             off_hours_cache: Arc::new(Mutex::new(OffHoursCache::new())),
+            history,
+            metrics,
+            logged_out: AtomicBool::new(false),
+            config_reloaded: Arc::new(Notify::new()),
         }
     }
+    fn context(&self) -> Arc<AppContext> {
+        self.context.read().unwrap().clone()
+    }
     pub fn run(
         self,
-        mut events: impl AppEvents,
+        events: impl AppEvents + 'static,
     ) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+        let context = self.context.clone();
+        let notification_events = AppNotificationEvents::new(move || {
+            context.read().unwrap().config.notifications.clone()
+        });
+        let context = self.context.clone();
+        let hook_events =
+            AppHookEvents::new(move || HooksContext::from_config(&context.read().unwrap().config));
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(async {
+            let (tx, rx) = mpsc::unbounded_channel::<ActionInfo>();
+            let mqtt_handle = self.register_mqtt(tx.clone()).await?;
+            let mut events_group: Vec<Box<dyn AppEvents>> = vec![
+                Box::new(events),
+                Box::new(notification_events),
+                Box::new(hook_events),
+            ];
+            if let Some(mqtt_handle) = mqtt_handle.as_ref() {
+                events_group.push(Box::new(mqtt_handle.events()));
+            }
+            let events: Arc<dyn AppEvents> = Arc::new(AppEventsGroup(events_group));
             {
-                let (tx, rx) = mpsc::unbounded_channel::<ActionInfo>();
                 let regular_check_handle = self.register_regular_check(tx.clone()).await?;
+                let config_watcher_handle = self.register_config_watcher(tx.clone()).await;
+                let metrics_server_handle = self.register_metrics_server().await?;
+                #[cfg(unix)]
+                let unix_signal_listener_handle =
+                    self.register_unix_signal_listener(tx.clone()).await?;
                 #[cfg(target_os = "windows")]
                 let _win32_connectivity_hint_listener_handle =
                     self.register_win32_connectivity_hint_listener(tx).await?; // there is an initial notification after registration
                 #[cfg(not(target_os = "windows"))]
-                let _ = tx.send(ActionInfo::CheckAndLogin()); // initial check
+                let _ = tx.send(ActionInfo::CheckAndLogin(Trigger::Startup)); // initial check
                 #[cfg(target_os = "linux")]
                 let linux_network_listener_handle =
                     self.register_linux_network_listener(tx).await?;
 
                 events.on_started();
                 info!("Started");
-                let event_loop_handle = tokio::spawn(async move { self.event_loop(rx).await });
+                let event_loop_events = events.clone();
+                let event_loop_handle =
+                    tokio::spawn(async move { self.event_loop(rx, event_loop_events).await });
                 events.register_abort_handle(event_loop_handle.abort_handle());
                 if let Ok(Err(err)) = event_loop_handle.await {
                     error!("Event loop error: {}", err.as_ref().chain());
@@ -57,6 +135,27 @@ impl AppMain {
                 regular_check_handle.abort();
                 let _ = regular_check_handle.await;
 
+                if let Some(config_watcher_handle) = config_watcher_handle {
+                    config_watcher_handle.abort();
+                    config_watcher_handle.join().await;
+                }
+
+                if let Some(metrics_server_handle) = metrics_server_handle {
+                    metrics_server_handle.abort();
+                    metrics_server_handle.join().await;
+                }
+
+                if let Some(mqtt_handle) = mqtt_handle {
+                    mqtt_handle.abort();
+                    mqtt_handle.join().await;
+                }
+
+                #[cfg(unix)]
+                {
+                    unix_signal_listener_handle.abort();
+                    unix_signal_listener_handle.join().await;
+                }
+
                 #[cfg(target_os = "linux")]
                 {
                     linux_network_listener_handle.abort();
@@ -72,33 +171,133 @@ impl AppMain {
         &self,
         tx: UnboundedSender<ActionInfo>,
     ) -> Result<JoinHandle<()>, Box<dyn std::error::Error + Sync + Send>> {
-        let mut check_interval = Duration::from_secs(self.config.check_interval);
-        if check_interval.is_zero() {
-            info!("Regular check is disabled");
-            return Ok(tokio::spawn(async {}));
-        }
-        if check_interval < Duration::from_secs(15) {
-            warn!("Regular check interval is too short, fallback to 15 secs");
-            check_interval = Duration::from_secs(15);
-        }
+        let context = self.context.clone();
         let off_hours_cache = self.off_hours_cache.clone();
+        let config_reloaded = self.config_reloaded.clone();
         let join_handle = tokio::spawn(async move {
-            tokio::time::sleep(check_interval).await;
+            let get_check_interval = || {
+                let check_interval =
+                    Duration::from_secs(context.read().unwrap().config.check_interval);
+                if check_interval.is_zero() {
+                    return None;
+                }
+                Some(std::cmp::max(check_interval, Duration::from_secs(15)))
+            };
+            let configured_check_interval = context.read().unwrap().config.check_interval;
+            if configured_check_interval == 0 {
+                info!("Regular check is disabled");
+            } else if configured_check_interval < 15 {
+                warn!("Regular check interval is too short, fallback to 15 secs");
+            }
+            // `None` while disabled, until it is enabled by a config reload.
+            let mut next_check = get_check_interval();
             while !tx.is_closed() {
+                let sleep = async {
+                    match next_check {
+                        Some(duration) => tokio::time::sleep(duration).await,
+                        None => std::future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    _ = sleep => {}
+                    _ = config_reloaded.notified() => {
+                        // The reload checks right away, count the new interval from it.
+                        next_check = get_check_interval();
+                        continue;
+                    }
+                }
+                let Some(check_interval) = get_check_interval() else {
+                    next_check = None;
+                    continue;
+                };
                 let expiration = off_hours_cache.lock().await.expiration();
                 if expiration.is_zero() {
-                    if tx.send(ActionInfo::CheckAndLogin()).is_err() {
+                    if tx
+                        .send(ActionInfo::CheckAndLogin(Trigger::Interval))
+                        .is_err()
+                    {
                         break;
                     }
-                    tokio::time::sleep(check_interval).await;
+                    next_check = Some(check_interval);
                 } else {
-                    tokio::time::sleep(std::cmp::min(expiration, check_interval)).await;
+                    next_check = Some(std::cmp::min(expiration, check_interval));
                 }
             }
         });
         Ok(join_handle)
     }
 
+    async fn register_config_watcher(
+        &self,
+        tx: UnboundedSender<ActionInfo>,
+    ) -> Option<crate::config_watcher::ConfigWatcherHandle> {
+        use crate::config_watcher::ConfigWatcherHandle;
+        let config_path = self.config_path.clone()?;
+        let handle = ConfigWatcherHandle::register(
+            move || {
+                let _ = tx.send(ActionInfo::ReloadConfig());
+            },
+            config_path,
+        );
+        match handle {
+            Ok(handle) => Some(handle),
+            Err(err) => {
+                // Hot reload is a convenience, the daemon should work without it.
+                warn!("Failed to watch config file: {}", err.as_ref().chain());
+                None
+            }
+        }
+    }
+
+    /// Only the address at startup is used, changing it requires a restart.
+    async fn register_metrics_server(
+        &self,
+    ) -> Result<Option<MetricsServerHandle>, Box<dyn std::error::Error + Sync + Send>> {
+        let Some(metrics_config) = self.context().config.metrics.clone() else {
+            return Ok(None);
+        };
+        let handle = MetricsServerHandle::register(metrics_config.listen, self.metrics.clone())
+            .await
+            .map_err(|e| {
+                format!(
+                    "failed to serve metrics on {}: {}",
+                    metrics_config.listen, e
+                )
+            })?;
+        Ok(Some(handle))
+    }
+
+    /// Only the broker at startup is used, changing it requires a restart.
+    async fn register_mqtt(
+        &self,
+        tx: UnboundedSender<ActionInfo>,
+    ) -> Result<Option<MqttHandle>, Box<dyn std::error::Error + Sync + Send>> {
+        let context = self.context();
+        let Some(mqtt_config) = context.config.mqtt.as_ref() else {
+            return Ok(None);
+        };
+        let handle = MqttHandle::register(mqtt_config, move |action| {
+            let _ = tx.send(action);
+        })
+        .await?;
+        Ok(Some(handle))
+    }
+
+    #[cfg(unix)]
+    async fn register_unix_signal_listener(
+        &self,
+        tx: UnboundedSender<ActionInfo>,
+    ) -> Result<
+        crate::unix_signal_listener::UnixSignalListenerHandle,
+        Box<dyn std::error::Error + Sync + Send>,
+    > {
+        use crate::unix_signal_listener::UnixSignalListenerHandle;
+        let handle = UnixSignalListenerHandle::register(move |action| {
+            let _ = tx.send(action);
+        })?;
+        Ok(handle)
+    }
+
     #[cfg(target_os = "windows")]
     async fn register_win32_connectivity_hint_listener(
         &self,
@@ -123,7 +322,8 @@ impl AppMain {
                 == NetworkConnectivityLevelHintConstrainedInternetAccess
                 || connectivity_hint.ConnectivityLevel == NetworkConnectivityLevelHintLocalAccess
             {
-                tx.send(ActionInfo::CheckAndLogin()).unwrap();
+                tx.send(ActionInfo::CheckAndLogin(Trigger::NetworkChange))
+                    .unwrap();
             }
         };
         let handle = NetworkConnectivityHintChangedHandle::register(listener, true)?;
@@ -139,25 +339,80 @@ impl AppMain {
         Box<dyn std::error::Error + Sync + Send>,
     > {
         use crate::linux_network_listener::LinuxNetworkListenerHandle;
+        let context = self.context.clone();
         let handle = LinuxNetworkListenerHandle::register(
             move || {
-                tx.send(ActionInfo::CheckAndLogin()).unwrap();
+                tx.send(ActionInfo::CheckAndLogin(Trigger::NetworkChange))
+                    .unwrap();
             },
-            self.config.interface.clone(),
+            move || context.read().unwrap().config.interface.clone(),
         )?;
         Ok(handle)
     }
 
+    async fn reload_config(&self) -> bool {
+        let config_path = match self.config_path.as_ref() {
+            Some(config_path) => config_path,
+            None => return false,
+        };
+        info!("Reloading config from {}", config_path.display());
+        let config = match read_config_file(config_path) {
+            Ok(config) => config,
+            Err(err) => {
+                error!(
+                    "Failed to read config, keep using the previous one: {}",
+                    err.chain()
+                );
+                return false;
+            }
+        };
+        // Getting the password may run a command or wait for a keyring.
+        let (config, validation) = match tokio::task::spawn_blocking(move || {
+            let validation = validate_config(&config);
+            (config, validation)
+        })
+        .await
+        {
+            Ok(result) => result,
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        };
+        if let Err(err) = validation {
+            error!(
+                "Invalid config, keep using the previous one: {}",
+                err.chain()
+            );
+            return false;
+        }
+        self.metrics
+            .set_check_interval(Duration::from_secs(config.check_interval));
+        *self.context.write().unwrap() = Arc::new(AppContext::new(config));
+        self.config_reloaded.notify_one();
+        info!("Config reloaded");
+        true
+    }
+
     async fn event_loop(
         &self,
         mut rx: UnboundedReceiver<ActionInfo>,
+        events: Arc<dyn AppEvents>,
     ) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
         let mut last_check_at: Option<std::time::Instant> = None;
-        let dns_resolver = login::new_dns_resolver(self.config.interface.clone());
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
-        while let Some(action) = rx.recv().await {
+        loop {
+            // Between actions, so a stuck check stops the heartbeat.
+            self.metrics.heartbeat();
+            events.on_heartbeat();
+            let action = tokio::select! {
+                action = rx.recv() => action,
+                _ = heartbeat.tick() => continue,
+            };
+            let Some(action) = action else {
+                break;
+            };
             match action {
-                ActionInfo::CheckAndLogin() => {
+                ActionInfo::CheckAndLogin(trigger) => {
                     {
                         // debounce
                         let check_at = std::time::Instant::now();
@@ -168,45 +423,214 @@ impl AppMain {
                         }
                         last_check_at = Some(check_at);
                     }
-
-                    info!("Start to check network status");
-                    let network_status =
-                        get_network_status(self.config.interface.as_deref(), dns_resolver.clone())
+                    self.check_and_login(events.as_ref(), trigger).await;
+                }
+                ActionInfo::ForceCheckAndLogin(trigger) => {
+                    last_check_at = Some(std::time::Instant::now());
+                    self.logged_out.store(false, Ordering::Relaxed);
+                    self.check_and_login(events.as_ref(), trigger).await;
+                }
+                ActionInfo::Logout(trigger) => {
+                    last_check_at = Some(std::time::Instant::now());
+                    self.logout(events.as_ref(), trigger).await;
+                    // Find out the status after the logout.
+                    self.check_and_login(events.as_ref(), trigger).await;
+                }
+                ActionInfo::ReloadConfig() => {
+                    if self.reload_config().await {
+                        // Credentials or interface may have changed, check again with the new config.
+                        last_check_at = Some(std::time::Instant::now());
+                        self.off_hours_cache.lock().await.clear();
+                        self.metrics.set_off_hours_expiration(Duration::ZERO);
+                        self.check_and_login(events.as_ref(), Trigger::ConfigReload)
                             .await;
-                    if network_status.is_err() {
-                        error!(
-                            "Failed to get network status: {}",
-                            network_status.unwrap_err().chain()
-                        );
-                        continue;
-                    }
-                    let network_status = network_status.unwrap();
-                    info!("Network status: {:?}", network_status);
-                    if let login::NetworkStatus::AuthenticationNJUPT(ap_info) = network_status {
-                        info!("Start to login");
-                        match send_login_request(
-                            self.config.interface.as_deref(),
-                            dns_resolver.clone(),
-                            &self.config.credential,
-                            &ap_info,
-                        )
-                        .await
-                        {
-                            Ok(_) => {
-                                info!("Connected");
-                                self.off_hours_cache.lock().await.clear();
-                            }
-                            Err(err) => {
-                                error!("Failed to connect: {}", err.chain());
-                                if let WifiLoginError::OffHours() = err {
-                                    self.off_hours_cache.lock().await.set();
-                                }
-                            }
-                        };
                     }
                 }
+                ActionInfo::Stop() => {
+                    info!("Stop requested");
+                    break;
+                }
             }
         }
         Ok(())
     }
+
+    fn record(&self, entry: HistoryEntry) {
+        self.metrics.observe(&entry);
+        self.history.record(&entry);
+    }
+
+    async fn logout(&self, events: &dyn AppEvents, trigger: Trigger) {
+        let context = self.context();
+        let interface = context.config.interface.as_deref();
+
+        info!("Start to logout");
+        let started_at = std::time::Instant::now();
+        let result = send_logout_request(interface, context.dns_resolver.clone()).await;
+        self.record(HistoryEntry::logout(
+            trigger,
+            result.as_ref().map(|_| ()),
+            started_at.elapsed(),
+            interface,
+        ));
+        events.on_logout(result.as_ref().map(|_| ()));
+        match result {
+            Ok(()) => {
+                info!("Logged out, logins are skipped until a check is requested");
+                self.logged_out.store(true, Ordering::Relaxed);
+            }
+            Err(err) => error!("Failed to logout: {}", err.chain()),
+        }
+    }
+
+    async fn check_and_login(&self, events: &dyn AppEvents, trigger: Trigger) {
+        self.metrics.check_started();
+        self.check_and_login_inner(events, trigger).await;
+        self.metrics.check_finished();
+        let expiration = self.off_hours_cache.lock().await.expiration();
+        self.metrics.set_off_hours_expiration(expiration);
+    }
+
+    async fn check_and_login_inner(&self, events: &dyn AppEvents, trigger: Trigger) {
+        let context = self.context();
+        let config = &context.config;
+        let dns_resolver = &context.dns_resolver;
+        let interface = config.interface.as_deref();
+
+        info!("Start to check network status");
+        let started_at = std::time::Instant::now();
+        let network_status = get_network_status(interface, dns_resolver.clone()).await;
+        self.record(HistoryEntry::check(
+            trigger,
+            network_status.as_ref(),
+            started_at.elapsed(),
+            interface,
+        ));
+        let network_status = match network_status {
+            Ok(network_status) => network_status,
+            Err(err) => {
+                error!("Failed to get network status: {}", err.chain());
+                return;
+            }
+        };
+        info!("Network status: {:?}", network_status);
+        events.on_network_status(&network_status);
+        if let login::NetworkStatus::Connected = network_status {
+            self.logged_out.store(false, Ordering::Relaxed);
+        }
+        if let login::NetworkStatus::AuthenticationNJUPT(ap_info) = network_status {
+            if self.logged_out.load(Ordering::Relaxed) {
+                info!("Skip login, as it was logged out on request");
+                return;
+            }
+            info!("Start to login");
+            let started_at = std::time::Instant::now();
+            let result = send_login_request(
+                interface,
+                dns_resolver.clone(),
+                &config.credential,
+                &ap_info,
+            )
+            .await;
+            self.record(HistoryEntry::login(
+                trigger,
+                result.as_ref().map(|_| ()),
+                started_at.elapsed(),
+                interface,
+            ));
+            events.on_login_result(result.as_ref().map(|_| ()));
+            match result {
+                Ok(_) => {
+                    info!("Connected");
+                    events.on_network_status(&login::NetworkStatus::Connected);
+                    self.off_hours_cache.lock().await.clear();
+                }
+                Err(err) => {
+                    error!("Failed to connect: {}", err.chain());
+                    if let WifiLoginError::OffHours() = err {
+                        let mut off_hours_cache = self.off_hours_cache.lock().await;
+                        off_hours_cache.set();
+                        events.on_off_hours(off_hours_cache.until());
+                    }
+                }
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str =
+        "userid: B22999999\npassword: password123456\nisp: CT\ncheck_interval: 600\n";
+
+    fn new_app(name: &str) -> (AppMain, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("njupt_wifi_reload_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("njupt_wifi.yml");
+        std::fs::write(&config_path, CONFIG).unwrap();
+        let config = read_config_file(&config_path).unwrap();
+        let app = AppMain::new(
+            config,
+            Some(config_path.clone()),
+            History::new(dir.join("history.jsonl")),
+        );
+        (app, config_path)
+    }
+
+    #[tokio::test]
+    async fn reload_applies_valid_config() {
+        let (app, config_path) = new_app("valid");
+        std::fs::write(&config_path, CONFIG.replace("600", "300")).unwrap();
+        assert!(app.reload_config().await);
+        assert_eq!(app.context().config.check_interval, 300);
+        let _ = std::fs::remove_dir_all(config_path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn reload_keeps_previous_config_if_invalid() {
+        let (app, config_path) = new_app("invalid");
+        let previous = app.context();
+        std::fs::write(
+            &config_path,
+            CONFIG.replace("B22999999", "\"\"").replace("600", "300"),
+        )
+        .unwrap();
+        assert!(!app.reload_config().await);
+        assert!(Arc::ptr_eq(&previous, &app.context()));
+        assert_eq!(app.context().config.check_interval, 600);
+
+        std::fs::write(&config_path, "userid: [").unwrap();
+        assert!(!app.reload_config().await);
+        assert!(Arc::ptr_eq(&previous, &app.context()));
+        let _ = std::fs::remove_dir_all(config_path.parent().unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reload_restarts_regular_check() {
+        let (app, config_path) = new_app("regular_check");
+        std::fs::write(&config_path, CONFIG.replace("600", "3600")).unwrap();
+        assert!(app.reload_config().await);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let handle = app.register_regular_check(tx).await.unwrap();
+        tokio::time::sleep(Duration::from_secs(10)).await;
+
+        // Shortened while the old interval is still running.
+        std::fs::write(&config_path, CONFIG.replace("600", "60")).unwrap();
+        assert!(app.reload_config().await);
+        let started_at = tokio::time::Instant::now();
+        let action = tokio::time::timeout(Duration::from_secs(120), rx.recv())
+            .await
+            .expect("the check follows the new interval");
+        assert!(matches!(
+            action,
+            Some(ActionInfo::CheckAndLogin(Trigger::Interval))
+        ));
+        assert_eq!(started_at.elapsed().as_secs(), 60);
+
+        handle.abort();
+        let _ = std::fs::remove_dir_all(config_path.parent().unwrap());
+    }
 }
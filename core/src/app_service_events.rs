@@ -83,7 +83,7 @@ impl AppEvents for AppServiceEvents {
         };
         self.status_handle.set_service_status(next_status).unwrap();
     }
-    fn register_abort_handle(&mut self, handle: AbortHandle) {
+    fn register_abort_handle(&self, handle: AbortHandle) {
         self.abort_handle.lock().unwrap().register(handle);
     }
 }
@@ -0,0 +1,205 @@
+#![cfg(target_os = "linux")]
+use std::{
+    env,
+    ffi::OsString,
+    io,
+    os::{
+        linux::net::SocketAddrExt,
+        unix::{
+            ffi::OsStrExt,
+            net::{SocketAddr, UnixDatagram},
+        },
+    },
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use tokio::task::{AbortHandle, JoinHandle};
+
+use crate::{app_events::AppEvents, login::NetworkStatus, metrics::HEARTBEAT_TIMEOUT};
+
+/// Report the state of the daemon to systemd through `$NOTIFY_SOCKET`,
+/// for units with `Type=notify`.
+pub struct AppSystemdEvents {
+    notify_socket: OsString,
+    watchdog_interval: Option<Duration>,
+    watchdog_handle: Mutex<Option<JoinHandle<()>>>,
+    /// The watchdog is only pinged while the event loop is responding.
+    last_heartbeat: Arc<Mutex<Instant>>,
+}
+
+impl AppSystemdEvents {
+    /// Returns `None` if not started by systemd with a notification socket.
+    pub fn from_env() -> Option<Self> {
+        let notify_socket = env::var_os("NOTIFY_SOCKET").filter(|x| !x.is_empty())?;
+        Some(Self {
+            notify_socket,
+            watchdog_interval: watchdog_interval(
+                env::var("WATCHDOG_PID").ok().as_deref(),
+                env::var("WATCHDOG_USEC").ok().as_deref(),
+            ),
+            watchdog_handle: Mutex::new(None),
+            last_heartbeat: Arc::new(Mutex::new(Instant::now())),
+        })
+    }
+
+    fn notify(&self, state: &str) {
+        if let Err(err) = sd_notify(&self.notify_socket, state) {
+            warn!("Failed to notify systemd: {}", err);
+        }
+    }
+}
+
+/// From `$WATCHDOG_PID` and `$WATCHDOG_USEC`.
+fn watchdog_interval(pid: Option<&str>, usec: Option<&str>) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    let usec = usec?.parse::<u64>().ok()?;
+    if usec == 0 {
+        return None;
+    }
+    // Ping twice per period, as recommended by sd_watchdog_enabled(3).
+    Some(Duration::from_micros(usec / 2))
+}
+
+fn sd_notify(notify_socket: &OsString, state: &str) -> io::Result<()> {
+    let socket = UnixDatagram::unbound()?;
+    let path = notify_socket.as_bytes();
+    let addr = match path.strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(notify_socket)?,
+    };
+    socket.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+fn describe_network_status(status: &NetworkStatus) -> &'static str {
+    match status {
+        NetworkStatus::Connected => "Connected",
+        NetworkStatus::AuthenticationNJUPT(_) => "Authentication required (NJUPT)",
+        NetworkStatus::AuthenticationUnknown => "Authentication required (unknown portal)",
+        NetworkStatus::Disconnected => "Disconnected",
+    }
+}
+
+impl AppEvents for AppSystemdEvents {
+    fn on_started(&self) {
+        self.notify("READY=1\nSTATUS=Started");
+        if let Some(watchdog_interval) = self.watchdog_interval {
+            let notify_socket = self.notify_socket.clone();
+            let last_heartbeat = self.last_heartbeat.clone();
+            *last_heartbeat.lock().unwrap() = Instant::now();
+            let handle = tokio::spawn(async move {
+                loop {
+                    // A stuck event loop stops the pings, so that systemd restarts the daemon.
+                    // Checks are allowed as long as the health check allows them.
+                    if last_heartbeat.lock().unwrap().elapsed() <= HEARTBEAT_TIMEOUT {
+                        if let Err(err) = sd_notify(&notify_socket, "WATCHDOG=1") {
+                            warn!("Failed to ping systemd watchdog: {}", err);
+                        }
+                    }
+                    tokio::time::sleep(watchdog_interval).await;
+                }
+            });
+            self.watchdog_handle.lock().unwrap().replace(handle);
+        }
+    }
+    fn on_stopping(&self) {
+        if let Some(handle) = self.watchdog_handle.lock().unwrap().take() {
+            handle.abort();
+        }
+        self.notify("STOPPING=1\nSTATUS=Stopping");
+    }
+    fn on_stopped(&self) {
+        // Nothing to report, systemd notices the exit of the main process.
+    }
+    fn on_network_status(&self, status: &NetworkStatus) {
+        self.notify(&format!(
+            "STATUS=Network status: {}",
+            describe_network_status(status)
+        ));
+    }
+    fn on_heartbeat(&self) {
+        *self.last_heartbeat.lock().unwrap() = Instant::now();
+    }
+    fn register_abort_handle(&self, _handle: AbortHandle) {
+        // Stop requests are delivered as SIGTERM, which is handled by the signal listener.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_interval_from_env() {
+        let pid = std::process::id().to_string();
+        assert_eq!(watchdog_interval(None, None), None);
+        assert_eq!(
+            watchdog_interval(None, Some("60000000")),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(watchdog_interval(None, Some("0")), None);
+        assert_eq!(watchdog_interval(None, Some("1min")), None);
+        assert_eq!(
+            watchdog_interval(Some(&pid), Some("20000000")),
+            Some(Duration::from_secs(10))
+        );
+        // Meant for another process, e.g. inherited from a parent.
+        let other_pid = (std::process::id() + 1).to_string();
+        assert_eq!(watchdog_interval(Some(&other_pid), Some("20000000")), None);
+        assert_eq!(watchdog_interval(Some("not a pid"), Some("20000000")), None);
+    }
+
+    #[tokio::test]
+    async fn watchdog_follows_heartbeat() {
+        let path = env::temp_dir().join(format!("njupt_wifi_notify_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        let events = AppSystemdEvents {
+            notify_socket: path.clone().into_os_string(),
+            watchdog_interval: Some(Duration::from_millis(20)),
+            watchdog_handle: Mutex::new(None),
+            last_heartbeat: Arc::new(Mutex::new(Instant::now())),
+        };
+        let receive_all = || {
+            let mut buf = [0; 256];
+            let mut messages = Vec::new();
+            while let Ok(len) = socket.recv(&mut buf) {
+                messages.push(String::from_utf8_lossy(&buf[..len]).to_string());
+            }
+            messages
+        };
+
+        events.on_started();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let messages = receive_all();
+        assert_eq!(messages[0], "READY=1\nSTATUS=Started");
+        assert!(messages[1..].iter().all(|message| message == "WATCHDOG=1"));
+        assert!(messages.len() >= 3);
+
+        // The event loop stopped responding.
+        *events.last_heartbeat.lock().unwrap() =
+            Instant::now().checked_sub(HEARTBEAT_TIMEOUT * 2).unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        receive_all();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(receive_all().is_empty());
+
+        events.on_heartbeat();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(receive_all().contains(&"WATCHDOG=1".to_string()));
+
+        events.on_stopping();
+        assert_eq!(
+            receive_all().last().map(String::as_str),
+            Some("STOPPING=1\nSTATUS=Stopping")
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,392 @@
+use std::{
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, Subcommand, ValueEnum};
+use display_error_chain::ErrorChainExt;
+use njupt_wifi_login_configuration::{
+    config_file::{
+        read_config_file, upgrade_config_file, write_config_file, ConfigFileError, ConfigFormat,
+    },
+    config_path::{config_candidates, ConfigLocation, ConfigPath},
+    credential::{Credential, IspType},
+    login_config::LoginConfig,
+    migration::CONFIG_VERSION,
+    password::{is_passphrase_from_env, set_passphrase, Password, PasswordError, PasswordScope},
+    schema::login_config_schema,
+    secret::SecretString,
+    validation::validate_config_source,
+};
+use thiserror::Error;
+
+use crate::config_file::{validate_config, ConfigValidationError};
+
+#[derive(Args, Clone, Debug)]
+pub struct ConfigCommand {
+    #[command(subcommand)]
+    subcommand: ConfigSubCommand,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum ConfigSubCommand {
+    /// Create the config file interactively.
+    Init {
+        /// Overwrite the existing config file.
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// Read the password from the terminal and store it in the config file.
+    SetPassword {
+        /// Where the stored password can be decrypted.
+        #[arg(long, value_enum, default_value_t = PasswordScopeArg::LocalMachine)]
+        scope: PasswordScopeArg,
+    },
+    /// Encrypt the password again, e.g. after the machine ID changed or to upgrade its format.
+    /// If it cannot be decrypted, it is read from the terminal.
+    Rekey {
+        /// Where the stored password can be decrypted, defaults to its current scope.
+        #[arg(long, value_enum)]
+        scope: Option<PasswordScopeArg>,
+    },
+    /// Check whether the config file can be loaded.
+    Validate,
+    /// Print the config file with the passwords redacted.
+    Show,
+    /// Print which config file is used and the search order.
+    Path,
+    /// Print the JSON Schema of the config file.
+    Schema,
+    /// Upgrade an older config file to the current version.
+    Migrate {
+        /// Do not keep the original file as `<file>.v<version>.bak`.
+        #[arg(long, default_value_t = false)]
+        no_backup: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum PasswordScopeArg {
+    LocalMachine,
+    CurrentUser,
+    /// Encrypted with a passphrase, see `NJUPT_WIFI_PASSPHRASE`.
+    Passphrase,
+    Anywhere,
+}
+
+impl From<PasswordScopeArg> for PasswordScope {
+    fn from(value: PasswordScopeArg) -> Self {
+        match value {
+            PasswordScopeArg::LocalMachine => PasswordScope::LocalMachine,
+            PasswordScopeArg::CurrentUser => PasswordScope::CurrentUser,
+            PasswordScopeArg::Passphrase => PasswordScope::Passphrase,
+            PasswordScopeArg::Anywhere => PasswordScope::Anywhere,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigCommandError {
+    #[error("config file `{0}` already exists, pass --force to overwrite it")]
+    AlreadyExists(PathBuf),
+    #[error("failed to read config file `{0}`")]
+    ReadConfig(PathBuf, #[source] ConfigFileError),
+    #[error("failed to write config file `{0}`")]
+    WriteConfig(PathBuf, #[source] ConfigFileError),
+    #[error("failed to upgrade config file `{0}`")]
+    Migrate(PathBuf, #[source] ConfigFileError),
+    #[error("invalid config")]
+    InvalidConfig(#[from] ConfigValidationError),
+    #[error("failed to read from terminal")]
+    Terminal(#[from] io::Error),
+    #[error("passwords do not match")]
+    PasswordMismatch,
+    #[error("passphrases do not match")]
+    PassphraseMismatch,
+    #[error("the password is not stored in the config file, nothing to rekey")]
+    ExternalPassword,
+    #[error("failed to encrypt password")]
+    Password(#[from] PasswordError),
+    #[error("failed to serialize config")]
+    Serialize(#[from] serde_yaml::Error),
+    #[error("failed to serialize schema")]
+    Schema(#[from] serde_json::Error),
+}
+
+fn prompt(message: &str, default: &str) -> io::Result<String> {
+    if default.is_empty() {
+        print!("{}: ", message);
+    } else {
+        print!("{} [{}]: ", message, default);
+    }
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    let line = line.trim();
+    Ok(if line.is_empty() {
+        default.to_string()
+    } else {
+        line.to_string()
+    })
+}
+
+fn prompt_isp() -> io::Result<IspType> {
+    loop {
+        match prompt("ISP (EDU/CMCC/CT)", "EDU")?.to_uppercase().as_str() {
+            "EDU" => return Ok(IspType::EDU),
+            "CMCC" => return Ok(IspType::CMCC),
+            "CT" => return Ok(IspType::CT),
+            _ => println!("Please enter one of EDU, CMCC and CT."),
+        }
+    }
+}
+
+fn prompt_password_scope() -> io::Result<PasswordScope> {
+    loop {
+        match prompt(
+            "Password scope (local-machine/current-user/passphrase/anywhere)",
+            "local-machine",
+        )?
+        .as_str()
+        {
+            "local-machine" => return Ok(PasswordScope::LocalMachine),
+            "current-user" => return Ok(PasswordScope::CurrentUser),
+            "passphrase" => return Ok(PasswordScope::Passphrase),
+            "anywhere" => return Ok(PasswordScope::Anywhere),
+            _ => println!(
+                "Please enter one of local-machine, current-user, passphrase and anywhere."
+            ),
+        }
+    }
+}
+
+fn prompt_new_password(scope: PasswordScope, userid: &str) -> Result<Password, ConfigCommandError> {
+    let password = SecretString::from(rpassword::prompt_password("Password: ")?);
+    let confirmation = SecretString::from(rpassword::prompt_password("Confirm password: ")?);
+    if password != confirmation {
+        return Err(ConfigCommandError::PasswordMismatch);
+    }
+    // A mistyped passphrase would make the password unreadable, so confirm it as well.
+    if scope == PasswordScope::Passphrase && !is_passphrase_from_env() {
+        let passphrase = SecretString::from(rpassword::prompt_password("Passphrase: ")?);
+        let confirmation = SecretString::from(rpassword::prompt_password("Confirm passphrase: ")?);
+        if passphrase != confirmation {
+            return Err(ConfigCommandError::PassphraseMismatch);
+        }
+        set_passphrase(passphrase);
+    }
+    Ok(Password::try_new(password, scope, userid)?)
+}
+
+/// External sources only hold a reference, which is shown as is.
+fn describe_password(password: &Password) -> Result<serde_yaml::Value, serde_yaml::Error> {
+    Ok(match password {
+        Password::Basic(_) => "<redacted, plaintext>".into(),
+        password if password.is_external() => serde_yaml::to_value(password)?,
+        // Only a reference to the keyring item.
+        #[cfg(target_os = "linux")]
+        Password::Keyring { .. } => serde_yaml::to_value(password)?,
+        _ => "<redacted, encrypted>".into(),
+    })
+}
+
+/// The config as YAML, with every password passed through [`describe_password`].
+fn show_config(config: &LoginConfig) -> Result<String, serde_yaml::Error> {
+    let mut value = serde_yaml::to_value(config)?;
+    let passwords: [(&[&str], Option<&Password>); 2] = [
+        (&["password"], Some(config.credential.password())),
+        (
+            &["mqtt", "password"],
+            config.mqtt.as_ref().and_then(|mqtt| mqtt.password.as_ref()),
+        ),
+    ];
+    for (path, password) in passwords {
+        let (Some(password), Some((field, parents))) = (password, path.split_last()) else {
+            continue;
+        };
+        let mapping = parents
+            .iter()
+            .try_fold(&mut value, |value, parent| value.get_mut(*parent))
+            .and_then(|value| value.as_mapping_mut());
+        if let Some(mapping) = mapping {
+            mapping.insert((*field).into(), describe_password(password)?);
+        }
+    }
+    serde_yaml::to_string(&value)
+}
+
+fn read_config(config_path: &Path) -> Result<LoginConfig, ConfigCommandError> {
+    read_config_file(config_path)
+        .map_err(|e| ConfigCommandError::ReadConfig(config_path.to_path_buf(), e))
+}
+
+fn write_config(config_path: &Path, config: &LoginConfig) -> Result<(), ConfigCommandError> {
+    write_config_file(config_path, config)
+        .map_err(|e| ConfigCommandError::WriteConfig(config_path.to_path_buf(), e))
+}
+
+pub fn handle_config_command(
+    command: ConfigCommand,
+    config_path: ConfigPath,
+) -> Result<(), ConfigCommandError> {
+    let ConfigPath {
+        path: config_path,
+        location,
+    } = config_path;
+    match command.subcommand {
+        ConfigSubCommand::Init { force } => {
+            if config_path.exists() && !force {
+                return Err(ConfigCommandError::AlreadyExists(config_path));
+            }
+            let isp = prompt_isp()?;
+            let userid = prompt("UserID", "")?;
+            let scope = prompt_password_scope()?;
+            let password = prompt_new_password(scope, &userid)?;
+            let check_interval = loop {
+                match prompt("Check interval in seconds (0 means disabled)", "1200")?.parse() {
+                    Ok(check_interval) => break check_interval,
+                    Err(_) => println!("Please enter a number."),
+                }
+            };
+            let interface = prompt("Interface (empty means not specified)", "")?;
+            let mut config = LoginConfig::new(Credential::new(userid, password, isp));
+            config.check_interval = check_interval;
+            if !interface.is_empty() {
+                config.interface = Some(interface);
+            }
+            validate_config(&config)?;
+            write_config(&config_path, &config)?;
+            println!("Config written to {}", config_path.display());
+        }
+        ConfigSubCommand::SetPassword { scope } => {
+            let mut config = read_config(&config_path)?;
+            let userid = config.credential.userid().to_string();
+            let isp = config.credential.isp();
+            let password = prompt_new_password(scope.into(), &userid)?;
+            config.credential = Credential::new(userid, password, isp);
+            write_config(&config_path, &config)?;
+            println!("Password updated in {}", config_path.display());
+        }
+        ConfigSubCommand::Rekey { scope } => {
+            let mut config = read_config(&config_path)?;
+            let current = config.credential.password();
+            if current.is_external() {
+                return Err(ConfigCommandError::ExternalPassword);
+            }
+            let scope = scope
+                .map(PasswordScope::from)
+                .or(current.scope())
+                .unwrap_or(PasswordScope::LocalMachine);
+            let userid = config.credential.userid().to_string();
+            let isp = config.credential.isp();
+            let password = match current.get() {
+                Ok(password) => Password::try_new(password, scope, &userid)?,
+                Err(err) => {
+                    println!("The stored password cannot be decrypted: {}", err.chain());
+                    println!("Please enter it again.");
+                    prompt_new_password(scope, &userid)?
+                }
+            };
+            config.credential = Credential::new(userid, password, isp);
+            write_config(&config_path, &config)?;
+            println!("Password encrypted again in {}", config_path.display());
+        }
+        ConfigSubCommand::Validate => {
+            let s = std::fs::read_to_string(&config_path).map_err(|e| {
+                ConfigCommandError::ReadConfig(config_path.clone(), ConfigFileError::Io(e))
+            })?;
+            let config = validate_config_source(ConfigFormat::from_path(&config_path), &s)
+                .map_err(ConfigValidationError::from)?;
+            validate_config(&config)?;
+            println!("{} is valid", config_path.display());
+        }
+        ConfigSubCommand::Show => {
+            let config = read_config(&config_path)?;
+            println!("# {}", config_path.display());
+            print!("{}", show_config(&config)?);
+        }
+        ConfigSubCommand::Migrate { no_backup } => {
+            match upgrade_config_file(&config_path, !no_backup)
+                .map_err(|e| ConfigCommandError::Migrate(config_path.clone(), e))?
+            {
+                Some((from, backup_path)) => {
+                    println!(
+                        "Upgraded {} from version {} to {}",
+                        config_path.display(),
+                        from,
+                        CONFIG_VERSION
+                    );
+                    if let Some(backup_path) = backup_path {
+                        println!("The original file is kept as {}", backup_path.display());
+                    }
+                }
+                None => println!("{} is up to date", config_path.display()),
+            }
+        }
+        ConfigSubCommand::Schema => {
+            println!("{}", serde_json::to_string_pretty(&login_config_schema())?);
+        }
+        ConfigSubCommand::Path => {
+            println!("{} (from {})", config_path.display(), location);
+            if location != ConfigLocation::CommandLine {
+                println!("Search order:");
+                for candidate in config_candidates()? {
+                    let state = if candidate.path.is_file() {
+                        "found"
+                    } else {
+                        "missing"
+                    };
+                    println!(
+                        "  {} ({}, {})",
+                        candidate.path.display(),
+                        candidate.location,
+                        state
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn show_redacts_passwords() {
+        let dir = std::env::temp_dir().join(format!("njupt_wifi_show_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("njupt_wifi.yml");
+        std::fs::write(
+            &config_path,
+            "isp: CT\n\
+             userid: B22999999\n\
+             password: loginsecret123\n\
+             mqtt:\n  host: localhost\n  username: router\n  password: mqttsecret123\n",
+        )
+        .unwrap();
+        let shown = show_config(&read_config(&config_path).unwrap()).unwrap();
+        assert!(!shown.contains("loginsecret123"), "{}", shown);
+        assert!(!shown.contains("mqttsecret123"), "{}", shown);
+        let value: serde_yaml::Value = serde_yaml::from_str(&shown).unwrap();
+        assert_eq!(value["password"], "<redacted, plaintext>");
+        assert_eq!(value["mqtt"]["password"], "<redacted, plaintext>");
+        assert_eq!(value["mqtt"]["username"], "router");
+
+        // External sources are only references.
+        std::fs::write(
+            &config_path,
+            "isp: CT\n\
+             userid: B22999999\n\
+             password: {env: LOGIN_PASSWORD}\n\
+             mqtt:\n  host: localhost\n  username: router\n  password: {file: /run/secrets/mqtt}\n",
+        )
+        .unwrap();
+        let shown = show_config(&read_config(&config_path).unwrap()).unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&shown).unwrap();
+        assert_eq!(value["password"]["env"], "LOGIN_PASSWORD");
+        assert_eq!(value["mqtt"]["password"]["file"], "/run/secrets/mqtt");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,20 @@
+use njupt_wifi_login_configuration::{
+    login_config::LoginConfig, validation::ConfigValidationErrors,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigValidationError {
+    #[error(transparent)]
+    Invalid(#[from] ConfigValidationErrors),
+    #[error("failed to get password")]
+    PasswordError(#[from] njupt_wifi_login_configuration::password::PasswordError),
+}
+
+/// Check the config before it is applied to a running instance.
+pub fn validate_config(config: &LoginConfig) -> Result<(), ConfigValidationError> {
+    config.validate()?;
+    // Make sure the password can be decrypted on this machine.
+    config.credential.password().get()?;
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use log::trace;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+// Editors usually emit several events for a single save (truncate, write, rename, ...),
+// so wait for the file to settle before notifying.
+const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(500);
+
+#[must_use]
+pub struct ConfigWatcherHandle {
+    _watcher: RecommendedWatcher,
+    handle_debounce: JoinHandle<()>,
+}
+impl ConfigWatcherHandle {
+    pub fn register<F>(
+        func: F,
+        path: PathBuf,
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>>
+    where
+        F: Fn() + Send + 'static,
+    {
+        let file_name = path.file_name().map(|x| x.to_os_string());
+        // Watch the parent directory instead of the file itself,
+        // for the file is likely to be replaced rather than modified in place.
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<()>();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let event = match event {
+                Ok(event) => event,
+                Err(_) => return,
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                return;
+            }
+            if event
+                .paths
+                .iter()
+                .any(|x| x.file_name().map(|x| x.to_os_string()) == file_name)
+            {
+                trace!("Config file changed: {:?}", event);
+                let _ = tx.send(());
+            }
+        })?;
+        watcher.watch(Path::new(&directory), RecursiveMode::NonRecursive)?;
+
+        let handle_debounce = tokio::spawn(async move {
+            while rx.recv().await.is_some() {
+                loop {
+                    match tokio::time::timeout(DEBOUNCE_INTERVAL, rx.recv()).await {
+                        Ok(Some(())) => continue,
+                        Ok(None) => return,
+                        Err(_) => break,
+                    }
+                }
+                func();
+            }
+        });
+
+        Ok(Self {
+            _watcher: watcher,
+            handle_debounce,
+        })
+    }
+
+    pub fn abort(&self) {
+        self.handle_debounce.abort();
+    }
+
+    pub async fn join(self) {
+        let _ = self.handle_debounce.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn burst_of_changes_is_reloaded_once() {
+        let dir = std::env::temp_dir().join(format!("njupt_wifi_watcher_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("njupt_wifi.yml");
+        std::fs::write(&path, "userid: a\n").unwrap();
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let handle = ConfigWatcherHandle::register(
+            move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+            path.clone(),
+        )
+        .unwrap();
+
+        for i in 0..10 {
+            std::fs::write(&path, format!("userid: a{}\n", i)).unwrap();
+            // Other files in the directory are ignored.
+            std::fs::write(dir.join("other.yml"), "").unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        tokio::time::sleep(DEBOUNCE_INTERVAL * 3).await;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        std::fs::write(&path, "userid: b\n").unwrap();
+        tokio::time::sleep(DEBOUNCE_INTERVAL * 3).await;
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        handle.abort();
+        handle.join().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -11,11 +11,15 @@ use std::sync::Arc;
 
 type SharedResolver = Arc<AsyncResolver<GenericConnector<BindableTokioRuntimeProvider>>>;
 type SharedFallback = Arc<Box<dyn Fn(&Name) -> Option<Addrs> + Send + Sync>>;
+type SharedMatcher = Arc<Box<dyn Fn(&Name) -> bool + Send + Sync>>;
 
 #[derive(Clone)]
 pub struct CustomTrustDnsResolver {
     shared: SharedResolver,
     fallback: SharedFallback,
+    /// Names matched are resolved with their own resolver instead.
+    route: Option<(SharedMatcher, SharedResolver)>,
+    interface: Option<String>,
 }
 
 struct SocketAddrs {
@@ -32,17 +36,41 @@ impl CustomTrustDnsResolver {
     where
         F: Fn(&Name) -> Option<Addrs> + Send + Sync + 'static,
     {
-        let connector = GenericConnector::new(BindableTokioRuntimeProvider::new(interface));
+        let connector = GenericConnector::new(BindableTokioRuntimeProvider::new(interface.clone()));
         Ok(CustomTrustDnsResolver {
             shared: Arc::new(AsyncResolver::new(config, options, connector)),
             fallback: Arc::new(Box::new(fallback)),
+            route: None,
+            interface,
         })
     }
+
+    /// Resolve the names matched by `matches` with other servers, bound to the same interface.
+    pub fn with_route<M>(
+        mut self,
+        matches: M,
+        config: ResolverConfig,
+        options: ResolverOpts,
+    ) -> Self
+    where
+        M: Fn(&Name) -> bool + Send + Sync + 'static,
+    {
+        let connector =
+            GenericConnector::new(BindableTokioRuntimeProvider::new(self.interface.clone()));
+        self.route = Some((
+            Arc::new(Box::new(matches)),
+            Arc::new(AsyncResolver::new(config, options, connector)),
+        ));
+        self
+    }
 }
 
 impl Resolve for CustomTrustDnsResolver {
     fn resolve(&self, name: Name) -> Resolving {
-        let shared = self.shared.clone();
+        let shared = match self.route.as_ref() {
+            Some((matches, resolver)) if matches(&name) => resolver.clone(),
+            _ => self.shared.clone(),
+        };
         let fallback = self.fallback.clone();
         Box::pin(async move {
             match shared.lookup_ip(name.as_str()).await {
@@ -49,7 +49,7 @@ impl AddressFamilyPreference {
 fn get_bind_addr(interface: &str, preference: AddressFamilyPreference) -> io::Result<SocketAddr> {
     let interfaces = match NetworkInterface::show() {
         Ok(interfaces) => interfaces,
-        Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+        Err(e) => return Err(io::Error::other(e)),
     };
     let interface_info = interfaces.into_iter().find(|x| x.name == interface);
     if interface_info.is_none() {
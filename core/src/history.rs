@@ -0,0 +1,415 @@
+//! Append-only record of checks and login attempts, one JSON object per line.
+use chrono::{DateTime, Utc};
+use display_error_chain::ErrorChainExt;
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::login::{NetworkStatus, WifiLoginError};
+
+pub const HISTORY_FILE_NAME: &str = "history.jsonl";
+/// Past this size the history is moved to `history.jsonl.1`, replacing the previous one,
+/// so that half a year to a year of checks every 20 minutes is kept.
+const MAX_HISTORY_SIZE: u64 = 2 * 1024 * 1024;
+
+/// What started a check.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    Startup,
+    /// The regular check of `check_interval`.
+    Interval,
+    NetworkChange,
+    /// SIGUSR1
+    Signal,
+    ConfigReload,
+    /// The `check` and `login` commands.
+    Command,
+    /// The command topic of MQTT.
+    Mqtt,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Attempt {
+    Check,
+    Login,
+    Logout,
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Trigger::Startup => "startup",
+            Trigger::Interval => "interval",
+            Trigger::NetworkChange => "network_change",
+            Trigger::Signal => "signal",
+            Trigger::ConfigReload => "config_reload",
+            Trigger::Command => "command",
+            Trigger::Mqtt => "mqtt",
+        })
+    }
+}
+
+impl fmt::Display for Attempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Attempt::Check => "check",
+            Attempt::Login => "login",
+            Attempt::Logout => "logout",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub time: DateTime<Utc>,
+    pub trigger: Trigger,
+    pub attempt: Attempt,
+    /// Network status found by a check, see [`NetworkStatus::kind`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// `None` if the attempt succeeded, see [`WifiLoginError::kind`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+}
+
+impl HistoryEntry {
+    fn new(trigger: Trigger, attempt: Attempt, latency: Duration, interface: Option<&str>) -> Self {
+        Self {
+            time: Utc::now(),
+            trigger,
+            attempt,
+            status: None,
+            error: None,
+            latency_ms: latency.as_millis() as u64,
+            interface: interface.map(str::to_string),
+        }
+    }
+
+    pub fn check(
+        trigger: Trigger,
+        result: Result<&NetworkStatus, &WifiLoginError>,
+        latency: Duration,
+        interface: Option<&str>,
+    ) -> Self {
+        let mut entry = Self::new(trigger, Attempt::Check, latency, interface);
+        match result {
+            Ok(status) => entry.status = Some(status.kind().to_string()),
+            Err(err) => entry.error = Some(err.kind().to_string()),
+        }
+        entry
+    }
+
+    pub fn login(
+        trigger: Trigger,
+        result: Result<(), &WifiLoginError>,
+        latency: Duration,
+        interface: Option<&str>,
+    ) -> Self {
+        let mut entry = Self::new(trigger, Attempt::Login, latency, interface);
+        entry.error = result.err().map(|err| err.kind().to_string());
+        entry
+    }
+
+    pub fn logout(
+        trigger: Trigger,
+        result: Result<(), &WifiLoginError>,
+        latency: Duration,
+        interface: Option<&str>,
+    ) -> Self {
+        let mut entry = Self::new(trigger, Attempt::Logout, latency, interface);
+        entry.error = result.err().map(|err| err.kind().to_string());
+        entry
+    }
+
+    /// Whether the network is usable after the attempt, `None` if it is not known.
+    fn online(&self) -> Option<bool> {
+        match self.attempt {
+            Attempt::Check => match self.status.as_deref() {
+                Some("connected") => Some(true),
+                Some(_) => Some(false),
+                // The check itself failed, e.g. the connectivity check host is unreachable.
+                None => Some(false),
+            },
+            Attempt::Login => self.error.is_none().then_some(true),
+            Attempt::Logout => self.error.is_none().then_some(false),
+        }
+    }
+}
+
+pub struct History {
+    path: PathBuf,
+    max_size: u64,
+}
+
+impl History {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            max_size: MAX_HISTORY_SIZE,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Failures are only logged, as the history is not worth failing a login for.
+    pub fn record(&self, entry: &HistoryEntry) {
+        if let Err(err) = self.append(entry) {
+            warn!(
+                "Failed to write history to {}: {}",
+                self.path.display(),
+                err.chain()
+            );
+        }
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".1");
+        path.into()
+    }
+
+    fn append(&self, entry: &HistoryEntry) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match fs::metadata(&self.path) {
+            Ok(metadata) if metadata.len() >= self.max_size => {
+                fs::rename(&self.path, self.rotated_path())?
+            }
+            _ => {}
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        // A single write, so that concurrent instances do not interleave lines.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    /// Entries at or after `since`, including the rotated ones, in the order they were written.
+    /// Lines which cannot be parsed, e.g. cut short by a power loss, are skipped.
+    pub fn read(&self, since: Option<DateTime<Utc>>) -> io::Result<Vec<HistoryEntry>> {
+        let mut entries = Vec::new();
+        read_file(&self.rotated_path(), since, &mut entries)?;
+        read_file(&self.path, since, &mut entries)?;
+        Ok(entries)
+    }
+}
+
+fn read_file(
+    path: &Path,
+    since: Option<DateTime<Utc>>,
+    entries: &mut Vec<HistoryEntry>,
+) -> io::Result<()> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<HistoryEntry>(&line) {
+            Ok(entry) if since.is_none_or(|since| entry.time >= since) => entries.push(entry),
+            Ok(_) => {}
+            Err(err) => warn!("Skip line {} of {}: {}", index + 1, path.display(), err),
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct HistorySummary {
+    pub checks: usize,
+    pub check_errors: usize,
+    pub logins: usize,
+    pub logins_succeeded: usize,
+    /// `None` if there is no login attempt.
+    pub login_success_rate: Option<f64>,
+    /// Time from a check finding the network unusable until it is usable again.
+    pub offline_secs: u64,
+    /// Periods in which logins were refused for off hours.
+    pub off_hours_blocks: usize,
+}
+
+pub fn summarize(entries: &[HistoryEntry]) -> HistorySummary {
+    let mut summary = HistorySummary::default();
+    let mut offline_since: Option<DateTime<Utc>> = None;
+    let mut in_off_hours = false;
+    for entry in entries {
+        match entry.attempt {
+            Attempt::Check => {
+                summary.checks += 1;
+                if entry.error.is_some() {
+                    summary.check_errors += 1;
+                }
+            }
+            Attempt::Login => {
+                summary.logins += 1;
+                if entry.error.is_none() {
+                    summary.logins_succeeded += 1;
+                }
+                let off_hours = entry.error.as_deref() == Some("off_hours");
+                if off_hours && !in_off_hours {
+                    summary.off_hours_blocks += 1;
+                }
+                in_off_hours = off_hours;
+            }
+            Attempt::Logout => {}
+        }
+        match entry.online() {
+            Some(true) => {
+                if let Some(since) = offline_since.take() {
+                    summary.offline_secs += (entry.time - since).num_seconds().max(0) as u64;
+                }
+                in_off_hours = false;
+            }
+            Some(false) => {
+                offline_since.get_or_insert(entry.time);
+            }
+            None => {}
+        }
+    }
+    // Still offline at the last entry.
+    if let (Some(since), Some(last)) = (offline_since, entries.last()) {
+        summary.offline_secs += (last.time - since).num_seconds().max(0) as u64;
+    }
+    if summary.logins > 0 {
+        summary.login_success_rate = Some(summary.logins_succeeded as f64 / summary.logins as f64);
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        minutes: i64,
+        attempt: Attempt,
+        status: Option<&str>,
+        error: Option<&str>,
+    ) -> HistoryEntry {
+        HistoryEntry {
+            time: DateTime::from_timestamp(1_700_000_000 + minutes * 60, 0).unwrap(),
+            trigger: Trigger::Interval,
+            attempt,
+            status: status.map(str::to_string),
+            error: error.map(str::to_string),
+            latency_ms: 100,
+            interface: None,
+        }
+    }
+
+    fn check(minutes: i64, status: &str) -> HistoryEntry {
+        entry(minutes, Attempt::Check, Some(status), None)
+    }
+
+    fn login(minutes: i64, error: Option<&str>) -> HistoryEntry {
+        entry(minutes, Attempt::Login, None, error)
+    }
+
+    #[test]
+    fn offline_spans() {
+        let entries = [
+            check(0, "connected"),
+            check(10, "authentication_njupt"),
+            login(11, Some("off_hours")),
+            login(12, Some("off_hours")),
+            login(15, None),
+            check(20, "connected"),
+            entry(30, Attempt::Check, None, Some("http_request_failed")),
+            check(40, "connected"),
+        ];
+        let summary = summarize(&entries);
+        assert_eq!(
+            summary,
+            HistorySummary {
+                checks: 5,
+                check_errors: 1,
+                logins: 3,
+                logins_succeeded: 1,
+                login_success_rate: Some(1.0 / 3.0),
+                // 10 to 15 and 30 to 40.
+                offline_secs: 15 * 60,
+                off_hours_blocks: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn still_offline_at_the_end() {
+        let entries = [
+            check(0, "connected"),
+            check(20, "disconnected"),
+            check(40, "disconnected"),
+            check(60, "authentication_unknown"),
+        ];
+        let summary = summarize(&entries);
+        assert_eq!(summary.offline_secs, 40 * 60);
+
+        let entries = [
+            login(0, Some("off_hours")),
+            login(5, Some("authentication_failed")),
+        ];
+        // A failed login alone tells nothing about the network.
+        assert_eq!(summarize(&entries).offline_secs, 0);
+    }
+
+    #[test]
+    fn rate_without_logins() {
+        let summary = summarize(&[check(0, "connected"), check(20, "connected")]);
+        assert_eq!(summary.logins, 0);
+        assert_eq!(summary.login_success_rate, None);
+        assert_eq!(summarize(&[]), HistorySummary::default());
+
+        let summary = summarize(&[login(0, Some("authentication_failed"))]);
+        assert_eq!(summary.login_success_rate, Some(0.0));
+    }
+
+    #[test]
+    fn rotation() {
+        let dir = std::env::temp_dir().join(format!("njupt_wifi_history_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let history = History {
+            path: dir.join(HISTORY_FILE_NAME),
+            max_size: 1000,
+        };
+        for minutes in 0..30 {
+            history.record(&check(minutes, "connected"));
+        }
+        // Rotated before an entry is appended to a full file.
+        assert!(fs::metadata(history.rotated_path()).unwrap().len() >= 1000);
+
+        // The rotated entries come first, the older ones are dropped.
+        let entries = history.read(None).unwrap();
+        let minutes: Vec<i64> = entries
+            .iter()
+            .map(|entry| (entry.time.timestamp() - 1_700_000_000) / 60)
+            .collect();
+        assert_eq!(minutes.last(), Some(&29));
+        assert!(minutes.windows(2).all(|pair| pair[1] == pair[0] + 1));
+        assert!(minutes[0] > 0);
+
+        let since = check(25, "connected").time;
+        assert_eq!(history.read(Some(since)).unwrap().len(), 5);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
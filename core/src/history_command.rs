@@ -0,0 +1,194 @@
+use chrono::{DateTime, Local, NaiveDate, TimeDelta, Utc};
+use clap::Args;
+use serde::Serialize;
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+use crate::history::{summarize, History, HistoryEntry, HistorySummary};
+
+#[derive(Args, Clone, Debug)]
+pub struct HistoryCommand {
+    /// Only include entries since a duration ago (e.g. 30m, 12h, 7d),
+    /// a local date (e.g. 2024-09-01) or an RFC 3339 time.
+    #[arg(long, value_parser = parse_since)]
+    since: Option<DateTime<Utc>>,
+    /// Print the entries and the summary as JSON.
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum HistoryCommandError {
+    #[error("failed to read history `{0}`")]
+    Read(PathBuf, #[source] io::Error),
+    #[error("failed to serialize history")]
+    Serialize(#[from] serde_json::Error),
+}
+
+fn parse_since(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.to_utc());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return date
+            .and_hms_opt(0, 0, 0)
+            .and_then(|time| time.and_local_timezone(Local).earliest())
+            .map(|time| time.to_utc())
+            .ok_or_else(|| format!("invalid local date `{}`", s));
+    }
+    let invalid = || {
+        format!(
+            "expected a duration (e.g. 12h), a date or an RFC 3339 time, found `{}`",
+            s
+        )
+    };
+    let unit = s.chars().last().ok_or_else(invalid)?;
+    let amount: i64 = s[..s.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    let duration = match unit {
+        's' => TimeDelta::try_seconds(amount),
+        'm' => TimeDelta::try_minutes(amount),
+        'h' => TimeDelta::try_hours(amount),
+        'd' => TimeDelta::try_days(amount),
+        'w' => TimeDelta::try_weeks(amount),
+        _ => None,
+    }
+    .ok_or_else(invalid)?;
+    Ok(Utc::now() - duration)
+}
+
+fn format_duration(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m {}s", minutes, secs % 60)
+    }
+}
+
+fn print_entry(entry: &HistoryEntry) {
+    let result = match (entry.error.as_deref(), entry.status.as_deref()) {
+        (Some(error), _) => format!("error: {}", error),
+        (None, Some(status)) => status.to_string(),
+        (None, None) => "ok".to_string(),
+    };
+    println!(
+        "{}  {:<6}  {:<14}  {:<24}  {:>6} ms{}",
+        entry.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+        entry.attempt.to_string(),
+        entry.trigger.to_string(),
+        result,
+        entry.latency_ms,
+        entry
+            .interface
+            .as_deref()
+            .map(|interface| format!("  ({})", interface))
+            .unwrap_or_default()
+    );
+}
+
+fn print_summary(summary: &HistorySummary) {
+    println!(
+        "Checks: {} ({} failed)",
+        summary.checks, summary.check_errors
+    );
+    match summary.login_success_rate {
+        Some(rate) => println!(
+            "Logins: {} ({} succeeded, {:.1}%)",
+            summary.logins,
+            summary.logins_succeeded,
+            rate * 100.0
+        ),
+        None => println!("Logins: 0"),
+    }
+    println!("Time offline: {}", format_duration(summary.offline_secs));
+    println!("Off-hours blocks: {}", summary.off_hours_blocks);
+}
+
+#[derive(Serialize)]
+struct HistoryReport<'a> {
+    entries: &'a [HistoryEntry],
+    summary: HistorySummary,
+}
+
+pub fn handle_history_command(
+    command: HistoryCommand,
+    history: &History,
+) -> Result<(), HistoryCommandError> {
+    let entries = history
+        .read(command.since)
+        .map_err(|e| HistoryCommandError::Read(history.path().to_path_buf(), e))?;
+    let summary = summarize(&entries);
+    if command.json {
+        println!(
+            "{}",
+            serde_json::to_string(&HistoryReport {
+                entries: &entries,
+                summary,
+            })?
+        );
+        return Ok(());
+    }
+    println!("# {}", history.path().display());
+    for entry in entries.iter() {
+        print_entry(entry);
+    }
+    if !entries.is_empty() {
+        println!();
+    }
+    print_summary(&summary);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn since_duration() {
+        for (s, expected) in [
+            ("30m", TimeDelta::minutes(30)),
+            ("12h", TimeDelta::hours(12)),
+            ("7d", TimeDelta::days(7)),
+            ("2w", TimeDelta::weeks(2)),
+        ] {
+            let since = parse_since(s).unwrap();
+            let difference = Utc::now() - expected - since;
+            assert!(
+                difference >= TimeDelta::zero() && difference < TimeDelta::seconds(5),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn since_date_and_time() {
+        let expected = NaiveDate::from_ymd_opt(2024, 9, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+            .to_utc();
+        assert_eq!(parse_since("2024-09-01").unwrap(), expected);
+        assert_eq!(
+            parse_since("2024-09-01T08:00:00+08:00").unwrap(),
+            DateTime::parse_from_rfc3339("2024-09-01T00:00:00Z").unwrap()
+        );
+        assert_eq!(
+            parse_since("2024-09-01T00:00:00Z").unwrap().timestamp(),
+            1_725_148_800
+        );
+    }
+
+    #[test]
+    fn since_invalid() {
+        for s in ["", "m", "30", "30x", "-m", "2024-13-01", "yesterday"] {
+            assert!(parse_since(s).is_err(), "{}", s);
+        }
+    }
+}
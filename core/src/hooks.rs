@@ -0,0 +1,354 @@
+//! Run the commands of the `hooks` section on login events,
+//! with the details in `NJUPT_WIFI_*` environment variables.
+use chrono::{DateTime, Utc};
+use display_error_chain::ErrorChainExt;
+use log::*;
+use njupt_wifi_login_configuration::{
+    login_config::{HooksConfig, LoginConfig},
+    password::{Password, PASSPHRASE_COMMAND_ENV, PASSPHRASE_ENV},
+};
+use std::{fmt, process::Stdio, sync::Mutex, time::Duration};
+use tokio::{
+    process::Command,
+    task::{AbortHandle, JoinHandle},
+};
+
+use crate::{
+    app_events::AppEvents,
+    login::{NetworkStatus, WifiLoginError},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    Connected,
+    LoginSuccess,
+    LoginFailure,
+    OffHours,
+    StatusChange,
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HookEvent::Connected => "on_connected",
+            HookEvent::LoginSuccess => "on_login_success",
+            HookEvent::LoginFailure => "on_login_failure",
+            HookEvent::OffHours => "on_off_hours",
+            HookEvent::StatusChange => "on_status_change",
+        })
+    }
+}
+
+/// What the hooks need from the current config.
+pub struct HooksContext {
+    hooks: HooksConfig,
+    interface: Option<String>,
+    /// Variables which may hold a password or the passphrase, removed from the environment of the hooks.
+    secret_envs: Vec<String>,
+}
+
+impl HooksContext {
+    /// `None` if there is no `hooks` section.
+    pub fn from_config(config: &LoginConfig) -> Option<Self> {
+        let hooks = config.hooks.clone()?;
+        let mut secret_envs = vec![
+            PASSPHRASE_ENV.to_string(),
+            PASSPHRASE_COMMAND_ENV.to_string(),
+        ];
+        let passwords = [
+            Some(config.credential.password()),
+            config.mqtt.as_ref().and_then(|mqtt| mqtt.password.as_ref()),
+        ];
+        for password in passwords.into_iter().flatten() {
+            if let Password::Env { env } = password {
+                secret_envs.push(env.clone());
+            }
+        }
+        Some(Self {
+            hooks,
+            interface: config.interface.clone(),
+            secret_envs,
+        })
+    }
+
+    fn command(&self, event: HookEvent) -> Option<&[String]> {
+        match event {
+            HookEvent::Connected => self.hooks.on_connected.as_deref(),
+            HookEvent::LoginSuccess => self.hooks.on_login_success.as_deref(),
+            HookEvent::LoginFailure => self.hooks.on_login_failure.as_deref(),
+            HookEvent::OffHours => self.hooks.on_off_hours.as_deref(),
+            HookEvent::StatusChange => self.hooks.on_status_change.as_deref(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct HookState {
+    /// Status found by the previous check.
+    last_status: Option<&'static str>,
+    /// Address of this device seen by the portal of NJUPT.
+    user_ip: Option<String>,
+}
+
+pub struct AppHookEvents {
+    context_getter: Box<dyn Fn() -> Option<HooksContext> + Send + Sync>,
+    state: Mutex<HookState>,
+    /// Hooks run one at a time in the order of the events, each after the previous one.
+    last_hook: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl AppHookEvents {
+    /// The config is read on every event, so that reloading it takes effect.
+    pub fn new(context_getter: impl Fn() -> Option<HooksContext> + Send + Sync + 'static) -> Self {
+        Self {
+            context_getter: Box::new(context_getter),
+            state: Mutex::new(HookState::default()),
+            last_hook: Mutex::new(None),
+        }
+    }
+
+    /// The command of the hook with its environment, `None` if there is no hook for `event`.
+    fn command(
+        &self,
+        context: &HooksContext,
+        event: HookEvent,
+        vars: Vec<(&'static str, String)>,
+    ) -> Option<(String, Command)> {
+        let (program, args) = context
+            .command(event)
+            .and_then(|command| command.split_first())?;
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .env("NJUPT_WIFI_EVENT", event.to_string());
+        for env in context.secret_envs.iter() {
+            command.env_remove(env);
+        }
+        {
+            let state = self.state.lock().unwrap();
+            if let Some(status) = state.last_status {
+                command.env("NJUPT_WIFI_STATUS", status);
+            }
+            if let Some(user_ip) = state.user_ip.as_ref() {
+                command.env("NJUPT_WIFI_USER_IP", user_ip);
+            }
+        }
+        if let Some(interface) = context.interface.as_ref() {
+            command.env("NJUPT_WIFI_INTERFACE", interface);
+        }
+        command.envs(vars);
+        Some((program.clone(), command))
+    }
+
+    fn run(&self, event: HookEvent, vars: Vec<(&'static str, String)>) {
+        let Some(context) = (self.context_getter)() else {
+            return;
+        };
+        let Some((program, mut command)) = self.command(&context, event, vars) else {
+            return;
+        };
+        let timeout = Duration::from_secs(context.hooks.timeout);
+        let mut last_hook = self.last_hook.lock().unwrap();
+        let previous_hook = last_hook.take();
+        let handle = tokio::spawn(async move {
+            if let Some(previous_hook) = previous_hook {
+                let _ = previous_hook.await;
+            }
+            info!("Run the {} hook `{}`", event, program);
+            match tokio::time::timeout(timeout, command.output()).await {
+                Ok(Ok(output)) => {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    if output.status.success() {
+                        debug!("The {} hook exited: {}", event, stderr.trim_end());
+                    } else {
+                        warn!(
+                            "The {} hook `{}` failed with {}: {}",
+                            event,
+                            program,
+                            output.status,
+                            stderr.trim_end()
+                        );
+                    }
+                }
+                Ok(Err(err)) => warn!(
+                    "Failed to run the {} hook `{}`: {}",
+                    event,
+                    program,
+                    err.chain()
+                ),
+                // Dropping the child on timeout kills it.
+                Err(_) => warn!(
+                    "The {} hook `{}` was killed after {} seconds",
+                    event,
+                    program,
+                    timeout.as_secs()
+                ),
+            }
+        });
+        last_hook.replace(handle);
+    }
+}
+
+impl AppEvents for AppHookEvents {
+    fn on_started(&self) {
+        // Do nothing.
+    }
+    fn on_stopping(&self) {
+        // Do nothing.
+    }
+    fn on_stopped(&self) {
+        // Do nothing.
+    }
+    fn on_network_status(&self, status: &NetworkStatus) {
+        let previous = {
+            let mut state = self.state.lock().unwrap();
+            if let NetworkStatus::AuthenticationNJUPT(ap_info) = status {
+                state.user_ip = Some(ap_info.user_ip.clone());
+            }
+            state.last_status.replace(status.kind())
+        };
+        if previous == Some(status.kind()) {
+            return;
+        }
+        let vars = previous
+            .map(|previous| ("NJUPT_WIFI_PREVIOUS_STATUS", previous.to_string()))
+            .into_iter()
+            .collect();
+        self.run(HookEvent::StatusChange, vars);
+        if let NetworkStatus::Connected = status {
+            self.run(HookEvent::Connected, Vec::new());
+        }
+    }
+    fn on_login_result(&self, result: Result<(), &WifiLoginError>) {
+        match result {
+            Ok(()) => self.run(HookEvent::LoginSuccess, Vec::new()),
+            Err(err) => self.run(
+                HookEvent::LoginFailure,
+                vec![
+                    ("NJUPT_WIFI_ERROR_KIND", err.kind().to_string()),
+                    ("NJUPT_WIFI_ERROR", err.chain().to_string()),
+                ],
+            ),
+        }
+    }
+    fn on_off_hours(&self, until: Option<DateTime<Utc>>) {
+        let vars = until
+            .map(|until| ("NJUPT_WIFI_OFF_HOURS_UNTIL", until.to_rfc3339()))
+            .into_iter()
+            .collect();
+        self.run(HookEvent::OffHours, vars);
+    }
+    fn register_abort_handle(&self, _handle: AbortHandle) {
+        // Do nothing.
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use njupt_wifi_login_configuration::config_file::read_config_file;
+    use std::{collections::HashMap, ffi::OsStr, path::PathBuf, time::Instant};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("njupt_wifi_hooks_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn new_events(dir: &std::path::Path, config: &str) -> AppHookEvents {
+        let config_path = dir.join("njupt_wifi.yml");
+        std::fs::write(&config_path, config).unwrap();
+        let config = read_config_file(&config_path).unwrap();
+        AppHookEvents::new(move || HooksContext::from_config(&config))
+    }
+
+    async fn wait_for_hooks(events: &AppHookEvents) {
+        let last_hook = events.last_hook.lock().unwrap().take();
+        if let Some(last_hook) = last_hook {
+            last_hook.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn hook_environment() {
+        let dir = temp_dir("environment");
+        let output = dir.join("env");
+        let events = new_events(
+            &dir,
+            &format!(
+                "isp: CT\n\
+                 userid: B22999999\n\
+                 password: {{env: LOGIN_PASSWORD}}\n\
+                 interface: wlan0\n\
+                 mqtt:\n  host: localhost\n  username: router\n  password: {{env: MQTT_PASSWORD}}\n\
+                 hooks:\n  on_login_failure: [sh, -c, 'env > \"$0\"', {}]\n",
+                output.display()
+            ),
+        );
+
+        let context = (events.context_getter)().unwrap();
+        let (program, command) = events
+            .command(&context, HookEvent::LoginFailure, Vec::new())
+            .unwrap();
+        assert_eq!(program, "sh");
+        let envs: HashMap<&OsStr, Option<&OsStr>> = command.as_std().get_envs().collect();
+        for secret in [
+            "NJUPT_WIFI_PASSPHRASE",
+            "NJUPT_WIFI_PASSPHRASE_COMMAND",
+            "LOGIN_PASSWORD",
+            "MQTT_PASSWORD",
+        ] {
+            assert_eq!(envs.get(OsStr::new(secret)), Some(&None), "{}", secret);
+        }
+        assert!(events
+            .command(&context, HookEvent::LoginSuccess, Vec::new())
+            .is_none());
+
+        events.on_network_status(&NetworkStatus::AuthenticationUnknown);
+        let err = WifiLoginError::ServerRejected("wrong password".to_string());
+        events.on_login_result(Err(&err));
+        wait_for_hooks(&events).await;
+        let output = std::fs::read_to_string(&output).unwrap();
+        let vars: HashMap<&str, &str> = output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .filter(|(name, _)| name.starts_with("NJUPT_"))
+            .collect();
+        assert_eq!(
+            vars,
+            HashMap::from([
+                ("NJUPT_WIFI_EVENT", "on_login_failure"),
+                ("NJUPT_WIFI_STATUS", "authentication_unknown"),
+                ("NJUPT_WIFI_INTERFACE", "wlan0"),
+                ("NJUPT_WIFI_ERROR_KIND", err.kind()),
+                ("NJUPT_WIFI_ERROR", err.chain().to_string().as_str()),
+            ])
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn hook_timeout() {
+        let dir = temp_dir("timeout");
+        let marker = dir.join("finished");
+        let events = new_events(
+            &dir,
+            &format!(
+                "isp: CT\nuserid: B22999999\npassword: x\n\
+                 hooks:\n  timeout: 1\n  on_off_hours: [sh, -c, 'sleep 30; touch \"$0\"', {}]\n",
+                marker.display()
+            ),
+        );
+        let started_at = Instant::now();
+        events.on_off_hours(None);
+        wait_for_hooks(&events).await;
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+        assert!(!marker.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
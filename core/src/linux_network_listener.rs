@@ -16,12 +16,13 @@ pub struct LinuxNetworkListenerHandle {
     handle_polling: JoinHandle<()>,
 }
 impl LinuxNetworkListenerHandle {
-    pub fn register<F>(
+    pub fn register<F, I>(
         func: F,
-        interface: Option<String>,
+        interface: I,
     ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>>
     where
         F: Fn() + Send + 'static,
+        I: Fn() -> Option<String> + Send + 'static,
     {
         let (mut conn, mut _handle, mut messages) = new_connection()?;
 
@@ -41,7 +42,7 @@ impl LinuxNetworkListenerHandle {
                         }
                     });
                     if gateway.is_some() {
-                        if !Self::match_interface(&message, interface.as_deref()) {
+                        if !Self::match_interface(&message, interface().as_deref()) {
                             trace!("Skipping due to interface mismatch: {:?}", message);
                             continue;
                         }
@@ -77,7 +78,7 @@ impl LinuxNetworkListenerHandle {
             let interface_info = NetworkInterface::show()
                 .ok()
                 .and_then(|x| x.into_iter().find(|x| x.index == *oif));
-            interface_info.map_or(false, |x| x.name == interface_name)
+            interface_info.is_some_and(|x| x.name == interface_name)
         } else {
             false
         }
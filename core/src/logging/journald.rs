@@ -0,0 +1,69 @@
+#![cfg(target_os = "linux")]
+//! Send the log to systemd-journald with its native protocol, so that the level and
+//! the module are kept as fields, see systemd-journald.socket(8).
+use super::syslog::{severity, LocalSocket};
+use log::Record;
+use log4rs::append::Append;
+use std::{io, path::Path};
+
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+#[derive(Debug)]
+pub struct JournaldAppender {
+    socket: LocalSocket,
+}
+
+impl JournaldAppender {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            socket: LocalSocket::connect(Path::new(JOURNALD_SOCKET))?,
+        })
+    }
+}
+
+/// `NAME=value`, or the length-prefixed form for values spanning lines.
+fn add_field(datagram: &mut Vec<u8>, name: &str, value: &[u8]) {
+    datagram.extend_from_slice(name.as_bytes());
+    if value.contains(&b'\n') {
+        datagram.push(b'\n');
+        datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        datagram.push(b'=');
+    }
+    datagram.extend_from_slice(value);
+    datagram.push(b'\n');
+}
+
+impl Append for JournaldAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let mut datagram = Vec::new();
+        add_field(
+            &mut datagram,
+            "MESSAGE",
+            record.args().to_string().as_bytes(),
+        );
+        add_field(
+            &mut datagram,
+            "PRIORITY",
+            severity(record.level()).to_string().as_bytes(),
+        );
+        add_field(
+            &mut datagram,
+            "SYSLOG_IDENTIFIER",
+            env!("CARGO_PKG_NAME").as_bytes(),
+        );
+        add_field(&mut datagram, "TARGET", record.target().as_bytes());
+        if let Some(module) = record.module_path() {
+            add_field(&mut datagram, "CODE_MODULE", module.as_bytes());
+        }
+        if let Some(file) = record.file() {
+            add_field(&mut datagram, "CODE_FILE", file.as_bytes());
+        }
+        if let Some(line) = record.line() {
+            add_field(&mut datagram, "CODE_LINE", line.to_string().as_bytes());
+        }
+        Ok(self.socket.send(&datagram)?)
+    }
+
+    fn flush(&self) {}
+}
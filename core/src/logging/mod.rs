@@ -0,0 +1,113 @@
+//! Sinks, levels and formats of the log, see the `logging` section of the config.
+pub mod journald;
+pub mod syslog;
+
+use byte_unit::Byte;
+use log::LevelFilter;
+use log4rs::{
+    append::{
+        console::{ConsoleAppender, Target},
+        rolling_file::{
+            policy::compound::{
+                roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger, CompoundPolicy,
+            },
+            RollingFileAppender,
+        },
+        Append,
+    },
+    config::{Appender, Logger, Root},
+    encode::{json::JsonEncoder, pattern::PatternEncoder, Encode},
+};
+use njupt_wifi_login_configuration::login_config::{LogFormat, LogLevel, LogSink, LoggingConfig};
+use std::path::Path;
+
+const APPENDER_NAME: &str = "log";
+
+pub fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Off => LevelFilter::Off,
+        LogLevel::Error => LevelFilter::Error,
+        LogLevel::Warn => LevelFilter::Warn,
+        LogLevel::Info => LevelFilter::Info,
+        LogLevel::Debug => LevelFilter::Debug,
+        LogLevel::Trace => LevelFilter::Trace,
+    }
+}
+
+fn encoder(format: LogFormat) -> Box<dyn Encode> {
+    match format {
+        LogFormat::Text => Box::<PatternEncoder>::default(),
+        LogFormat::Json => Box::new(JsonEncoder::new()),
+    }
+}
+
+fn rolling_file_appender(
+    log_path: &Path,
+    config: &LoggingConfig,
+) -> Result<Box<dyn Append>, Box<dyn std::error::Error + Sync + Send>> {
+    let rolling_pattern = log_path.to_string_lossy()
+        + if config.compress {
+            // The extension tells the roller to compress.
+            ".{}.gz"
+        } else {
+            ".{}"
+        };
+    let file_policy = CompoundPolicy::new(
+        Box::new(SizeTrigger::new(
+            config
+                .size_limit
+                .unwrap_or(Byte::from_u64(3 * 1024 * 1024))
+                .as_u64(),
+        )),
+        Box::new(
+            FixedWindowRoller::builder()
+                .base(1)
+                .build(rolling_pattern.as_ref(), config.file_count.unwrap_or(2))?,
+        ),
+    );
+    Ok(Box::new(
+        RollingFileAppender::builder()
+            .encoder(encoder(config.format))
+            .build(log_path, Box::new(file_policy))?,
+    ))
+}
+
+/// `level` overrides the level in the config, but not the levels of modules.
+/// `log_path` is only used by the file sink.
+pub fn init_log(
+    config: &LoggingConfig,
+    level: Option<LevelFilter>,
+    log_path: &Path,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+    let appender: Box<dyn Append> = match config.sink {
+        LogSink::File => rolling_file_appender(log_path, config)?,
+        LogSink::Stderr => Box::new(
+            ConsoleAppender::builder()
+                .target(Target::Stderr)
+                .encoder(encoder(config.format))
+                .build(),
+        ),
+        #[cfg(target_os = "linux")]
+        LogSink::Journald => Box::new(journald::JournaldAppender::new()?),
+        #[cfg(unix)]
+        LogSink::Syslog => Box::new(syslog::SyslogAppender::new()?),
+        #[allow(unreachable_patterns)]
+        sink => {
+            return Err(format!("log sink {:?} is not supported on this platform", sink).into())
+        }
+    };
+    let level = level.unwrap_or(level_filter(config.level.unwrap_or(LogLevel::Info)));
+
+    let log_config = log4rs::Config::builder()
+        .appender(Appender::builder().build(APPENDER_NAME, appender))
+        .loggers(
+            config
+                .modules
+                .iter()
+                .map(|(module, level)| Logger::builder().build(module, level_filter(*level))),
+        )
+        .build(Root::builder().appender(APPENDER_NAME).build(level))?;
+
+    let _ = log4rs::init_config(log_config)?;
+    Ok(())
+}
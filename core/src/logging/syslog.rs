@@ -0,0 +1,91 @@
+#![cfg(unix)]
+//! Send the log to the local syslog daemon, as in RFC 3164 without the timestamp and host,
+//! which are added by the daemon.
+use log::{Level, Record};
+use log4rs::append::Append;
+use std::{
+    io,
+    os::unix::net::UnixDatagram,
+    path::{Path, PathBuf},
+};
+
+/// Where the syslog daemon listens on Linux, macOS and the BSDs.
+const SYSLOG_SOCKETS: [&str; 3] = ["/dev/log", "/var/run/syslog", "/var/run/log"];
+/// The `daemon` facility.
+const FACILITY_DAEMON: u8 = 3;
+
+/// A datagram socket connected to a local daemon, reconnected if the daemon is restarted.
+#[derive(Debug)]
+pub(super) struct LocalSocket {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+impl LocalSocket {
+    pub fn connect(path: &Path) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Self {
+            socket,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn send(&self, datagram: &[u8]) -> io::Result<()> {
+        if self.socket.send(datagram).is_err() {
+            self.socket.connect(&self.path)?;
+            self.socket.send(datagram)?;
+        }
+        Ok(())
+    }
+}
+
+/// Severity of syslog and the journal.
+pub(super) fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+#[derive(Debug)]
+pub struct SyslogAppender {
+    socket: LocalSocket,
+    pid: u32,
+}
+
+impl SyslogAppender {
+    pub fn new() -> io::Result<Self> {
+        let mut error = io::Error::from(io::ErrorKind::NotFound);
+        for path in SYSLOG_SOCKETS {
+            match LocalSocket::connect(Path::new(path)) {
+                Ok(socket) => {
+                    return Ok(Self {
+                        socket,
+                        pid: std::process::id(),
+                    })
+                }
+                Err(e) => error = e,
+            }
+        }
+        Err(error)
+    }
+}
+
+impl Append for SyslogAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let message = format!(
+            "<{}>{}[{}]: {}: {}",
+            FACILITY_DAEMON * 8 + severity(record.level()),
+            env!("CARGO_PKG_NAME"),
+            self.pid,
+            record.target(),
+            record.args()
+        );
+        Ok(self.socket.send(message.as_bytes())?)
+    }
+
+    fn flush(&self) {}
+}
@@ -6,7 +6,12 @@ use hickory_resolver::config::{
     NameServerConfig, Protocol, ResolverConfig, ResolverOpts, ServerOrderingStrategy,
 };
 use log::*;
-use njupt_wifi_login_configuration::{credential::Credential, password::PasswordError};
+use njupt_wifi_login_configuration::{
+    credential::Credential,
+    login_config::{DnsConfig, DnsOrdering, DnsPolicy, DnsProtocol, DnsServer, LoginConfig},
+    password::{Password, PasswordError},
+    secret::SecretString,
+};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use reqwest::{
@@ -27,7 +32,9 @@ const URLS_CONNECTIVITY_CHECK_204: [&str; 3] = [
     "http://connectivitycheck.platform.hicloud.com/generate_204",
     "http://wifi.vivo.com.cn/generate_204",
 ];
+const AP_PORTAL_HOST: &str = "p.njupt.edu.cn";
 const URL_AP_PORTAL: &str = "https://p.njupt.edu.cn/a79.htm";
+const URL_LOGOUT: &str = "https://p.njupt.edu.cn:802/eportal/portal/logout";
 const AP_PORTAL_FALLBACK_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 10, 244, 11));
 const POSSIBLE_MSGS_OFF_HOURS: [&str; 2] = [
     // Confirmed on 2023-07-24
@@ -67,12 +74,44 @@ pub enum WifiLoginError {
     OffHours(),
     #[error("authentication server rejected: {0}")]
     ServerRejected(String),
+    #[error("portal of NJUPT is unavailable")]
+    PortalUnavailable(),
+    #[error("unexpected response to the logout request")]
+    UnexpectedLogoutResponse(),
     #[error("failed to get password")]
     PasswordError(#[from] PasswordError),
     #[error("failed to bind to interface")]
     BindToInterfaceError(#[from] crate::smart_bind_to_interface_ext::SmartBindToInterfaceError),
 }
 
+impl NetworkStatus {
+    /// Stable name of the status, as written to the history.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NetworkStatus::Connected => "connected",
+            NetworkStatus::AuthenticationNJUPT(_) => "authentication_njupt",
+            NetworkStatus::AuthenticationUnknown => "authentication_unknown",
+            NetworkStatus::Disconnected => "disconnected",
+        }
+    }
+}
+
+impl WifiLoginError {
+    /// Stable name of the error, as written to the history.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WifiLoginError::HttpRequestFailed(_) => "http_request_failed",
+            WifiLoginError::AuthenticationFailed() => "authentication_failed",
+            WifiLoginError::OffHours() => "off_hours",
+            WifiLoginError::ServerRejected(_) => "server_rejected",
+            WifiLoginError::PortalUnavailable() => "portal_unavailable",
+            WifiLoginError::UnexpectedLogoutResponse() => "unexpected_logout_response",
+            WifiLoginError::PasswordError(_) => "password_error",
+            WifiLoginError::BindToInterfaceError(_) => "bind_to_interface_error",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct NJUPTAuthenticationResult {
     result: i32,
@@ -80,34 +119,68 @@ struct NJUPTAuthenticationResult {
     ret_code: Option<i32>,
 }
 
-pub fn new_dns_resolver(interface: Option<String>) -> Arc<CustomTrustDnsResolver> {
-    let mut config = ResolverConfig::new();
-
-    let ns = NameServerConfig::new(
-        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
-        Protocol::Udp,
-    );
-    config.add_name_server(ns);
-
-    let ns = NameServerConfig::new(
-        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(114, 114, 114, 114)), 53),
-        Protocol::Udp,
-    );
-    config.add_name_server(ns);
+/// Servers of the `dns` section, or the defaults if there is none.
+pub fn new_dns_resolver(login_config: &LoginConfig) -> Arc<CustomTrustDnsResolver> {
+    let default_dns = DnsConfig::default();
+    let dns = login_config.dns.as_ref().unwrap_or(&default_dns);
+    let interface = login_config.interface.clone();
+    let resolver_config = |encrypted: Option<bool>| {
+        let mut config = ResolverConfig::new();
+        let servers = dns.servers.iter().filter(|server| {
+            encrypted.is_none_or(|encrypted| server.protocol.is_encrypted() == encrypted)
+        });
+        for server in servers {
+            config.add_name_server(name_server_config(server));
+        }
+        config
+    };
 
     let mut opts = ResolverOpts::default();
-    opts.server_ordering_strategy = ServerOrderingStrategy::QueryStatistics;
-    Arc::new(
-        CustomTrustDnsResolver::new(interface, config, opts, |name: &Name| -> Option<Addrs> {
-            if name.as_str() == "p.njupt.edu.cn" {
-                return Some(Box::new(
-                    vec![SocketAddr::new(AP_PORTAL_FALLBACK_IP, 0)].into_iter(),
-                ));
-            }
-            None
-        })
-        .unwrap(),
-    )
+    opts.timeout = Duration::from_secs(dns.timeout);
+    opts.attempts = dns.attempts;
+    opts.server_ordering_strategy = match dns.ordering {
+        DnsOrdering::QueryStatistics => ServerOrderingStrategy::QueryStatistics,
+        DnsOrdering::UserProvided => ServerOrderingStrategy::UserProvidedOrder,
+    };
+    let fallback = |name: &Name| -> Option<Addrs> {
+        if name.as_str() == AP_PORTAL_HOST {
+            return Some(Box::new(
+                vec![SocketAddr::new(AP_PORTAL_FALLBACK_IP, 0)].into_iter(),
+            ));
+        }
+        None
+    };
+    let resolver = match dns.policy {
+        DnsPolicy::AllServers => {
+            CustomTrustDnsResolver::new(interface, resolver_config(None), opts, fallback).unwrap()
+        }
+        // The portal is only reachable on campus, where encrypted DNS may be blocked before login.
+        DnsPolicy::EncryptedProbes => CustomTrustDnsResolver::new(
+            interface,
+            resolver_config(Some(true)),
+            opts.clone(),
+            fallback,
+        )
+        .unwrap()
+        .with_route(
+            |name: &Name| name.as_str() == AP_PORTAL_HOST,
+            resolver_config(Some(false)),
+            opts,
+        ),
+    };
+    Arc::new(resolver)
+}
+
+fn name_server_config(server: &DnsServer) -> NameServerConfig {
+    let protocol = match server.protocol {
+        DnsProtocol::Udp => Protocol::Udp,
+        DnsProtocol::Tcp => Protocol::Tcp,
+        DnsProtocol::Tls => Protocol::Tls,
+        DnsProtocol::Https => Protocol::Https,
+    };
+    let mut config = NameServerConfig::new(server.socket_addr(), protocol);
+    config.tls_dns_name = server.tls_name.clone();
+    config
 }
 
 pub fn random_url_for_connectivity_check_204() -> &'static str {
@@ -150,7 +223,7 @@ pub async fn get_network_status(
                 Err(_) => return Ok(NetworkStatus::Disconnected),
             };
             if NJUPT_AUTHENTICATION_PATTERN.is_match(content.as_str()) {
-                match get_ap_info(client).await {
+                match get_ap_info(&client, URL_AP_PORTAL).await {
                     Some(value) => Ok(NetworkStatus::AuthenticationNJUPT(value)),
                     None => Ok(NetworkStatus::AuthenticationUnknown),
                 }
@@ -165,8 +238,8 @@ pub async fn get_network_status(
     }
 }
 
-async fn get_ap_info(client: reqwest::Client) -> Option<ApInfo> {
-    let ap_portal = match client.get(URL_AP_PORTAL).send().await {
+async fn get_ap_info(client: &reqwest::Client, url: &str) -> Option<ApInfo> {
+    let ap_portal = match client.get(url).send().await {
         Ok(ap_portal) => ap_portal,
         Err(err) => {
             error!("Failed to get ap info: {}", err.chain());
@@ -194,6 +267,15 @@ async fn get_ap_info(client: reqwest::Client) -> Option<ApInfo> {
     }
 }
 
+/// Get the password on the blocking pool, as it may run a command or wait for a keyring.
+pub async fn get_password(password: &Password) -> Result<SecretString, PasswordError> {
+    let password = password.clone();
+    match tokio::task::spawn_blocking(move || password.get()).await {
+        Ok(result) => result,
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
+
 pub async fn send_login_request(
     interface: Option<&str>,
     dns_resolver: Arc<impl Resolve + 'static>,
@@ -202,12 +284,12 @@ pub async fn send_login_request(
 ) -> Result<(), WifiLoginError> {
     let url = "https://p.njupt.edu.cn:802/eportal/portal/login";
     let ddddd = format!(",0,{}", credential.derive_account());
-    let upass = credential.password().get()?;
+    let upass = get_password(credential.password()).await?;
     let params = [
         ("callback", "dr1003"),
         ("login_method", "1"),
         ("user_account", ddddd.as_ref()),
-        ("user_password", upass.as_ref()),
+        ("user_password", upass.expose_secret()),
         ("wlan_user_ip", ap_info.user_ip.as_ref()),
         ("wlan_user_ipv6", ""),
         ("wlan_user_mac", "000000000000"),
@@ -226,9 +308,15 @@ pub async fn send_login_request(
         .dns_resolver(dns_resolver)
         .redirect(Policy::none())
         .build()?;
-    let resp = client.get(url).query(&params).send().await?;
+    // The URL carries the password in its query, keep it out of errors and logs.
+    let resp = client
+        .get(url)
+        .query(&params)
+        .send()
+        .await
+        .map_err(reqwest::Error::without_url)?;
     if resp.status() == reqwest::StatusCode::OK {
-        let content = resp.text().await?;
+        let content = resp.text().await.map_err(reqwest::Error::without_url)?;
         if content.len() <= ("dr1003();".len()) {
             error!("Failed to parse authentication result: {}", content);
         } else {
@@ -265,3 +353,196 @@ pub async fn send_login_request(
     }
     Err(WifiLoginError::AuthenticationFailed())
 }
+
+/// Log out of the portal of NJUPT, with the address of this device read from the portal page.
+pub async fn send_logout_request(
+    interface: Option<&str>,
+    dns_resolver: Arc<impl Resolve + 'static>,
+) -> Result<(), WifiLoginError> {
+    let client = reqwest::Client::builder()
+        .optional_smart_bind_to_interface(interface)?
+        .no_proxy()
+        .timeout(Duration::from_secs(30))
+        .dns_resolver(dns_resolver)
+        .redirect(Policy::none())
+        .build()?;
+    logout(&client, URL_AP_PORTAL, URL_LOGOUT).await
+}
+
+/// The request sent by the logout button of the Dr.COM ePortal page (`jsVersion` 4.1.3),
+/// which answers with JSONP like the login.
+async fn logout(
+    client: &reqwest::Client,
+    ap_portal_url: &str,
+    logout_url: &str,
+) -> Result<(), WifiLoginError> {
+    let ap_info = get_ap_info(client, ap_portal_url)
+        .await
+        .ok_or(WifiLoginError::PortalUnavailable())?;
+    let params = [
+        ("callback", "dr1004"),
+        ("login_method", "1"),
+        // With `ac_logout`, the session is found by `wlan_user_ip`. The page always sends
+        // these placeholders instead of an account, so no credential is needed to log out.
+        ("user_account", "drcom"),
+        ("user_password", "123"),
+        ("ac_logout", "1"),
+        ("register_mode", "1"),
+        ("wlan_user_ip", ap_info.user_ip.as_ref()),
+        ("wlan_user_ipv6", ""),
+        ("wlan_vlan_id", "0"),
+        ("wlan_user_mac", "000000000000"),
+        ("wlan_ac_ip", ""),
+        ("wlan_ac_name", ""),
+        ("jsVersion", "4.1.3"),
+        ("v", "3335"),
+        ("lang", "zh"),
+    ];
+    let content = client
+        .get(logout_url)
+        .query(&params)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let result = content
+        .trim()
+        .strip_prefix("dr1004(")
+        .and_then(|content| content.strip_suffix(");"))
+        .and_then(|json| serde_json::from_str::<NJUPTAuthenticationResult>(json).ok());
+    match result {
+        Some(result) if result.result == 1 => Ok(()),
+        Some(result) => Err(WifiLoginError::ServerRejected(result.msg)),
+        None => {
+            error!("Failed to parse logout result: {}", content);
+            Err(WifiLoginError::UnexpectedLogoutResponse())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::SocketAddr, sync::Mutex};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Serve the portal page and the logout endpoint over plain HTTP,
+    /// and record the request lines.
+    async fn start_portal(
+        ap_portal: Option<&'static str>,
+        logout: &'static str,
+    ) -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut head = Vec::new();
+                let mut chunk = [0u8; 1024];
+                while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    head.extend_from_slice(&chunk[..n]);
+                }
+                let head = String::from_utf8_lossy(&head);
+                let request_line = head.lines().next().unwrap_or_default().to_string();
+                let body = match request_line.split(' ').nth(1).unwrap_or_default() {
+                    "/a79.htm" => ap_portal,
+                    path if path.starts_with("/eportal/portal/logout?") => Some(logout),
+                    _ => None,
+                };
+                recorded.lock().unwrap().push(request_line);
+                let response = match body {
+                    Some(body) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    ),
+                    None => {
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string()
+                    }
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            }
+        });
+        (addr, requests)
+    }
+
+    const AP_PORTAL: &str = "<script>v46ip='10.163.1.2';</script>";
+
+    async fn logout_from(
+        ap_portal: Option<&'static str>,
+        logout_response: &'static str,
+    ) -> (Result<(), WifiLoginError>, Vec<String>) {
+        let (addr, requests) = start_portal(ap_portal, logout_response).await;
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let result = logout(
+            &client,
+            &format!("http://{}/a79.htm", addr),
+            &format!("http://{}/eportal/portal/logout", addr),
+        )
+        .await;
+        let requests = requests.lock().unwrap().clone();
+        (result, requests)
+    }
+
+    #[tokio::test]
+    async fn logout_succeeds() {
+        let (result, requests) = logout_from(
+            Some(AP_PORTAL),
+            "dr1004({\"result\":1,\"msg\":\"注销成功\"});\n",
+        )
+        .await;
+        result.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].starts_with("GET /a79.htm "));
+        let query = requests[1]
+            .split(' ')
+            .nth(1)
+            .unwrap()
+            .split_once('?')
+            .unwrap()
+            .1;
+        for param in [
+            "callback=dr1004",
+            "ac_logout=1",
+            "user_account=drcom",
+            "wlan_user_ip=10.163.1.2",
+        ] {
+            assert!(query.split('&').any(|x| x == param), "{}", param);
+        }
+    }
+
+    #[tokio::test]
+    async fn logout_failures() {
+        let (result, _) = logout_from(
+            Some(AP_PORTAL),
+            "dr1004({\"result\":0,\"msg\":\"not online\",\"ret_code\":1});",
+        )
+        .await;
+        assert!(matches!(result, Err(WifiLoginError::ServerRejected(msg)) if msg == "not online"));
+
+        let (result, _) = logout_from(Some(AP_PORTAL), "<html>maintenance</html>").await;
+        let err = result.unwrap_err();
+        assert!(matches!(err, WifiLoginError::UnexpectedLogoutResponse()));
+        assert_eq!(err.kind(), "unexpected_logout_response");
+
+        // The address of this device is unknown without the portal page.
+        let (result, requests) = logout_from(None, "").await;
+        assert!(matches!(result, Err(WifiLoginError::PortalUnavailable())));
+        assert_eq!(requests.len(), 1);
+        let (result, requests) = logout_from(Some("no address"), "").await;
+        assert!(matches!(result, Err(WifiLoginError::PortalUnavailable())));
+        assert_eq!(requests.len(), 1);
+    }
+}
@@ -2,35 +2,56 @@
 mod app_events;
 mod app_main;
 mod app_service_events;
+mod app_systemd_events;
+mod config_command;
+mod config_file;
+mod config_watcher;
 mod dns;
+mod history;
+mod history_command;
+mod hooks;
 mod linux_network_listener;
+mod logging;
 mod login;
+mod metrics;
+mod metrics_server;
+mod mqtt;
+mod notification;
 mod off_hours_cache;
+mod oneshot_command;
+mod openwrt_command;
 mod smart_bind_to_interface_ext;
+mod unix_signal_listener;
 mod win32_network_connectivity_hint_changed;
 use app_events::DefaultAppEvents;
 use app_main::AppMain;
-use byte_unit::Byte;
 use clap::{Parser, Subcommand};
+use config_command::{handle_config_command, ConfigCommand};
 use display_error_chain::ErrorChainExt;
+use history::{History, HISTORY_FILE_NAME};
+use history_command::{handle_history_command, HistoryCommand};
 use log::*;
-use log4rs::{
-    append::rolling_file::{
-        policy::compound::{
-            roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger, CompoundPolicy,
-        },
-        RollingFileAppender,
+use njupt_wifi_login_configuration::{
+    config_file::read_and_migrate_config_file,
+    config_path::{
+        default_state_dir, discover_config_path, find_config_in_dir, ConfigLocation, ConfigPath,
     },
-    config::{Appender, Root},
-    encode::pattern::PatternEncoder,
+    login_config::LoginConfig,
 };
-use njupt_wifi_login_configuration::login_config::LoginConfig;
+use oneshot_command::{handle_oneshot_command, OneshotCommand};
 use std::env;
 use std::path::{Path, PathBuf};
+mod systemd_command;
 mod windows_service_command;
+#[cfg(target_os = "linux")]
+use openwrt_command::{handle_openwrt_command, OpenWrtCommand};
+#[cfg(target_os = "linux")]
+use systemd_command::{handle_systemd_command, SystemdCommand};
 #[cfg(all(feature = "windows-service-mode", target_os = "windows"))]
 use windows_service_command::{handle_service_command, ServiceCommand};
 
+const DEFAULT_LOG_FILE_NAME: &str = "njupt_wifi.log";
+
 #[derive(Debug)]
 pub enum ActionInfo {
     CheckAndLogin(),
@@ -39,18 +60,24 @@ pub enum ActionInfo {
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Provide more detailed log during execution.
+    /// Provide more detailed log during execution, the same as `--log-level trace`.
     #[arg(short, long, default_value_t = false)]
     verbose: bool,
+    /// Set the log level of modules without their own level in the config
+    /// (off, error, warn, info, debug or trace) [default: `logging.level` in the config, or info]
+    #[arg(long, conflicts_with = "verbose")]
+    log_level: Option<LevelFilter>,
     /// Set working directory
     #[arg(short('D'), long("directory"))]
     working_directory: Option<String>,
-    /// Set configuration file
-    #[arg(short, long, default_value = "njupt_wifi.yml")]
-    config: String,
-    /// Set log file
-    #[arg(long, default_value = "njupt_wifi.log")]
-    log_file: String,
+    /// Set configuration file [default: njupt_wifi.yml, .yaml, .toml or .json searched in
+    /// $NJUPT_WIFI_CONFIG, $XDG_CONFIG_HOME/njupt_wifi/, /etc/njupt_wifi/ and the executable directory]
+    #[arg(short, long)]
+    config: Option<String>,
+    /// Set log file, relative to the state directory
+    /// [default: `logging.file` in the config, or njupt_wifi.log]
+    #[arg(long)]
+    log_file: Option<String>,
     #[command(subcommand)]
     command: Option<Command>,
 }
@@ -65,23 +92,86 @@ impl Args {
             Ok(path.join(f))
         }
     }
+
+    /// `$STATE_DIRECTORY` set by systemd, the working directory if it is set,
+    /// or the default state directory.
+    pub fn state_dir(&self) -> std::io::Result<PathBuf> {
+        if let Some(dir) = env::var_os("STATE_DIRECTORY").filter(|dir| !dir.is_empty()) {
+            // Multiple directories are separated by colons, the first one is ours.
+            let dir = dir.to_string_lossy();
+            return Ok(PathBuf::from(dir.split(':').next().unwrap_or_default()));
+        }
+        match self.working_directory.as_ref() {
+            Some(working_directory) => Ok(PathBuf::from(working_directory)),
+            None => default_state_dir(),
+        }
+    }
+
+    pub fn history(&self) -> std::io::Result<History> {
+        Ok(History::new(self.state_dir()?.join(HISTORY_FILE_NAME)))
+    }
+
+    /// An explicit `--config` or `--directory` takes precedence over the search order.
+    pub fn config_path(&self) -> std::io::Result<ConfigPath> {
+        let path = match (self.config.as_ref(), self.working_directory.as_ref()) {
+            (Some(config), _) => self.path_of(config)?,
+            (None, Some(working_directory)) => find_config_in_dir(working_directory),
+            (None, None) => return discover_config_path(),
+        };
+        Ok(ConfigPath {
+            path,
+            location: ConfigLocation::CommandLine,
+        })
+    }
 }
 
 #[derive(Subcommand, Clone, Debug)]
 pub enum Command {
+    /// Manage the config file.
+    Config {
+        #[clap(flatten)]
+        args: ConfigCommand,
+    },
+    /// Check the network status once and exit.
+    Check {
+        #[clap(flatten)]
+        args: OneshotCommand,
+    },
+    /// Check the network status and login if required, then exit.
+    Login {
+        #[clap(flatten)]
+        args: OneshotCommand,
+    },
+    /// Print the history of checks and logins with a summary.
+    History {
+        #[clap(flatten)]
+        args: HistoryCommand,
+    },
     /// Windows service mode.
     #[cfg(all(feature = "windows-service-mode", target_os = "windows"))]
     Service {
         #[clap(flatten)]
         args: ServiceCommand,
     },
+    /// Manage the systemd unit.
+    #[cfg(target_os = "linux")]
+    Systemd {
+        #[clap(flatten)]
+        args: SystemdCommand,
+    },
+    /// Manage the procd init script on OpenWrt.
+    #[cfg(target_os = "linux")]
+    Openwrt {
+        #[clap(flatten)]
+        args: OpenWrtCommand,
+    },
 }
 
-fn read_my_config(args: &Args) -> Result<LoginConfig, Box<dyn std::error::Error + Sync + Send>> {
-    let config_path = args.path_of(args.config.as_str())?;
-    let f = std::fs::File::open(config_path)?;
-    let config: LoginConfig = serde_yaml::from_reader(f)?;
-    Ok(config)
+/// Also returns the version the config was upgraded from, if it is an older one.
+fn read_my_config(
+    config_path: &ConfigPath,
+) -> Result<(LoginConfig, Option<u32>), Box<dyn std::error::Error + Sync + Send>> {
+    Ok(read_and_migrate_config_file(config_path.path.as_path())?)
 }
 
 fn init_log(
@@ -89,35 +179,18 @@ fn init_log(
     config: &LoginConfig,
 ) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
     let log_level = if args.verbose {
-        LevelFilter::Trace
+        Some(LevelFilter::Trace)
     } else {
-        LevelFilter::Info
+        args.log_level
     };
-    let log_path = args.path_of(args.config.as_str())?;
-    let rolling_pattern = log_path.as_path().to_string_lossy() + ".{}";
-    let file_policy = CompoundPolicy::new(
-        Box::new(SizeTrigger::new(
-            config
-                .log_policy
-                .size_limit
-                .unwrap_or(Byte::from_u64(3 * 1024 * 1024))
-                .as_u64(),
-        )),
-        Box::new(FixedWindowRoller::builder().base(1).build(
-            rolling_pattern.as_ref(),
-            config.log_policy.file_count.unwrap_or(2),
-        )?),
-    );
-    let file_log = RollingFileAppender::builder()
-        .encoder(Box::<PatternEncoder>::default())
-        .build(log_path, Box::new(file_policy))?;
-
-    let log_config = log4rs::Config::builder()
-        .appender(Appender::builder().build("file_log", Box::new(file_log)))
-        .build(Root::builder().appender("file_log").build(log_level))?;
-
-    let _ = log4rs::init_config(log_config)?;
-    Ok(())
+    let log_file = args
+        .log_file
+        .as_deref()
+        .or(config.logging.file.as_deref())
+        .unwrap_or(DEFAULT_LOG_FILE_NAME);
+    // Kept with the history rather than next to the executable, which may be read-only.
+    let log_path = args.state_dir()?.join(log_file);
+    logging::init_log(&config.logging, log_level, &log_path)
 }
 
 fn windows_error_dialog(#[allow(unused)] error: &str) {
@@ -149,6 +222,14 @@ fn windows_error_dialog(#[allow(unused)] error: &str) {
     }
 }
 
+fn run_app(app: AppMain) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+    #[cfg(target_os = "linux")]
+    if let Some(events) = app_systemd_events::AppSystemdEvents::from_env() {
+        return app.run(events);
+    }
+    app.run(DefaultAppEvents)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
     let args = match Args::try_parse() {
         Ok(args) => args,
@@ -159,14 +240,33 @@ fn main() -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
         }
     };
 
-    let my_config = match read_my_config(&args) {
+    let config_path = args.config_path()?;
+
+    // Config commands should work even if the config file is missing or broken.
+    if let Some(Command::Config { args: config_args }) = args.command.clone() {
+        if let Err(error) = handle_config_command(config_args, config_path) {
+            eprintln!("Error: {}", error.chain());
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::History { args: history_args }) = args.command.clone() {
+        if let Err(error) = handle_history_command(history_args, &args.history()?) {
+            eprintln!("Error: {}", error.chain());
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let (my_config, migrated_from) = match read_my_config(&config_path) {
         Ok(config) => config,
         Err(error) => {
             windows_error_dialog(&format!(
-                "Failed to read config: {}",
+                "Failed to read config {}: {}",
+                config_path,
                 error.as_ref().chain()
             ));
-            eprintln!("Failed to read config.");
+            eprintln!("Failed to read config {}.", config_path);
             return Err(error);
         }
     };
@@ -177,14 +277,41 @@ fn main() -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
         return Err(error);
     }
 
+    info!("Using config file {}", config_path);
+    if let Some(version) = migrated_from {
+        warn!(
+            "Config file is of version {}, upgraded in memory, run `config migrate` to update the file",
+            version
+        );
+    }
+    if my_config.credential.password().is_outdated() {
+        warn!("Password is encrypted in an older format, run `config rekey` to upgrade it");
+    }
+    let config_path = config_path.path;
+    let history = args.history()?;
     let run: Result<(), Box<dyn std::error::Error + Sync + Send>> = match args.command {
+        Some(Command::Check { args }) => {
+            std::process::exit(handle_oneshot_command(args, my_config, false, &history))
+        }
+        Some(Command::Login { args }) => {
+            std::process::exit(handle_oneshot_command(args, my_config, true, &history))
+        }
         #[cfg(all(feature = "windows-service-mode", target_os = "windows"))]
         Some(Command::Service { args }) => {
-            handle_service_command(args, my_config).map_err(|e| e.into())
+            handle_service_command(args, my_config, Some(config_path), history)
+                .map_err(|e| e.into())
+        }
+        #[cfg(target_os = "linux")]
+        Some(Command::Systemd { args }) => {
+            handle_systemd_command(args, config_path).map_err(|e| e.into())
+        }
+        #[cfg(target_os = "linux")]
+        Some(Command::Openwrt { args }) => {
+            handle_openwrt_command(args, config_path).map_err(|e| e.into())
         }
         _ => {
-            let app = AppMain::new(my_config);
-            app.run(DefaultAppEvents)
+            let app = AppMain::new(my_config, Some(config_path), history);
+            run_app(app)
         }
     };
     if let Err(error) = run {
@@ -0,0 +1,429 @@
+//! Counters of checks and logins for the Prometheus `/metrics` endpoint and the health check,
+//! fed with the same entries as the history.
+use chrono::{DateTime, Utc};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::history::{Attempt, HistoryEntry};
+
+const PREFIX: &str = "njupt_wifi_login";
+/// Upper bounds of the probe latency buckets in seconds.
+const LATENCY_BUCKETS: [f64; 9] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+/// How often the event loop reports that it is running while it waits for actions.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// No heartbeat for longer than this means the event loop is stuck, as every request
+/// has a timeout of 30 seconds and a password command one of 90 seconds.
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(180);
+/// Without regular checks, results only come with network changes,
+/// so the last one is trusted for longer.
+const MAX_RESULT_AGE_WITHOUT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Default)]
+struct MetricsState {
+    /// By network status, or `error` if the check failed.
+    checks: BTreeMap<&'static str, u64>,
+    logins: u64,
+    /// By attempt and error kind.
+    failures: BTreeMap<(Attempt, String), u64>,
+    latency_buckets: [u64; LATENCY_BUCKETS.len()],
+    latency_sum: f64,
+    latency_count: u64,
+    off_hours_until: Option<Instant>,
+    last_success: Option<DateTime<Utc>>,
+    /// When the last check or login finished, and whether the network was usable.
+    last_result: Option<(Instant, bool)>,
+    check_started_at: Option<Instant>,
+    check_interval: Duration,
+    last_heartbeat: Option<Instant>,
+}
+
+pub enum Health {
+    Healthy,
+    Unhealthy(String),
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    state: Mutex<MetricsState>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Called by the event loop between actions.
+    pub fn heartbeat(&self) {
+        self.state.lock().unwrap().last_heartbeat = Some(Instant::now());
+    }
+
+    /// `0` means regular checks are disabled.
+    pub fn set_check_interval(&self, check_interval: Duration) {
+        self.state.lock().unwrap().check_interval = check_interval;
+    }
+
+    pub fn set_off_hours_expiration(&self, expiration: Duration) {
+        self.state.lock().unwrap().off_hours_until =
+            (!expiration.is_zero()).then(|| Instant::now() + expiration);
+    }
+
+    pub fn check_started(&self) {
+        self.state.lock().unwrap().check_started_at = Some(Instant::now());
+    }
+
+    pub fn check_finished(&self) {
+        self.state.lock().unwrap().check_started_at = None;
+    }
+
+    pub fn observe(&self, entry: &HistoryEntry) {
+        let mut state = self.state.lock().unwrap();
+        let latency = entry.latency_ms as f64 / 1000.0;
+        match entry.attempt {
+            Attempt::Check => {
+                let status = match entry.status.as_deref() {
+                    Some("connected") => "connected",
+                    Some("authentication_njupt") => "authentication_njupt",
+                    Some("authentication_unknown") => "authentication_unknown",
+                    Some("disconnected") => "disconnected",
+                    _ => "error",
+                };
+                *state.checks.entry(status).or_default() += 1;
+                for (index, bound) in LATENCY_BUCKETS.iter().enumerate() {
+                    if latency <= *bound {
+                        state.latency_buckets[index] += 1;
+                    }
+                }
+                state.latency_sum += latency;
+                state.latency_count += 1;
+            }
+            Attempt::Login => state.logins += 1,
+            Attempt::Logout => {}
+        }
+        if let Some(error) = entry.error.as_ref() {
+            *state
+                .failures
+                .entry((entry.attempt, error.clone()))
+                .or_default() += 1;
+        }
+        let online = match entry.attempt {
+            Attempt::Check => entry.status.as_deref() == Some("connected"),
+            Attempt::Login => entry.error.is_none(),
+            Attempt::Logout => false,
+        };
+        if online {
+            state.last_success = Some(entry.time);
+        }
+        // A check which finds the portal is followed by a login.
+        if entry.attempt != Attempt::Logout
+            && entry.status.as_deref() != Some("authentication_njupt")
+        {
+            state.last_result = Some((Instant::now(), online));
+        }
+    }
+
+    /// Healthy if the event loop is running and not stuck, and the last check or login
+    /// found the network usable within twice the check interval, or a day without one.
+    pub fn health(&self) -> Health {
+        let state = self.state.lock().unwrap();
+        let Some(last_heartbeat) = state.last_heartbeat else {
+            return Health::Unhealthy("event loop is not running".to_string());
+        };
+        if last_heartbeat.elapsed() > HEARTBEAT_TIMEOUT {
+            return Health::Unhealthy(
+                if state.check_started_at.is_some() {
+                    "check is stuck"
+                } else {
+                    "event loop is not responding"
+                }
+                .to_string(),
+            );
+        }
+        let max_age = if state.check_interval.is_zero() {
+            MAX_RESULT_AGE_WITHOUT_INTERVAL
+        } else {
+            state.check_interval * 2 + Duration::from_secs(60)
+        };
+        match state.last_result {
+            None => Health::Unhealthy("no check has finished yet".to_string()),
+            Some((_, false)) => Health::Unhealthy("network is not usable".to_string()),
+            Some((finished_at, true)) if finished_at.elapsed() > max_age => {
+                Health::Unhealthy(format!(
+                    "last check finished {} seconds ago",
+                    finished_at.elapsed().as_secs()
+                ))
+            }
+            Some(_) => Health::Healthy,
+        }
+    }
+
+    /// In the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+        let header = |out: &mut String, name: &str, kind: &str, help: &str| {
+            let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+            let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+        };
+
+        header(
+            &mut out,
+            "checks_total",
+            "counter",
+            "Network status checks by result.",
+        );
+        for (status, count) in state.checks.iter() {
+            let _ = writeln!(
+                out,
+                "{}_checks_total{{status=\"{}\"}} {}",
+                PREFIX, status, count
+            );
+        }
+
+        header(&mut out, "logins_total", "counter", "Login attempts.");
+        let _ = writeln!(out, "{}_logins_total {}", PREFIX, state.logins);
+
+        header(
+            &mut out,
+            "failures_total",
+            "counter",
+            "Failed checks, logins and logouts by error kind.",
+        );
+        for ((attempt, kind), count) in state.failures.iter() {
+            let _ = writeln!(
+                out,
+                "{}_failures_total{{attempt=\"{}\",kind=\"{}\"}} {}",
+                PREFIX, attempt, kind, count
+            );
+        }
+
+        header(
+            &mut out,
+            "probe_duration_seconds",
+            "histogram",
+            "Latency of network status checks.",
+        );
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(state.latency_buckets.iter()) {
+            let _ = writeln!(
+                out,
+                "{}_probe_duration_seconds_bucket{{le=\"{}\"}} {}",
+                PREFIX, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_probe_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            PREFIX, state.latency_count
+        );
+        let _ = writeln!(
+            out,
+            "{}_probe_duration_seconds_sum {}",
+            PREFIX, state.latency_sum
+        );
+        let _ = writeln!(
+            out,
+            "{}_probe_duration_seconds_count {}",
+            PREFIX, state.latency_count
+        );
+
+        header(
+            &mut out,
+            "off_hours",
+            "gauge",
+            "1 if logins are paused until the off hours end.",
+        );
+        let off_hours = state
+            .off_hours_until
+            .is_some_and(|until| until > Instant::now());
+        let _ = writeln!(out, "{}_off_hours {}", PREFIX, off_hours as u8);
+
+        header(
+            &mut out,
+            "last_success_timestamp_seconds",
+            "gauge",
+            "Unix time when the network was last found usable, 0 if never.",
+        );
+        let _ = writeln!(
+            out,
+            "{}_last_success_timestamp_seconds {}",
+            PREFIX,
+            state.last_success.map_or(0, |time| time.timestamp())
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Trigger;
+
+    fn entry(
+        attempt: Attempt,
+        status: Option<&str>,
+        error: Option<&str>,
+        latency_ms: u64,
+    ) -> HistoryEntry {
+        HistoryEntry {
+            time: Utc::now(),
+            trigger: Trigger::Interval,
+            attempt,
+            status: status.map(str::to_string),
+            error: error.map(str::to_string),
+            latency_ms,
+            interface: None,
+        }
+    }
+
+    fn check(status: &str) -> HistoryEntry {
+        entry(Attempt::Check, Some(status), None, 100)
+    }
+
+    fn unhealthy_reason(metrics: &Metrics) -> Option<String> {
+        match metrics.health() {
+            Health::Healthy => None,
+            Health::Unhealthy(reason) => Some(reason),
+        }
+    }
+
+    fn ago(secs: u64) -> Instant {
+        Instant::now()
+            .checked_sub(Duration::from_secs(secs))
+            .unwrap()
+    }
+
+    #[test]
+    fn only_a_usable_network_is_healthy() {
+        let metrics = Metrics::new();
+        metrics.set_check_interval(Duration::from_secs(1200));
+        metrics.observe(&check("connected"));
+        assert_eq!(
+            unhealthy_reason(&metrics).as_deref(),
+            Some("event loop is not running")
+        );
+        metrics.heartbeat();
+        assert_eq!(unhealthy_reason(&metrics), None);
+
+        for status in ["disconnected", "authentication_unknown"] {
+            metrics.observe(&check(status));
+            assert_eq!(
+                unhealthy_reason(&metrics).as_deref(),
+                Some("network is not usable"),
+                "{}",
+                status
+            );
+        }
+        metrics.observe(&entry(
+            Attempt::Check,
+            None,
+            Some("http_request_failed"),
+            100,
+        ));
+        assert!(unhealthy_reason(&metrics).is_some());
+
+        // Finding the portal is not a result yet, the login is.
+        metrics.observe(&check("authentication_njupt"));
+        assert!(unhealthy_reason(&metrics).is_some());
+        metrics.observe(&entry(Attempt::Login, None, Some("off_hours"), 100));
+        assert!(unhealthy_reason(&metrics).is_some());
+        metrics.observe(&entry(Attempt::Login, None, None, 100));
+        assert_eq!(unhealthy_reason(&metrics), None);
+    }
+
+    #[test]
+    fn stale_and_stuck() {
+        let metrics = Metrics::new();
+        metrics.heartbeat();
+        assert_eq!(
+            unhealthy_reason(&metrics).as_deref(),
+            Some("no check has finished yet")
+        );
+
+        metrics.set_check_interval(Duration::from_secs(60));
+        metrics.state.lock().unwrap().last_result = Some((ago(200), true));
+        assert_eq!(
+            unhealthy_reason(&metrics).as_deref(),
+            Some("last check finished 200 seconds ago")
+        );
+        // Without regular checks, the result is trusted for a day.
+        metrics.set_check_interval(Duration::ZERO);
+        assert_eq!(unhealthy_reason(&metrics), None);
+        metrics.state.lock().unwrap().last_result = Some((ago(25 * 60 * 60), true));
+        assert!(unhealthy_reason(&metrics).is_some());
+
+        metrics.state.lock().unwrap().last_result = Some((Instant::now(), true));
+        metrics.state.lock().unwrap().last_heartbeat = Some(ago(200));
+        assert_eq!(
+            unhealthy_reason(&metrics).as_deref(),
+            Some("event loop is not responding")
+        );
+        metrics.check_started();
+        assert_eq!(
+            unhealthy_reason(&metrics).as_deref(),
+            Some("check is stuck")
+        );
+        metrics.check_finished();
+        metrics.heartbeat();
+        assert_eq!(unhealthy_reason(&metrics), None);
+    }
+
+    #[test]
+    fn render_histogram() {
+        let metrics = Metrics::new();
+        for latency_ms in [30, 200, 2000, 60000] {
+            metrics.observe(&entry(Attempt::Check, Some("connected"), None, latency_ms));
+        }
+        metrics.observe(&entry(
+            Attempt::Check,
+            None,
+            Some("http_request_failed"),
+            100,
+        ));
+        metrics.observe(&entry(Attempt::Login, None, Some("off_hours"), 100));
+        let out = metrics.render();
+        let value = |name: &str| -> f64 {
+            let line = out
+                .lines()
+                .find(|line| line.split(' ').next() == Some(name))
+                .unwrap_or_else(|| panic!("{} not found in\n{}", name, out));
+            line.rsplit(' ').next().unwrap().parse().unwrap()
+        };
+
+        let buckets: Vec<f64> = LATENCY_BUCKETS
+            .iter()
+            .map(|bound| {
+                value(&format!(
+                    "njupt_wifi_login_probe_duration_seconds_bucket{{le=\"{}\"}}",
+                    bound
+                ))
+            })
+            .collect();
+        assert_eq!(buckets, [1.0, 2.0, 3.0, 3.0, 3.0, 4.0, 4.0, 4.0, 4.0]);
+        assert!(buckets.windows(2).all(|pair| pair[0] <= pair[1]));
+        let count = value("njupt_wifi_login_probe_duration_seconds_count");
+        assert_eq!(count, 5.0);
+        assert_eq!(
+            value("njupt_wifi_login_probe_duration_seconds_bucket{le=\"+Inf\"}"),
+            count
+        );
+        assert_eq!(value("njupt_wifi_login_probe_duration_seconds_sum"), 62.33);
+
+        assert_eq!(
+            value("njupt_wifi_login_checks_total{status=\"connected\"}"),
+            4.0
+        );
+        assert_eq!(
+            value("njupt_wifi_login_checks_total{status=\"error\"}"),
+            1.0
+        );
+        assert_eq!(value("njupt_wifi_login_logins_total"), 1.0);
+        assert_eq!(
+            value("njupt_wifi_login_failures_total{attempt=\"login\",kind=\"off_hours\"}"),
+            1.0
+        );
+        assert!(value("njupt_wifi_login_last_success_timestamp_seconds") > 0.0);
+    }
+}
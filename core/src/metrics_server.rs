@@ -0,0 +1,277 @@
+//! A minimal HTTP/1.1 server for `/metrics` and `/healthz`, which is all that
+//! Prometheus and health checkers need, without pulling in a web framework.
+use log::*;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+use crate::metrics::{Health, Metrics};
+
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[must_use]
+pub struct MetricsServerHandle {
+    handle_accept: JoinHandle<()>,
+}
+
+impl MetricsServerHandle {
+    pub async fn register(
+        listen: SocketAddr,
+        metrics: Arc<Metrics>,
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let listener = TcpListener::bind(listen).await?;
+        if !listen.ip().is_loopback() {
+            warn!(
+                "Metrics are served on {}, which is reachable from other hosts",
+                listen
+            );
+        }
+        info!("Serving metrics on http://{}/metrics", listen);
+        Ok(Self::serve_listener(listener, metrics))
+    }
+
+    fn serve_listener(listener: TcpListener, metrics: Arc<Metrics>) -> Self {
+        let handle_accept = tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        warn!("Failed to accept metrics connection: {}", err);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+                let metrics = metrics.clone();
+                tokio::spawn(async move {
+                    let served =
+                        tokio::time::timeout(REQUEST_TIMEOUT, serve(stream, metrics.as_ref()))
+                            .await;
+                    match served {
+                        Ok(Ok(())) => {}
+                        Ok(Err(err)) => debug!("Metrics request from {} failed: {}", peer, err),
+                        Err(_) => debug!("Metrics request from {} timed out", peer),
+                    }
+                });
+            }
+        });
+        Self { handle_accept }
+    }
+
+    pub fn abort(&self) {
+        self.handle_accept.abort();
+    }
+
+    pub async fn join(self) {
+        let _ = self.handle_accept.await;
+    }
+}
+
+/// Read the request head, the body of a GET request is ignored.
+async fn read_request_head(stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buffer.windows(4).any(|window| window == b"\r\n\r\n") {
+        if buffer.len() > MAX_REQUEST_SIZE {
+            return Ok(None);
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+    Ok(Some(String::from_utf8_lossy(&buffer).into_owned()))
+}
+
+async fn serve(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    let Some(head) = read_request_head(&mut stream).await? else {
+        return respond(&mut stream, "400 Bad Request", TEXT, "bad request\n", true).await;
+    };
+    let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+    let (method, target) = (
+        request_line.next().unwrap_or_default(),
+        request_line.next().unwrap_or_default(),
+    );
+    let send_body = method != "HEAD";
+    if method != "GET" && method != "HEAD" {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            TEXT,
+            "method not allowed\n",
+            send_body,
+        )
+        .await;
+    }
+    let path = target.split('?').next().unwrap_or_default();
+    match path {
+        "/metrics" => {
+            let body = metrics.render();
+            respond(&mut stream, "200 OK", PROMETHEUS, &body, send_body).await
+        }
+        "/healthz" => match metrics.health() {
+            Health::Healthy => respond(&mut stream, "200 OK", TEXT, "ok\n", send_body).await,
+            Health::Unhealthy(reason) => {
+                let body = format!("unhealthy: {}\n", reason);
+                respond(
+                    &mut stream,
+                    "503 Service Unavailable",
+                    TEXT,
+                    &body,
+                    send_body,
+                )
+                .await
+            }
+        },
+        _ => respond(&mut stream, "404 Not Found", TEXT, "not found\n", send_body).await,
+    }
+}
+
+const TEXT: &str = "text/plain; charset=utf-8";
+const PROMETHEUS: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+    send_body: bool,
+) -> std::io::Result<()> {
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    if send_body {
+        response.push_str(body);
+    }
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        history::{HistoryEntry, Trigger},
+        login::NetworkStatus,
+    };
+
+    async fn start(metrics: Arc<Metrics>) -> (MetricsServerHandle, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        (MetricsServerHandle::serve_listener(listener, metrics), addr)
+    }
+
+    /// Send `request` as is, and return the whole response.
+    async fn send(addr: SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        // The server may respond and close before an oversized request is written.
+        let _ = stream.write_all(request).await;
+        let _ = stream.shutdown().await;
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response).await;
+        String::from_utf8(response).unwrap()
+    }
+
+    async fn get(addr: SocketAddr, path: &str) -> String {
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        send(addr, request.as_bytes()).await
+    }
+
+    fn status_line(response: &str) -> &str {
+        response.lines().next().unwrap_or_default()
+    }
+
+    fn body(response: &str) -> &str {
+        response.split_once("\r\n\r\n").unwrap().1
+    }
+
+    #[tokio::test]
+    async fn routes() {
+        let metrics = Arc::new(Metrics::new());
+        let (handle, addr) = start(metrics.clone()).await;
+
+        let response = get(addr, "/metrics?format=text").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+        assert!(response.contains(&format!("Content-Type: {}\r\n", PROMETHEUS)));
+        assert!(response.contains(&format!("Content-Length: {}\r\n", body(&response).len())));
+        assert_eq!(body(&response), metrics.render());
+
+        let response = get(addr, "/healthz").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 503 Service Unavailable");
+        assert_eq!(body(&response), "unhealthy: event loop is not running\n");
+        metrics.heartbeat();
+        metrics.observe(&HistoryEntry::check(
+            Trigger::Interval,
+            Ok(&NetworkStatus::Connected),
+            Duration::from_millis(100),
+            None,
+        ));
+        let response = get(addr, "/healthz").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+        assert_eq!(body(&response), "ok\n");
+
+        let response = get(addr, "/").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 404 Not Found");
+
+        // HEAD has the headers only.
+        let response = send(addr, b"HEAD /healthz HTTP/1.1\r\n\r\n").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+        assert!(response.contains("Content-Length: 3\r\n"));
+        assert_eq!(body(&response), "");
+
+        let response = send(addr, b"POST /metrics HTTP/1.1\r\nContent-Length: 0\r\n\r\n").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 405 Method Not Allowed");
+
+        handle.abort();
+        handle.join().await;
+    }
+
+    #[tokio::test]
+    async fn bad_requests() {
+        let metrics = Arc::new(Metrics::new());
+        let (handle, addr) = start(metrics).await;
+
+        let oversized = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_REQUEST_SIZE * 2));
+        let response = send(addr, oversized.as_bytes()).await;
+        // The rest of the request is left unread, so the response may be lost to a reset.
+        assert!(
+            response.is_empty() || status_line(&response) == "HTTP/1.1 400 Bad Request",
+            "{}",
+            response
+        );
+
+        // Closed before the end of the head.
+        let response = send(addr, b"GET /metrics HTTP/1.1\r\n").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+
+        for request in [
+            &b"\r\n\r\n"[..],
+            b"GARBAGE\r\n\r\n",
+            b"\xff\xfe /metrics\r\n\r\n",
+        ] {
+            let response = send(addr, request).await;
+            assert_eq!(
+                status_line(&response),
+                "HTTP/1.1 405 Method Not Allowed",
+                "{:?}",
+                request
+            );
+        }
+        let response = send(addr, b"GET\r\n\r\n").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 404 Not Found");
+
+        // The server still works.
+        let response = get(addr, "/metrics").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+
+        handle.abort();
+        handle.join().await;
+    }
+}
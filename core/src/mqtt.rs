@@ -0,0 +1,668 @@
+//! Publish the status to an MQTT broker as retained JSON with Home Assistant discovery,
+//! and accept `check`, `login` and `logout` on the command topic.
+use chrono::{DateTime, Utc};
+use log::*;
+use njupt_wifi_login_configuration::login_config::MqttConfig;
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Outgoing, Packet, QoS};
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::task::{AbortHandle, JoinHandle};
+
+use crate::{
+    app_events::AppEvents,
+    app_main::ActionInfo,
+    history::Trigger,
+    login::{get_password, NetworkStatus, WifiLoginError},
+};
+
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+struct MqttTopics {
+    status: String,
+    availability: String,
+    command: String,
+}
+
+impl MqttTopics {
+    fn new(prefix: &str) -> Self {
+        Self {
+            status: format!("{}/status", prefix),
+            availability: format!("{}/availability", prefix),
+            command: format!("{}/command", prefix),
+        }
+    }
+}
+
+/// The retained payload of the status topic.
+#[derive(Serialize, Debug, Default)]
+struct MqttStatus {
+    /// See [`NetworkStatus::kind`], `None` before the first check.
+    status: Option<&'static str>,
+    /// When the last login succeeded.
+    last_login: Option<DateTime<Utc>>,
+    /// See [`WifiLoginError::kind`], `None` if the last login succeeded.
+    last_login_error: Option<String>,
+    off_hours_until: Option<DateTime<Utc>>,
+    /// Logins are paused after a logout until a check or login is requested.
+    logged_out: bool,
+}
+
+/// Shared by the event loop, which republishes everything on every connection,
+/// and the app events, which publish the status as it changes.
+struct MqttPublisher {
+    client: AsyncClient,
+    topics: MqttTopics,
+    /// Node ID of the discovery topics, from the client ID.
+    node_id: String,
+    discovery_prefix: Option<String>,
+    status: Mutex<MqttStatus>,
+}
+
+impl MqttPublisher {
+    fn publish(&self, topic: &str, retain: bool, payload: impl Into<Vec<u8>>) {
+        // The request queue is only full while the broker is unreachable,
+        // everything is published again once it is connected.
+        if let Err(err) = self
+            .client
+            .try_publish(topic, QoS::AtLeastOnce, retain, payload)
+        {
+            debug!("Failed to publish to {}: {}", topic, err);
+        }
+    }
+
+    fn publish_status(&self) {
+        let payload = serde_json::to_vec(&*self.status.lock().unwrap()).unwrap();
+        self.publish(&self.topics.status, true, payload);
+    }
+
+    fn update_status(&self, update: impl FnOnce(&mut MqttStatus)) {
+        update(&mut self.status.lock().unwrap());
+        self.publish_status();
+    }
+
+    fn on_connected(&self) {
+        if let Err(err) = self
+            .client
+            .try_subscribe(&self.topics.command, QoS::AtLeastOnce)
+        {
+            warn!("Failed to subscribe to {}: {}", self.topics.command, err);
+        }
+        self.publish(&self.topics.availability, true, ONLINE);
+        self.publish_discovery();
+        self.publish_status();
+    }
+
+    fn publish_discovery(&self) {
+        let Some(discovery_prefix) = self.discovery_prefix.as_ref() else {
+            return;
+        };
+        let device = json!({
+            "identifiers": [self.node_id],
+            "name": "NJUPT WiFi Login",
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        });
+        let entities = [
+            (
+                "sensor",
+                "network_status",
+                json!({
+                    "name": "Network status",
+                    "icon": "mdi:wifi",
+                    "state_topic": self.topics.status,
+                    "value_template": "{{ value_json.status }}",
+                }),
+            ),
+            (
+                "binary_sensor",
+                "online",
+                json!({
+                    "name": "Online",
+                    "device_class": "connectivity",
+                    "state_topic": self.topics.status,
+                    "value_template":
+                        "{{ 'ON' if value_json.status == 'connected' else 'OFF' }}",
+                }),
+            ),
+            (
+                "sensor",
+                "last_login",
+                json!({
+                    "name": "Last login",
+                    "device_class": "timestamp",
+                    "state_topic": self.topics.status,
+                    "value_template": "{{ value_json.last_login }}",
+                }),
+            ),
+            (
+                "sensor",
+                "off_hours_until",
+                json!({
+                    "name": "Off hours until",
+                    "device_class": "timestamp",
+                    "state_topic": self.topics.status,
+                    "value_template": "{{ value_json.off_hours_until }}",
+                }),
+            ),
+            (
+                "button",
+                "check",
+                json!({
+                    "name": "Check and login",
+                    "icon": "mdi:refresh",
+                    "command_topic": self.topics.command,
+                    "payload_press": "check",
+                }),
+            ),
+            (
+                "button",
+                "logout",
+                json!({
+                    "name": "Logout",
+                    "icon": "mdi:logout",
+                    "command_topic": self.topics.command,
+                    "payload_press": "logout",
+                }),
+            ),
+        ];
+        for (component, object_id, mut payload) in entities {
+            let fields = payload.as_object_mut().unwrap();
+            fields.insert(
+                "unique_id".to_string(),
+                json!(format!("{}_{}", self.node_id, object_id)),
+            );
+            fields.insert("object_id".to_string(), json!(object_id));
+            fields.insert(
+                "availability_topic".to_string(),
+                json!(self.topics.availability),
+            );
+            fields.insert("device".to_string(), device.clone());
+            let topic = format!(
+                "{}/{}/{}/{}/config",
+                discovery_prefix, component, self.node_id, object_id
+            );
+            self.publish(&topic, true, payload.to_string());
+        }
+    }
+}
+
+/// Only characters allowed in the node ID of discovery topics.
+fn node_id(client_id: &str) -> String {
+    client_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn parse_command(payload: &[u8]) -> Option<ActionInfo> {
+    match String::from_utf8_lossy(payload).trim() {
+        "check" | "login" => Some(ActionInfo::ForceCheckAndLogin(Trigger::Mqtt)),
+        "logout" => Some(ActionInfo::Logout(Trigger::Mqtt)),
+        _ => None,
+    }
+}
+
+#[must_use]
+pub struct MqttHandle {
+    publisher: Arc<MqttPublisher>,
+    handle_event_loop: JoinHandle<()>,
+}
+
+impl MqttHandle {
+    pub async fn register(
+        config: &MqttConfig,
+        on_command: impl Fn(ActionInfo) + Send + 'static,
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let topics = MqttTopics::new(&config.topic_prefix);
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_last_will(LastWill::new(
+            &topics.availability,
+            OFFLINE,
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = config.username.as_ref() {
+            let password = match config.password.as_ref() {
+                Some(password) => get_password(password)
+                    .await
+                    .map_err(|e| format!("failed to get MQTT password: {}", e))?
+                    .expose_secret()
+                    .to_string(),
+                None => String::new(),
+            };
+            options.set_credentials(username, password);
+        }
+        let (client, event_loop) = AsyncClient::new(options, 32);
+        let publisher = Arc::new(MqttPublisher {
+            client,
+            topics,
+            node_id: node_id(&config.client_id),
+            discovery_prefix: config.discovery_prefix.clone(),
+            status: Mutex::new(MqttStatus::default()),
+        });
+        info!(
+            "Publishing status to mqtt://{}:{}/{}",
+            config.host, config.port, publisher.topics.status
+        );
+        let handle_event_loop = tokio::spawn(run_event_loop(
+            event_loop,
+            publisher.clone(),
+            Box::new(on_command),
+        ));
+        Ok(Self {
+            publisher,
+            handle_event_loop,
+        })
+    }
+
+    pub fn events(&self) -> AppMqttEvents {
+        AppMqttEvents {
+            publisher: self.publisher.clone(),
+        }
+    }
+
+    /// Mark the daemon offline and disconnect, instead of leaving it to the last will.
+    pub fn abort(&self) {
+        let publisher = &self.publisher;
+        publisher.publish(&publisher.topics.availability, true, OFFLINE);
+        let _ = publisher.client.try_disconnect();
+    }
+
+    pub async fn join(self) {
+        let abort_handle = self.handle_event_loop.abort_handle();
+        if tokio::time::timeout(DISCONNECT_TIMEOUT, self.handle_event_loop)
+            .await
+            .is_err()
+        {
+            // The broker is unreachable, there is nothing to disconnect from.
+            abort_handle.abort();
+        }
+    }
+}
+
+async fn run_event_loop(
+    mut event_loop: EventLoop,
+    publisher: Arc<MqttPublisher>,
+    on_command: Box<dyn Fn(ActionInfo) + Send>,
+) {
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                info!("Connected to MQTT broker");
+                publisher.on_connected();
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                if publish.topic != publisher.topics.command {
+                    continue;
+                }
+                // A retained command would be run again on every connection.
+                if publish.retain {
+                    warn!("Ignore retained MQTT command on {}", publish.topic);
+                    continue;
+                }
+                match parse_command(&publish.payload) {
+                    Some(action) => {
+                        info!("MQTT command: {:?}", action);
+                        on_command(action);
+                    }
+                    None => warn!(
+                        "Unknown MQTT command `{}`, expected check, login or logout",
+                        String::from_utf8_lossy(&publish.payload)
+                    ),
+                }
+            }
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+            Ok(_) => {}
+            Err(err) => {
+                warn!(
+                    "MQTT connection error, retry in {} seconds: {}",
+                    RECONNECT_DELAY.as_secs(),
+                    err
+                );
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Publish the retained status as the events change it.
+pub struct AppMqttEvents {
+    publisher: Arc<MqttPublisher>,
+}
+
+impl AppEvents for AppMqttEvents {
+    fn on_started(&self) {
+        // Do nothing.
+    }
+    fn on_stopping(&self) {
+        // Do nothing.
+    }
+    fn on_stopped(&self) {
+        // Do nothing.
+    }
+    fn on_network_status(&self, status: &NetworkStatus) {
+        self.publisher.update_status(|state| {
+            state.status = Some(status.kind());
+            if let NetworkStatus::Connected = status {
+                state.off_hours_until = None;
+                state.logged_out = false;
+            }
+        });
+    }
+    fn on_login_result(&self, result: Result<(), &WifiLoginError>) {
+        self.publisher.update_status(|state| match result {
+            Ok(()) => {
+                state.last_login = Some(Utc::now());
+                state.last_login_error = None;
+                state.logged_out = false;
+            }
+            Err(err) => state.last_login_error = Some(err.kind().to_string()),
+        });
+    }
+    fn on_logout(&self, result: Result<(), &WifiLoginError>) {
+        if result.is_ok() {
+            self.publisher
+                .update_status(|state| state.logged_out = true);
+        }
+    }
+    fn on_off_hours(&self, until: Option<DateTime<Utc>>) {
+        self.publisher
+            .update_status(|state| state.off_hours_until = until);
+    }
+    fn register_abort_handle(&self, _handle: AbortHandle) {
+        // Do nothing.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rumqttc::Publish;
+    use serde_json::Value;
+    use std::{collections::HashMap, net::TcpListener};
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Start an in-process broker on a free port.
+    fn start_broker() -> u16 {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = json!({
+            "id": 0,
+            "router": {
+                "max_connections": 10,
+                "max_outgoing_packet_count": 200,
+                "max_segment_size": 1048576,
+                "max_segment_count": 10,
+            },
+            "v4": {
+                "1": {
+                    "name": "v4-1",
+                    "listen": format!("127.0.0.1:{}", port),
+                    "next_connection_delay_ms": 1,
+                    "connections": {
+                        "connection_timeout_ms": 60000,
+                        "max_payload_size": 20480,
+                        "max_inflight_count": 100,
+                        "dynamic_filters": true,
+                    },
+                },
+            },
+        });
+        let mut broker = rumqttd::Broker::new(serde_json::from_value(config).unwrap());
+        std::thread::spawn(move || broker.start().unwrap());
+        // Wait for the listener.
+        for _ in 0..100 {
+            if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                return port;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("the broker is not listening on {}", port);
+    }
+
+    fn mqtt_config(port: u16, client_id: &str, topic_prefix: &str) -> MqttConfig {
+        serde_json::from_value(json!({
+            "host": "127.0.0.1",
+            "port": port,
+            "client_id": client_id,
+            "topic_prefix": topic_prefix,
+            "discovery_prefix": "homeassistant",
+        }))
+        .unwrap()
+    }
+
+    /// Another client of the broker, e.g. Home Assistant.
+    struct Observer {
+        client: AsyncClient,
+        event_loop: EventLoop,
+        /// The last message of each topic.
+        received: HashMap<String, Publish>,
+    }
+
+    impl Observer {
+        async fn connect(port: u16, client_id: &str, topics: &[&str]) -> Self {
+            let (client, event_loop) =
+                AsyncClient::new(MqttOptions::new(client_id, "127.0.0.1", port), 32);
+            for topic in topics {
+                client.subscribe(*topic, QoS::AtLeastOnce).await.unwrap();
+            }
+            Self {
+                client,
+                event_loop,
+                received: HashMap::new(),
+            }
+        }
+
+        /// Poll until `condition` holds for the received messages.
+        async fn wait_for(&mut self, condition: impl Fn(&HashMap<String, Publish>) -> bool) {
+            tokio::time::timeout(TIMEOUT, async {
+                while !condition(&self.received) {
+                    if let Event::Incoming(Packet::Publish(publish)) =
+                        self.event_loop.poll().await.unwrap()
+                    {
+                        self.received.insert(publish.topic.clone(), publish);
+                    }
+                }
+            })
+            .await
+            .expect("the messages are received");
+        }
+
+        /// Keep the connection going, e.g. while a publish is sent.
+        async fn poll_for(&mut self, duration: Duration) {
+            let _ = tokio::time::timeout(duration, async {
+                loop {
+                    if let Event::Incoming(Packet::Publish(publish)) =
+                        self.event_loop.poll().await.unwrap()
+                    {
+                        self.received.insert(publish.topic.clone(), publish);
+                    }
+                }
+            })
+            .await;
+        }
+    }
+
+    fn payload(received: &HashMap<String, Publish>, topic: &str) -> Option<String> {
+        received
+            .get(topic)
+            .map(|publish| String::from_utf8_lossy(&publish.payload).to_string())
+    }
+
+    fn json_payload(received: &HashMap<String, Publish>, topic: &str) -> Value {
+        serde_json::from_str(&payload(received, topic).unwrap()).unwrap()
+    }
+
+    async fn next_action(receiver: &mut UnboundedReceiver<ActionInfo>) -> ActionInfo {
+        tokio::time::timeout(TIMEOUT, receiver.recv())
+            .await
+            .expect("the command is received")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn broker() {
+        let port = start_broker();
+        let mut observer = Observer::connect(
+            port,
+            "observer",
+            &[
+                "njupt_test/status",
+                "njupt_test/availability",
+                "homeassistant/#",
+            ],
+        )
+        .await;
+        // Only a command sent while connected is run, not one left on the broker.
+        observer
+            .client
+            .publish("njupt_test/command", QoS::AtLeastOnce, true, "logout")
+            .await
+            .unwrap();
+        observer.poll_for(Duration::from_millis(200)).await;
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let handle = MqttHandle::register(
+            &mqtt_config(port, "test.client", "njupt_test"),
+            move |action| {
+                let _ = sender.send(action);
+            },
+        )
+        .await
+        .unwrap();
+        let events = handle.events();
+        events.on_network_status(&NetworkStatus::Connected);
+        events.on_login_result(Ok(()));
+
+        observer
+            .wait_for(|received| {
+                payload(received, "njupt_test/availability").as_deref() == Some(ONLINE)
+                    && payload(received, "njupt_test/status")
+                        .is_some_and(|status| status.contains("last_login\":\""))
+                    && received
+                        .keys()
+                        .filter(|topic| topic.starts_with("homeassistant/"))
+                        .count()
+                        == 6
+            })
+            .await;
+        let status = json_payload(&observer.received, "njupt_test/status");
+        assert_eq!(status["status"], "connected");
+        assert_eq!(status["last_login_error"], Value::Null);
+        assert_eq!(status["logged_out"], false);
+        // Clients connecting later get the retained messages.
+        let mut late = Observer::connect(
+            port,
+            "late",
+            &["njupt_test/status", "njupt_test/availability"],
+        )
+        .await;
+        late.wait_for(|received| received.len() == 2).await;
+        for (topic, publish) in &late.received {
+            assert!(publish.retain, "{}", topic);
+            assert_eq!(publish.payload, observer.received[topic].payload);
+        }
+
+        let button = json_payload(
+            &observer.received,
+            "homeassistant/button/test_client/check/config",
+        );
+        assert_eq!(button["command_topic"], "njupt_test/command");
+        assert_eq!(button["payload_press"], "check");
+        assert_eq!(button["availability_topic"], "njupt_test/availability");
+        assert_eq!(button["unique_id"], "test_client_check");
+        assert_eq!(button["device"]["identifiers"], json!(["test_client"]));
+        let online = json_payload(
+            &observer.received,
+            "homeassistant/binary_sensor/test_client/online/config",
+        );
+        assert_eq!(online["state_topic"], "njupt_test/status");
+        assert_eq!(online["device_class"], "connectivity");
+        for topic in [
+            "homeassistant/sensor/test_client/network_status/config",
+            "homeassistant/sensor/test_client/last_login/config",
+            "homeassistant/sensor/test_client/off_hours_until/config",
+            "homeassistant/button/test_client/logout/config",
+        ] {
+            assert!(observer.received.contains_key(topic), "{}", topic);
+        }
+
+        for command in ["check", " login\n", "unknown", "logout"] {
+            observer
+                .client
+                .publish("njupt_test/command", QoS::AtLeastOnce, false, command)
+                .await
+                .unwrap();
+        }
+        observer.poll_for(Duration::from_millis(200)).await;
+        assert!(matches!(
+            next_action(&mut receiver).await,
+            ActionInfo::ForceCheckAndLogin(Trigger::Mqtt)
+        ));
+        assert!(matches!(
+            next_action(&mut receiver).await,
+            ActionInfo::ForceCheckAndLogin(Trigger::Mqtt)
+        ));
+        assert!(matches!(
+            next_action(&mut receiver).await,
+            ActionInfo::Logout(Trigger::Mqtt)
+        ));
+        assert!(receiver.try_recv().is_err());
+
+        events.on_logout(Ok(()));
+        observer
+            .wait_for(|received| {
+                payload(received, "njupt_test/status")
+                    .is_some_and(|status| status.contains("\"logged_out\":true"))
+            })
+            .await;
+
+        // A clean disconnect marks the daemon offline.
+        handle.abort();
+        handle.join().await;
+        observer
+            .wait_for(|received| {
+                payload(received, "njupt_test/availability").as_deref() == Some(OFFLINE)
+            })
+            .await;
+
+        // Otherwise the broker publishes the last will.
+        let handle = MqttHandle::register(&mqtt_config(port, "will", "njupt_will"), |_| {})
+            .await
+            .unwrap();
+        observer
+            .client
+            .subscribe("njupt_will/availability", QoS::AtLeastOnce)
+            .await
+            .unwrap();
+        observer
+            .wait_for(|received| {
+                payload(received, "njupt_will/availability").as_deref() == Some(ONLINE)
+            })
+            .await;
+        handle.handle_event_loop.abort();
+        observer
+            .wait_for(|received| {
+                payload(received, "njupt_will/availability").as_deref() == Some(OFFLINE)
+            })
+            .await;
+    }
+}
@@ -0,0 +1,269 @@
+#![cfg(target_os = "linux")]
+//! Notifications through `org.freedesktop.Notifications` on the session bus,
+//! which is provided by every desktop environment and standalone daemons such as dunst.
+use display_error_chain::ErrorChainExt;
+use log::*;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Mutex;
+use zbus::{zvariant::Value, Connection};
+
+use super::{Category, Notification, Notifier};
+
+const DESTINATION: &str = "org.freedesktop.Notifications";
+const PATH: &str = "/org/freedesktop/Notifications";
+const INTERFACE: &str = "org.freedesktop.Notifications";
+const APP_NAME: &str = "NJUPT Wi-Fi Login";
+const APP_ICON: &str = "network-wireless";
+
+#[derive(Default)]
+struct State {
+    /// Connected on the first notification, and again after a failure.
+    connection: Option<Connection>,
+    /// The last notification of each category is replaced instead of stacked up.
+    replaces_id: HashMap<Category, u32>,
+}
+
+pub struct FreedesktopNotifier {
+    state: Arc<Mutex<State>>,
+}
+
+impl FreedesktopNotifier {
+    /// Connect to the session bus of `DBUS_SESSION_BUS_ADDRESS` on the first notification.
+    pub fn new() -> Self {
+        Self::with_connection(None)
+    }
+
+    /// Use `connection` until it fails, e.g. a private bus.
+    pub fn with_connection(connection: Option<Connection>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                connection,
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+impl State {
+    async fn send(&mut self, notification: &Notification) -> zbus::Result<()> {
+        let connection = match self.connection.as_ref() {
+            Some(connection) => connection.clone(),
+            None => {
+                let connection = Connection::session().await?;
+                self.connection.replace(connection.clone());
+                connection
+            }
+        };
+        let replaces_id = self
+            .replaces_id
+            .get(&notification.category)
+            .copied()
+            .unwrap_or(0);
+        // Low, normal and critical are 0, 1 and 2.
+        let urgency: u8 = if notification.urgent { 2 } else { 1 };
+        let hints = HashMap::from([("urgency", Value::from(urgency))]);
+        let reply = connection
+            .call_method(
+                Some(DESTINATION),
+                PATH,
+                Some(INTERFACE),
+                "Notify",
+                &(
+                    APP_NAME,
+                    replaces_id,
+                    APP_ICON,
+                    notification.summary.as_str(),
+                    notification.body.as_str(),
+                    Vec::<&str>::new(),
+                    hints,
+                    // Expire as the server sees fit.
+                    -1i32,
+                ),
+            )
+            .await?;
+        let id: u32 = reply.body().deserialize()?;
+        self.replaces_id.insert(notification.category, id);
+        Ok(())
+    }
+}
+
+impl Notifier for FreedesktopNotifier {
+    fn notify(&self, notification: Notification) {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let mut state = state.lock().await;
+            if let Err(err) = state.send(&notification).await {
+                warn!(
+                    "Failed to show the {} notification: {}",
+                    notification.category,
+                    err.chain()
+                );
+                state.connection = None;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        time::Duration,
+    };
+    use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+    use zbus::zvariant::OwnedValue;
+
+    #[derive(Debug, PartialEq)]
+    struct Received {
+        app_name: String,
+        replaces_id: u32,
+        summary: String,
+        body: String,
+        urgency: Option<u8>,
+    }
+
+    /// Stand-in notification server, which records the notifications.
+    struct Server {
+        next_id: std::sync::Mutex<u32>,
+        sender: UnboundedSender<Received>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl Server {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            app_name: String,
+            replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            body: String,
+            _actions: Vec<String>,
+            hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let urgency = hints
+                .get("urgency")
+                .and_then(|urgency| u8::try_from(urgency).ok());
+            let _ = self.sender.send(Received {
+                app_name,
+                replaces_id,
+                summary,
+                body,
+                urgency,
+            });
+            // Replaced notifications keep their ID.
+            if replaces_id != 0 {
+                return replaces_id;
+            }
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        }
+    }
+
+    struct SessionBus(Child);
+
+    impl Drop for SessionBus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    /// Start a private session bus, or `None` if `dbus-daemon` is not installed.
+    fn start_session_bus() -> Option<(SessionBus, String)> {
+        let mut child = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        Some((SessionBus(child), address.trim().to_string()))
+    }
+
+    fn notification(category: Category, summary: &str, urgent: bool) -> Notification {
+        Notification {
+            category,
+            summary: summary.to_string(),
+            body: format!("body of {}", summary),
+            urgent,
+        }
+    }
+
+    async fn next(receiver: &mut UnboundedReceiver<Received>) -> Received {
+        tokio::time::timeout(Duration::from_secs(10), receiver.recv())
+            .await
+            .expect("the notification is sent")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn notify_through_the_session_bus() {
+        let Some((_bus, address)) = start_session_bus() else {
+            eprintln!("dbus-daemon is not found, skip the test");
+            return;
+        };
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let _server = zbus::connection::Builder::address(address.as_str())
+            .unwrap()
+            .name(DESTINATION)
+            .unwrap()
+            .serve_at(
+                PATH,
+                Server {
+                    next_id: Default::default(),
+                    sender,
+                },
+            )
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let connection = zbus::connection::Builder::address(address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let notifier = FreedesktopNotifier::with_connection(Some(connection));
+
+        notifier.notify(notification(Category::LoggedIn, "Logged in", false));
+        assert_eq!(
+            next(&mut receiver).await,
+            Received {
+                app_name: APP_NAME.to_string(),
+                replaces_id: 0,
+                summary: "Logged in".to_string(),
+                body: "body of Logged in".to_string(),
+                urgency: Some(1),
+            }
+        );
+
+        // Each category replaces its own previous notification.
+        notifier.notify(notification(
+            Category::CredentialsRejected,
+            "Rejected",
+            true,
+        ));
+        let received = next(&mut receiver).await;
+        assert_eq!((received.replaces_id, received.urgency), (0, Some(2)));
+
+        notifier.notify(notification(Category::LoggedIn, "Logged in again", false));
+        let received = next(&mut receiver).await;
+        assert_eq!(received.replaces_id, 1);
+        assert_eq!(received.summary, "Logged in again");
+
+        notifier.notify(notification(
+            Category::CredentialsRejected,
+            "Rejected",
+            true,
+        ));
+        assert_eq!(next(&mut receiver).await.replaces_id, 2);
+    }
+}
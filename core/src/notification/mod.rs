@@ -0,0 +1,280 @@
+//! Desktop notifications of login events, see the `notifications` section of the config.
+pub mod freedesktop;
+pub mod windows_toast;
+
+use chrono::{DateTime, Local, Utc};
+use log::*;
+use njupt_wifi_login_configuration::login_config::{NotificationRule, NotificationsConfig};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::task::AbortHandle;
+
+use crate::{
+    app_events::AppEvents,
+    login::{NetworkStatus, WifiLoginError},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    LoggedIn,
+    OffHours,
+    CredentialsRejected,
+    UnknownPortal,
+}
+
+impl Category {
+    fn rule(self, config: &NotificationsConfig) -> NotificationRule {
+        match self {
+            Category::LoggedIn => config.logged_in,
+            Category::OffHours => config.off_hours,
+            Category::CredentialsRejected => config.credentials_rejected,
+            Category::UnknownPortal => config.unknown_portal,
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Category::LoggedIn => "logged_in",
+            Category::OffHours => "off_hours",
+            Category::CredentialsRejected => "credentials_rejected",
+            Category::UnknownPortal => "unknown_portal",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub category: Category,
+    pub summary: String,
+    pub body: String,
+    /// Needs the attention of the user, e.g. the password is wrong.
+    pub urgent: bool,
+}
+
+pub trait Notifier: Send + Sync {
+    /// Show the notification without waiting for it, failures are only logged.
+    fn notify(&self, notification: Notification);
+}
+
+fn platform_notifier() -> Option<Box<dyn Notifier>> {
+    #[cfg(target_os = "linux")]
+    return Some(Box::new(freedesktop::FreedesktopNotifier::new()));
+    #[cfg(target_os = "windows")]
+    return Some(Box::new(windows_toast::ToastNotifier));
+    #[allow(unreachable_code)]
+    None
+}
+
+/// Turn login events into desktop notifications,
+/// each category at most once in its `min_interval`.
+pub struct AppNotificationEvents {
+    config_getter: Box<dyn Fn() -> Option<NotificationsConfig> + Send + Sync>,
+    notifier: Option<Box<dyn Notifier>>,
+    last_sent: Mutex<HashMap<Category, Instant>>,
+}
+
+impl AppNotificationEvents {
+    /// The config is read on every event, so that reloading it takes effect.
+    pub fn new(
+        config_getter: impl Fn() -> Option<NotificationsConfig> + Send + Sync + 'static,
+    ) -> Self {
+        Self::with_notifier(config_getter, platform_notifier())
+    }
+
+    /// `None` if the platform has no notifications.
+    pub fn with_notifier(
+        config_getter: impl Fn() -> Option<NotificationsConfig> + Send + Sync + 'static,
+        notifier: Option<Box<dyn Notifier>>,
+    ) -> Self {
+        Self {
+            config_getter: Box::new(config_getter),
+            notifier,
+            last_sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn notify(&self, category: Category, summary: &str, body: String, urgent: bool) {
+        let Some(config) = (self.config_getter)() else {
+            return;
+        };
+        let rule = category.rule(&config);
+        if !rule.enabled {
+            return;
+        }
+        {
+            let mut last_sent = self.last_sent.lock().unwrap();
+            let now = Instant::now();
+            if let Some(sent_at) = last_sent.get(&category) {
+                if now.duration_since(*sent_at) < Duration::from_secs(rule.min_interval) {
+                    debug!("Skip the {} notification, sent recently", category);
+                    return;
+                }
+            }
+            last_sent.insert(category, now);
+        }
+        let Some(notifier) = self.notifier.as_ref() else {
+            debug!("Desktop notifications are not supported on this platform");
+            return;
+        };
+        notifier.notify(Notification {
+            category,
+            summary: summary.to_string(),
+            body,
+            urgent,
+        });
+    }
+}
+
+impl AppEvents for AppNotificationEvents {
+    fn on_started(&self) {
+        // Do nothing.
+    }
+    fn on_stopping(&self) {
+        // Do nothing.
+    }
+    fn on_stopped(&self) {
+        // Do nothing.
+    }
+    fn on_network_status(&self, status: &NetworkStatus) {
+        if let NetworkStatus::AuthenticationUnknown = status {
+            self.notify(
+                Category::UnknownPortal,
+                "Unknown captive portal",
+                "The network requires a login, but not to the portal of NJUPT.".to_string(),
+                false,
+            );
+        }
+    }
+    fn on_login_result(&self, result: Result<(), &WifiLoginError>) {
+        match result {
+            Ok(()) => self.notify(
+                Category::LoggedIn,
+                "Logged in",
+                "Connected to the campus network.".to_string(),
+                false,
+            ),
+            Err(WifiLoginError::ServerRejected(message)) => self.notify(
+                Category::CredentialsRejected,
+                "Login rejected",
+                format!("The authentication server rejected the login: {}", message),
+                true,
+            ),
+            Err(_) => {}
+        }
+    }
+    fn on_off_hours(&self, until: Option<DateTime<Utc>>) {
+        let body = match until {
+            Some(until) => format!(
+                "Logins are refused until {}.",
+                until.with_timezone(&Local).format("%H:%M")
+            ),
+            None => "Logins are refused in off hours.".to_string(),
+        };
+        self.notify(Category::OffHours, "Off hours", body, false);
+    }
+    fn register_abort_handle(&self, _handle: AbortHandle) {
+        // Do nothing.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    struct Recorder(Arc<Mutex<Vec<Notification>>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, notification: Notification) {
+            self.0.lock().unwrap().push(notification);
+        }
+    }
+
+    fn rule(enabled: bool, min_interval: u64) -> NotificationRule {
+        NotificationRule {
+            enabled,
+            min_interval,
+        }
+    }
+
+    fn new_events(
+        config: Option<NotificationsConfig>,
+    ) -> (AppNotificationEvents, Arc<Mutex<Vec<Notification>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let events = AppNotificationEvents::with_notifier(
+            move || config.clone(),
+            Some(Box::new(Recorder(sent.clone()))),
+        );
+        (events, sent)
+    }
+
+    fn categories(sent: &Mutex<Vec<Notification>>) -> Vec<Category> {
+        sent.lock()
+            .unwrap()
+            .iter()
+            .map(|notification| notification.category)
+            .collect()
+    }
+
+    #[test]
+    fn min_interval() {
+        let (events, sent) = new_events(Some(NotificationsConfig {
+            logged_in: rule(true, 3600),
+            off_hours: rule(false, 0),
+            credentials_rejected: rule(true, 0),
+            unknown_portal: rule(true, 3600),
+        }));
+        let rejected = WifiLoginError::ServerRejected("wrong password".to_string());
+        for _ in 0..2 {
+            events.on_login_result(Ok(()));
+            events.on_login_result(Err(&rejected));
+            events.on_off_hours(None);
+            events.on_network_status(&NetworkStatus::AuthenticationUnknown);
+        }
+        assert_eq!(
+            categories(&sent),
+            [
+                Category::LoggedIn,
+                Category::CredentialsRejected,
+                Category::UnknownPortal,
+                Category::CredentialsRejected,
+            ]
+        );
+        {
+            let sent = sent.lock().unwrap();
+            assert!(sent[1].urgent && !sent[0].urgent);
+            assert_eq!(
+                sent[1].body,
+                "The authentication server rejected the login: wrong password"
+            );
+        }
+
+        // Once the interval has passed, the category is sent again.
+        let long_ago = Instant::now()
+            .checked_sub(Duration::from_secs(3601))
+            .unwrap();
+        events
+            .last_sent
+            .lock()
+            .unwrap()
+            .insert(Category::LoggedIn, long_ago);
+        events.on_login_result(Ok(()));
+        events.on_network_status(&NetworkStatus::AuthenticationUnknown);
+        assert_eq!(categories(&sent).len(), 5);
+        assert_eq!(categories(&sent)[4], Category::LoggedIn);
+    }
+
+    #[test]
+    fn disabled_without_config() {
+        let (events, sent) = new_events(None);
+        events.on_login_result(Ok(()));
+        events.on_off_hours(None);
+        assert!(sent.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+#![cfg(target_os = "windows")]
+//! Toast notifications through the Windows Runtime.
+use display_error_chain::ErrorChainExt;
+use log::*;
+use windows::{
+    core::HSTRING,
+    Data::Xml::Dom::XmlDocument,
+    UI::Notifications::{ToastNotification, ToastNotificationManager},
+};
+
+use super::{Notification, Notifier};
+
+/// Toasts can only be shown under the ID of an app with a Start menu shortcut,
+/// so the one of PowerShell is used, which is present on every installation.
+const APP_ID: &str =
+    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+pub struct ToastNotifier;
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn show(notification: &Notification) -> windows::core::Result<()> {
+    let scenario = if notification.urgent {
+        r#" scenario="reminder""#
+    } else {
+        ""
+    };
+    let xml = format!(
+        r#"<toast{}><visual><binding template="ToastGeneric"><text>{}</text><text>{}</text></binding></visual></toast>"#,
+        scenario,
+        escape_xml(&notification.summary),
+        escape_xml(&notification.body)
+    );
+    let document = XmlDocument::new()?;
+    document.LoadXml(&HSTRING::from(xml))?;
+    let toast = ToastNotification::CreateToastNotification(&document)?;
+    // The last toast of each category is replaced instead of stacked up.
+    toast.SetTag(&HSTRING::from(notification.category.to_string()))?;
+    ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(APP_ID))?.Show(&toast)
+}
+
+impl Notifier for ToastNotifier {
+    fn notify(&self, notification: Notification) {
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = show(&notification) {
+                warn!(
+                    "Failed to show the {} notification: {}",
+                    notification.category,
+                    err.chain()
+                );
+            }
+        });
+    }
+}
@@ -26,6 +26,12 @@ impl OffHoursCache {
         self.cached_off_hours = None;
     }
 
+    /// When the cached off hours end, `None` if not cached or already ended.
+    pub fn until(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.cached_off_hours
+            .filter(|off_hours| *off_hours > chrono::Utc::now())
+    }
+
     pub fn expiration(&self) -> std::time::Duration {
         if let Some(off_hours) = self.cached_off_hours {
             let now: chrono::DateTime<chrono::Utc> = chrono::Utc::now();
@@ -0,0 +1,259 @@
+use clap::Args;
+use display_error_chain::ErrorChainExt;
+use njupt_wifi_login_configuration::login_config::LoginConfig;
+use serde::Serialize;
+
+use crate::history::{History, HistoryEntry, Trigger};
+use crate::login::{self, get_network_status, send_login_request, NetworkStatus, WifiLoginError};
+
+#[derive(Args, Clone, Debug)]
+pub struct OneshotCommand {
+    /// Print the result as JSON.
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+/// Result of a one-shot command, which is also reflected by the exit code.
+/// Code 2 is skipped, as it is used by clap for usage errors.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OneshotResult {
+    /// The network is available without login.
+    Connected,
+    Error,
+    /// Login succeeded.
+    LoggedIn,
+    /// The NJUPT portal is detected, but no login is performed.
+    AuthenticationRequired,
+    OffHours,
+    /// The authentication server rejected the credential.
+    Rejected,
+    /// A captive portal other than NJUPT is detected.
+    NotOnCampus,
+    Disconnected,
+}
+
+impl OneshotResult {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            OneshotResult::Connected => 0,
+            OneshotResult::Error => 1,
+            OneshotResult::LoggedIn => 3,
+            OneshotResult::AuthenticationRequired => 4,
+            OneshotResult::OffHours => 5,
+            OneshotResult::Rejected => 6,
+            OneshotResult::NotOnCampus => 7,
+            OneshotResult::Disconnected => 8,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            OneshotResult::Connected => "Connected",
+            OneshotResult::Error => "Error",
+            OneshotResult::LoggedIn => "Logged in",
+            OneshotResult::AuthenticationRequired => "Authentication required",
+            OneshotResult::OffHours => "Off hours",
+            OneshotResult::Rejected => "Rejected",
+            OneshotResult::NotOnCampus => "Not on campus",
+            OneshotResult::Disconnected => "Disconnected",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct OneshotReport {
+    result: OneshotResult,
+    exit_code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl OneshotReport {
+    fn new(result: OneshotResult) -> Self {
+        Self {
+            result,
+            exit_code: result.exit_code(),
+            user_ip: None,
+            message: None,
+        }
+    }
+
+    fn with_message(mut self, message: String) -> Self {
+        self.message = Some(message);
+        self
+    }
+
+    fn format(&self, json: bool) -> String {
+        if json {
+            return serde_json::to_string(self).unwrap();
+        }
+        let mut line = self.result.description().to_string();
+        if let Some(user_ip) = self.user_ip.as_ref() {
+            line.push_str(&format!(" (user ip: {})", user_ip));
+        }
+        if let Some(message) = self.message.as_ref() {
+            line.push_str(&format!(": {}", message));
+        }
+        line
+    }
+}
+
+fn report_for_login_error(err: WifiLoginError) -> OneshotReport {
+    match err {
+        WifiLoginError::OffHours() => OneshotReport::new(OneshotResult::OffHours),
+        WifiLoginError::ServerRejected(msg) => {
+            OneshotReport::new(OneshotResult::Rejected).with_message(msg)
+        }
+        err => OneshotReport::new(OneshotResult::Error).with_message(err.chain().to_string()),
+    }
+}
+
+async fn check_and_maybe_login(
+    config: &LoginConfig,
+    login: bool,
+    history: &History,
+) -> OneshotReport {
+    let dns_resolver = login::new_dns_resolver(config);
+    let interface = config.interface.as_deref();
+    let started_at = std::time::Instant::now();
+    let network_status = get_network_status(interface, dns_resolver.clone()).await;
+    history.record(&HistoryEntry::check(
+        Trigger::Command,
+        network_status.as_ref(),
+        started_at.elapsed(),
+        interface,
+    ));
+    let network_status = match network_status {
+        Ok(network_status) => network_status,
+        Err(err) => return report_for_login_error(err),
+    };
+    match network_status {
+        NetworkStatus::Connected => OneshotReport::new(OneshotResult::Connected),
+        NetworkStatus::AuthenticationUnknown => OneshotReport::new(OneshotResult::NotOnCampus),
+        NetworkStatus::Disconnected => OneshotReport::new(OneshotResult::Disconnected),
+        NetworkStatus::AuthenticationNJUPT(ap_info) => {
+            let mut report = if login {
+                let started_at = std::time::Instant::now();
+                let result =
+                    send_login_request(interface, dns_resolver, &config.credential, &ap_info).await;
+                history.record(&HistoryEntry::login(
+                    Trigger::Command,
+                    result.as_ref().map(|_| ()),
+                    started_at.elapsed(),
+                    interface,
+                ));
+                match result {
+                    Ok(()) => OneshotReport::new(OneshotResult::LoggedIn),
+                    Err(err) => report_for_login_error(err),
+                }
+            } else {
+                OneshotReport::new(OneshotResult::AuthenticationRequired)
+            };
+            report.user_ip = Some(ap_info.user_ip);
+            report
+        }
+    }
+}
+
+/// Run a single check (and login if `login` is set), print the result and return the exit code.
+pub fn handle_oneshot_command(
+    command: OneshotCommand,
+    config: LoginConfig,
+    login: bool,
+    history: &History,
+) -> i32 {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let report = rt.block_on(check_and_maybe_login(&config, login, history));
+    log::info!("One-shot result: {:?}", report);
+    println!("{}", report.format(command.json));
+    report.exit_code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn exit_codes() {
+        let codes = [
+            (OneshotResult::Connected, 0),
+            (OneshotResult::Error, 1),
+            (OneshotResult::LoggedIn, 3),
+            (OneshotResult::AuthenticationRequired, 4),
+            (OneshotResult::OffHours, 5),
+            (OneshotResult::Rejected, 6),
+            (OneshotResult::NotOnCampus, 7),
+            (OneshotResult::Disconnected, 8),
+        ];
+        for (result, code) in codes {
+            assert_eq!(result.exit_code(), code, "{:?}", result);
+            assert_eq!(OneshotReport::new(result).exit_code, code);
+        }
+    }
+
+    #[test]
+    fn login_errors() {
+        let report = report_for_login_error(WifiLoginError::OffHours());
+        assert_eq!(report.result, OneshotResult::OffHours);
+        let report = report_for_login_error(WifiLoginError::ServerRejected("bad".to_string()));
+        assert_eq!(report.result, OneshotResult::Rejected);
+        assert_eq!(report.message.as_deref(), Some("bad"));
+        let report = report_for_login_error(WifiLoginError::PortalUnavailable());
+        assert_eq!(report.result, OneshotResult::Error);
+        assert!(report.message.is_some());
+    }
+
+    fn parse_json(report: &OneshotReport) -> serde_json::Value {
+        serde_json::from_str(&report.format(true)).unwrap()
+    }
+
+    #[test]
+    fn json_output() {
+        assert_eq!(
+            parse_json(&OneshotReport::new(OneshotResult::Connected)),
+            json!({"result": "connected", "exit_code": 0})
+        );
+        let mut report = OneshotReport::new(OneshotResult::AuthenticationRequired);
+        report.user_ip = Some("10.0.0.2".to_string());
+        assert_eq!(
+            parse_json(&report),
+            json!({"result": "authentication_required", "exit_code": 4, "user_ip": "10.0.0.2"})
+        );
+        let report =
+            OneshotReport::new(OneshotResult::Rejected).with_message("wrong password".to_string());
+        assert_eq!(
+            parse_json(&report),
+            json!({"result": "rejected", "exit_code": 6, "message": "wrong password"})
+        );
+        for (result, name) in [
+            (OneshotResult::Error, "error"),
+            (OneshotResult::LoggedIn, "logged_in"),
+            (OneshotResult::OffHours, "off_hours"),
+            (OneshotResult::NotOnCampus, "not_on_campus"),
+            (OneshotResult::Disconnected, "disconnected"),
+        ] {
+            assert_eq!(parse_json(&OneshotReport::new(result))["result"], name);
+        }
+        // A single line, for scripts reading it line by line.
+        assert!(!report.format(true).contains('\n'));
+    }
+
+    #[test]
+    fn text_output() {
+        let mut report =
+            OneshotReport::new(OneshotResult::LoggedIn).with_message("welcome".to_string());
+        report.user_ip = Some("10.0.0.2".to_string());
+        assert_eq!(
+            report.format(false),
+            "Logged in (user ip: 10.0.0.2): welcome"
+        );
+        assert_eq!(
+            OneshotReport::new(OneshotResult::NotOnCampus).format(false),
+            "Not on campus"
+        );
+    }
+}
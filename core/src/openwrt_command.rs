@@ -0,0 +1,154 @@
+#![cfg(target_os = "linux")]
+use std::{
+    env, fs, io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
+
+use clap::{Args, Subcommand};
+use thiserror::Error;
+
+use njupt_wifi_login_configuration::config_file::is_uci_config_file;
+
+#[derive(Args, Clone, Debug)]
+pub struct OpenWrtCommand {
+    /// Name of the init script.
+    /// If not provided, the script will be named "njupt_wifi_login".
+    #[arg(short, long)]
+    name: Option<String>,
+    #[command(subcommand)]
+    subcommand: OpenWrtSubCommand,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum OpenWrtSubCommand {
+    /// Install and enable the procd init script.
+    Install,
+    /// Disable and uninstall the procd init script.
+    Uninstall,
+}
+
+#[derive(Error, Debug)]
+pub enum OpenWrtCommandError {
+    #[error("failed to get executable path")]
+    GetExePath(#[source] io::Error),
+    #[error("failed to write init script `{0}`")]
+    WriteInitScript(PathBuf, #[source] io::Error),
+    #[error("failed to remove init script `{0}`")]
+    RemoveInitScript(PathBuf, #[source] io::Error),
+    #[error("failed to run init script")]
+    RunInitScript(#[source] io::Error),
+    #[error("init script exited with {0}")]
+    InitScriptFailed(ExitStatus),
+}
+
+fn run_init_script(init_script_path: &Path, action: &str) -> Result<(), OpenWrtCommandError> {
+    let status = Command::new(init_script_path)
+        .arg(action)
+        .status()
+        .map_err(OpenWrtCommandError::RunInitScript)?;
+    if !status.success() {
+        return Err(OpenWrtCommandError::InitScriptFailed(status));
+    }
+    Ok(())
+}
+
+/// Quote a word for ash, the shell used by OpenWrt.
+fn quote_shell_word(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn generate_init_script(name: &str, exe_path: &Path, config_path: &Path) -> String {
+    let command = [
+        exe_path.to_string_lossy().as_ref(),
+        "--config",
+        config_path.to_string_lossy().as_ref(),
+        // Keep logs out of flash storage.
+        "--log-file",
+        format!("/var/log/{}.log", name).as_str(),
+    ]
+    .iter()
+    .map(|x| quote_shell_word(x))
+    .collect::<Vec<_>>()
+    .join(" ");
+    let service_triggers = if is_uci_config_file(config_path) {
+        format!(
+            "
+service_triggers() {{
+	procd_add_reload_trigger {}
+}}
+",
+            quote_shell_word(
+                config_path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .as_ref()
+            )
+        )
+    } else {
+        String::new()
+    };
+    format!(
+        "#!/bin/sh /etc/rc.common
+# Generated by `njupt_wifi_login openwrt install`.
+
+USE_PROCD=1
+START=99
+STOP=10
+
+start_service() {{
+	procd_open_instance
+	procd_set_param command {command}
+	procd_set_param file {config}
+	procd_set_param respawn ${{respawn_threshold:-3600}} ${{respawn_timeout:-5}} ${{respawn_retry:-0}}
+	procd_set_param stdout 1
+	procd_set_param stderr 1
+	procd_close_instance
+}}
+
+reload_service() {{
+	procd_send_signal {name_quoted}
+}}
+{service_triggers}",
+        config = quote_shell_word(config_path.to_string_lossy().as_ref()),
+        name_quoted = quote_shell_word(name),
+    )
+}
+
+pub fn handle_openwrt_command(
+    command: OpenWrtCommand,
+    config_path: PathBuf,
+) -> Result<(), OpenWrtCommandError> {
+    let name = command
+        .name
+        .unwrap_or_else(|| "njupt_wifi_login".to_string());
+    let init_script_path = Path::new("/etc/init.d").join(name.as_str());
+    let config_path = fs::canonicalize(&config_path).unwrap_or(config_path);
+    match command.subcommand {
+        OpenWrtSubCommand::Install => {
+            let exe_path = env::current_exe().map_err(OpenWrtCommandError::GetExePath)?;
+            let script = generate_init_script(name.as_str(), &exe_path, &config_path);
+            fs::write(&init_script_path, script)
+                .and_then(|_| {
+                    fs::set_permissions(&init_script_path, fs::Permissions::from_mode(0o755))
+                })
+                .map_err(|e| OpenWrtCommandError::WriteInitScript(init_script_path.clone(), e))?;
+            run_init_script(&init_script_path, "enable")?;
+        }
+        OpenWrtSubCommand::Uninstall => {
+            if init_script_path.exists() {
+                // The service may be not running, it is fine to fail here.
+                let _ = run_init_script(&init_script_path, "stop");
+                run_init_script(&init_script_path, "disable")?;
+            }
+            match fs::remove_file(&init_script_path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(OpenWrtCommandError::RemoveInitScript(init_script_path, e)),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,288 @@
+#![cfg(target_os = "linux")]
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
+
+use clap::{Args, Subcommand};
+use njupt_wifi_login_configuration::config_path::CONFIG_DIR_NAME;
+use thiserror::Error;
+
+#[derive(Args, Clone, Debug)]
+pub struct SystemdCommand {
+    /// Name of the unit.
+    /// If not provided, the unit will be named "njupt_wifi_login".
+    #[arg(short, long)]
+    name: Option<String>,
+    /// Manage the unit of the calling user instead of the system.
+    #[arg(long, default_value_t = false)]
+    user: bool,
+    #[command(subcommand)]
+    subcommand: SystemdSubCommand,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum SystemdSubCommand {
+    /// Install and enable the unit.
+    Install,
+    /// Disable and uninstall the unit.
+    Uninstall,
+}
+
+#[derive(Error, Debug)]
+pub enum SystemdCommandError {
+    #[error("failed to get executable path")]
+    GetExePath(#[source] io::Error),
+    #[error("failed to locate the user unit directory")]
+    LocateUserUnitDirectory,
+    #[error("failed to write unit file `{0}`")]
+    WriteUnitFile(PathBuf, #[source] io::Error),
+    #[error("failed to remove unit file `{0}`")]
+    RemoveUnitFile(PathBuf, #[source] io::Error),
+    #[error("failed to run systemctl")]
+    RunSystemctl(#[source] io::Error),
+    #[error("systemctl exited with {0}")]
+    SystemctlFailed(ExitStatus),
+}
+
+fn unit_directory(user: bool) -> Result<PathBuf, SystemdCommandError> {
+    if !user {
+        return Ok(PathBuf::from("/etc/systemd/system"));
+    }
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .ok_or(SystemdCommandError::LocateUserUnitDirectory)?;
+    Ok(config_home.join("systemd").join("user"))
+}
+
+fn systemctl(user: bool, args: &[&str]) -> Result<(), SystemdCommandError> {
+    let mut command = Command::new("systemctl");
+    if user {
+        command.arg("--user");
+    }
+    let status = command
+        .args(args)
+        .status()
+        .map_err(SystemdCommandError::RunSystemctl)?;
+    if !status.success() {
+        return Err(SystemdCommandError::SystemctlFailed(status));
+    }
+    Ok(())
+}
+
+/// Quote an argument for `ExecStart=`, see "COMMAND LINES" in systemd.service(5).
+fn quote_exec_arg(arg: &str) -> String {
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for c in arg.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            '$' => quoted.push_str("$$"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quote a path for options like `ReadWritePaths=`, where only specifiers are expanded.
+fn quote_path(path: &Path) -> String {
+    format!("\"{}\"", path.to_string_lossy().replace('%', "%%"))
+}
+
+fn generate_unit(name: &str, user: bool, exe_path: &Path, config_path: &Path) -> String {
+    let working_directory = config_path.parent().unwrap_or(Path::new("/"));
+    let exec_start = [
+        exe_path.to_string_lossy().as_ref(),
+        "--directory",
+        working_directory.to_string_lossy().as_ref(),
+        "--config",
+        config_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .as_ref(),
+    ]
+    .iter()
+    .map(|x| quote_exec_arg(x))
+    .collect::<Vec<_>>()
+    .join(" ");
+
+    let mut unit = format!(
+        "[Unit]
+Description=NJUPT WiFi Login Service ({name})
+After=network.target
+
+[Service]
+Type=notify
+NotifyAccess=main
+ExecStart={exec_start}
+ExecReload=/bin/kill -HUP $MAINPID
+Restart=on-failure
+RestartSec=5
+WatchdogSec=60
+NoNewPrivileges=yes
+StateDirectory={CONFIG_DIR_NAME}
+"
+    );
+    if !user {
+        // Sandboxing options which are only available to system units.
+        // CAP_NET_RAW is kept for binding sockets to an interface.
+        unit.push_str(&format!(
+            "CapabilityBoundingSet=CAP_NET_RAW
+ProtectSystem=strict
+ProtectHome=read-only
+ReadWritePaths={}
+PrivateTmp=yes
+PrivateDevices=yes
+ProtectKernelTunables=yes
+ProtectKernelModules=yes
+ProtectKernelLogs=yes
+ProtectControlGroups=yes
+ProtectClock=yes
+ProtectHostname=yes
+RestrictAddressFamilies=AF_UNIX AF_INET AF_INET6 AF_NETLINK
+RestrictNamespaces=yes
+RestrictRealtime=yes
+RestrictSUIDSGID=yes
+LockPersonality=yes
+MemoryDenyWriteExecute=yes
+SystemCallArchitectures=native
+",
+            quote_path(working_directory)
+        ));
+    }
+    unit.push_str(&format!(
+        "
+[Install]
+WantedBy={}
+",
+        if user {
+            "default.target"
+        } else {
+            "multi-user.target"
+        }
+    ));
+    unit
+}
+
+pub fn handle_systemd_command(
+    command: SystemdCommand,
+    config_path: PathBuf,
+) -> Result<(), SystemdCommandError> {
+    let unit_name = format!(
+        "{}.service",
+        command.name.as_deref().unwrap_or("njupt_wifi_login")
+    );
+    let unit_path = unit_directory(command.user)?.join(unit_name.as_str());
+    let config_path = fs::canonicalize(&config_path).unwrap_or(config_path);
+    match command.subcommand {
+        SystemdSubCommand::Install => {
+            let exe_path = env::current_exe().map_err(SystemdCommandError::GetExePath)?;
+            let unit = generate_unit(
+                unit_name.trim_end_matches(".service"),
+                command.user,
+                &exe_path,
+                &config_path,
+            );
+            if let Some(parent) = unit_path.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| SystemdCommandError::WriteUnitFile(unit_path.clone(), e))?;
+            }
+            fs::write(&unit_path, unit)
+                .map_err(|e| SystemdCommandError::WriteUnitFile(unit_path.clone(), e))?;
+            systemctl(command.user, &["daemon-reload"])?;
+            systemctl(command.user, &["enable", unit_name.as_str()])?;
+        }
+        SystemdSubCommand::Uninstall => {
+            // The unit may be not enabled or running, it is fine to fail here.
+            let _ = systemctl(command.user, &["disable", "--now", unit_name.as_str()]);
+            match fs::remove_file(&unit_path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(SystemdCommandError::RemoveUnitFile(unit_path, e)),
+            }
+            systemctl(command.user, &["daemon-reload"])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_exec_arg_escapes() {
+        assert_eq!(quote_exec_arg("plain"), r#""plain""#);
+        assert_eq!(quote_exec_arg("with space"), r#""with space""#);
+        assert_eq!(quote_exec_arg("100%"), r#""100%%""#);
+        assert_eq!(quote_exec_arg("$HOME"), r#""$$HOME""#);
+        assert_eq!(quote_exec_arg(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_exec_arg(r"C:\dir\"), r#""C:\\dir\\""#);
+        assert_eq!(quote_exec_arg(""), r#""""#);
+    }
+
+    #[test]
+    fn quote_path_escapes_specifiers_only() {
+        assert_eq!(
+            quote_path(Path::new("/srv/50% off/$x")),
+            r#""/srv/50%% off/$x""#
+        );
+    }
+
+    fn exec_start(unit: &str) -> &str {
+        unit.lines()
+            .find_map(|x| x.strip_prefix("ExecStart="))
+            .unwrap()
+    }
+
+    #[test]
+    fn system_unit() {
+        let unit = generate_unit(
+            "njupt_wifi_login",
+            false,
+            Path::new("/opt/njupt wifi/njupt_wifi_login"),
+            Path::new("/etc/njupt 100%/njupt_wifi.yml"),
+        );
+        assert!(unit.contains("Description=NJUPT WiFi Login Service (njupt_wifi_login)\n"));
+        assert_eq!(
+            exec_start(&unit),
+            r#""/opt/njupt wifi/njupt_wifi_login" "--directory" "/etc/njupt 100%%" "--config" "njupt_wifi.yml""#
+        );
+        assert!(unit.contains("Type=notify\n"));
+        assert!(unit.contains("WatchdogSec=60\n"));
+        assert!(unit.contains(&format!("StateDirectory={}\n", CONFIG_DIR_NAME)));
+        assert!(unit.contains("ProtectSystem=strict\n"));
+        assert!(unit.contains("ReadWritePaths=\"/etc/njupt 100%%\"\n"));
+        assert!(unit.contains("CapabilityBoundingSet=CAP_NET_RAW\n"));
+        assert!(unit.ends_with("[Install]\nWantedBy=multi-user.target\n"));
+    }
+
+    #[test]
+    fn user_unit() {
+        let unit = generate_unit(
+            "wifi",
+            true,
+            Path::new("/home/me/bin/njupt_wifi_login"),
+            Path::new("/home/me/.config/njupt_wifi_login/$config.yml"),
+        );
+        assert!(unit.contains("Description=NJUPT WiFi Login Service (wifi)\n"));
+        assert_eq!(
+            exec_start(&unit),
+            r#""/home/me/bin/njupt_wifi_login" "--directory" "/home/me/.config/njupt_wifi_login" "--config" "$$config.yml""#
+        );
+        // System-only sandboxing is left out.
+        assert!(!unit.contains("ProtectSystem="));
+        assert!(!unit.contains("ReadWritePaths="));
+        assert!(!unit.contains("CapabilityBoundingSet="));
+        assert!(unit.ends_with("[Install]\nWantedBy=default.target\n"));
+    }
+}
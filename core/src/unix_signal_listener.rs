@@ -0,0 +1,57 @@
+#![cfg(unix)]
+use crate::app_main::ActionInfo;
+use crate::history::Trigger;
+use log::info;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::task::JoinHandle;
+
+#[must_use]
+pub struct UnixSignalListenerHandle {
+    handle_polling: JoinHandle<()>,
+}
+impl UnixSignalListenerHandle {
+    /// Map signals to actions:
+    /// SIGTERM/SIGINT to stop gracefully, SIGHUP to reload config and SIGUSR1 to check immediately.
+    pub fn register<F>(func: F) -> Result<Self, Box<dyn std::error::Error + Sync + Send>>
+    where
+        F: Fn(ActionInfo) + Send + 'static,
+    {
+        let mut sigterm = signal(SignalKind::terminate())?;
+        let mut sigint = signal(SignalKind::interrupt())?;
+        let mut sighup = signal(SignalKind::hangup())?;
+        let mut sigusr1 = signal(SignalKind::user_defined1())?;
+        let handle_polling = tokio::spawn(async move {
+            loop {
+                let action = tokio::select! {
+                    Some(()) = sigterm.recv() => {
+                        info!("Received SIGTERM");
+                        ActionInfo::Stop()
+                    }
+                    Some(()) = sigint.recv() => {
+                        info!("Received SIGINT");
+                        ActionInfo::Stop()
+                    }
+                    Some(()) = sighup.recv() => {
+                        info!("Received SIGHUP");
+                        ActionInfo::ReloadConfig()
+                    }
+                    Some(()) = sigusr1.recv() => {
+                        info!("Received SIGUSR1");
+                        ActionInfo::ForceCheckAndLogin(Trigger::Signal)
+                    }
+                    else => break,
+                };
+                func(action);
+            }
+        });
+        Ok(Self { handle_polling })
+    }
+
+    pub fn abort(&self) {
+        self.handle_polling.abort();
+    }
+
+    pub async fn join(self) {
+        let _ = self.handle_polling.await;
+    }
+}
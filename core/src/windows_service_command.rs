@@ -2,6 +2,7 @@
 use std::{
     env,
     ffi::{OsStr, OsString},
+    path::PathBuf,
 };
 
 use clap::{Args, Subcommand};
@@ -18,17 +19,19 @@ use windows_service::{
     service_manager::{ServiceManager, ServiceManagerAccess},
 };
 
-use crate::{app_main::AppMain, app_service_events::AppServiceEvents};
+use crate::{app_main::AppMain, app_service_events::AppServiceEvents, history::History};
 
 struct ServiceGlobals {
     config: LoginConfig,
+    config_path: Option<PathBuf>,
+    history: History,
     service_name: String,
 }
 static mut SERVICE_GLOBALS: Option<ServiceGlobals> = None;
 /// Entrypoint for the Windows service.
 pub fn service_main(_arguments: Vec<OsString>) {
     let globals = unsafe { SERVICE_GLOBALS.take().unwrap() };
-    let app = AppMain::new(globals.config);
+    let app = AppMain::new(globals.config, globals.config_path, globals.history);
     app.run(AppServiceEvents::new(globals.service_name).unwrap())
         .unwrap();
 }
@@ -85,6 +88,8 @@ pub enum ServiceCommandError {
 pub fn handle_service_command(
     command: ServiceCommand,
     my_config: LoginConfig,
+    config_path: Option<PathBuf>,
+    history: History,
 ) -> Result<(), ServiceCommandError> {
     let service_name = command
         .name
@@ -169,6 +174,8 @@ pub fn handle_service_command(
         ServiceSubCommand::Main => {
             let globals = ServiceGlobals {
                 config: my_config,
+                config_path,
+                history,
                 service_name: service_name.to_string(),
             };
             unsafe { SERVICE_GLOBALS = Some(globals) };